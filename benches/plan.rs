@@ -0,0 +1,36 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{write_jpeg, Exif};
+use criterion::{criterion_group, criterion_main, Criterion};
+use datier::{find_files, plan_renames, RenameOptions, Template, TextValues, DEFAULT_EXTENSIONS};
+
+  // enough files to make the per-file work show, with a few per minute so that there are ties to break
+const FILE_COUNT: usize = 2000;
+
+fn synthetic_dir()->tempfile::TempDir {
+  let dir = tempfile::tempdir().unwrap();
+  for index in 0..FILE_COUNT {
+    let datetime = format!("2021:03:{:02} {:02}:{:02}:00", 1 + index / 600, index / 60 % 10, index % 60 / 3);
+    write_jpeg(dir.path(), &format!("IMG_{:04}.jpg", index), &Exif::new().datetime(&datetime).subsec(&format!("{}", index % 3)));
+  }
+  dir
+}
+
+fn bench_plan(c: &mut Criterion) {
+  let dir = synthetic_dir();
+  let extensions: Vec<String> = DEFAULT_EXTENSIONS.iter().map(|ext| ext.to_string()).collect();
+  let paths = find_files(dir.path(), &extensions, false, None, false).unwrap().paths;
+  let opts = RenameOptions { target_dir: Some(dir.path().to_path_buf()), input_dir: dir.path().to_path_buf(), ..RenameOptions::default() };
+
+  c.bench_function("find_files", |b| b.iter(|| find_files(dir.path(), &extensions, false, None, false).unwrap()));
+  c.bench_function("plan_renames", |b| b.iter(|| plan_renames(&paths, &opts)));
+
+  let template = Template::default();
+  let datetime = exif::DateTime { year: 2021, month: 3, day: 15, hour: 9, minute: 30, second: 0, nanosecond: None, offset: None };
+  let text = TextValues::default();
+  c.bench_function("expand_template", |b| b.iter(|| template.expand(&datetime, 42, &text)));
+}
+
+criterion_group!(benches, bench_plan);
+criterion_main!(benches);
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::metadata::{get_metadata, Dimensions, FileMetadata, GetDateTimeError, GpsPosition, ReadOptions, Subseconds};
+
+#[derive(Debug, Snafu)]
+pub enum CacheError {
+  #[snafu(display("Could not write cache {}: {}", path.display(), source))]
+  CacheWrite {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("Could not remove cache {}: {}", path.display(), source))]
+  CacheRemove {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+}
+
+  // the metadata read from files in earlier runs, so that repeated runs over the same folder don't have to read every file again
+  // an entry is only used while the file's modification time and size stay the same
+  // the cache is kept in the user's cache directory, with one file per input folder
+pub struct MetadataCache {
+  path: PathBuf,
+    // the read options the cached metadata was read with, as they change the results
+  read_options: String,
+  loaded: HashMap<PathBuf, CacheEntry>,
+    // the entries used or added in this run, which are the ones that are written back
+  used: Mutex<HashMap<PathBuf, CacheEntry>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+  modified: (u64, u32),
+  size: u64,
+  datetime: CachedDateTime,
+  subseconds: Subseconds,
+  offset: Option<i16>,
+  gps: Option<GpsPosition>,
+  make: Option<String>,
+  model: Option<String>,
+  lens: Option<String>,
+  focal_length: Option<f64>,
+  image_number: Option<u32>,
+  dimensions: Option<Dimensions>,
+}
+
+  // the fields of exif::DateTime, which can't be serialized itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedDateTime {
+  year: u16,
+  month: u8,
+  day: u8,
+  hour: u8,
+  minute: u8,
+  second: u8,
+  nanosecond: Option<u32>,
+  offset: Option<i16>,
+}
+
+impl CacheEntry {
+  fn new(modified: (u64, u32), size: u64, metadata: &FileMetadata)->CacheEntry {
+    let datetime = &metadata.datetime;
+    CacheEntry {
+      modified,
+      size,
+      datetime: CachedDateTime {
+        year: datetime.year,
+        month: datetime.month,
+        day: datetime.day,
+        hour: datetime.hour,
+        minute: datetime.minute,
+        second: datetime.second,
+        nanosecond: datetime.nanosecond,
+        offset: datetime.offset,
+      },
+      subseconds: metadata.subseconds.clone(),
+      offset: metadata.offset,
+      gps: metadata.gps,
+      make: metadata.make.clone(),
+      model: metadata.model.clone(),
+      lens: metadata.lens.clone(),
+      focal_length: metadata.focal_length,
+      image_number: metadata.image_number,
+      dimensions: metadata.dimensions,
+    }
+  }
+
+  fn metadata(&self)->FileMetadata {
+    let datetime = &self.datetime;
+    FileMetadata {
+      datetime: exif::DateTime {
+        year: datetime.year,
+        month: datetime.month,
+        day: datetime.day,
+        hour: datetime.hour,
+        minute: datetime.minute,
+        second: datetime.second,
+        nanosecond: datetime.nanosecond,
+        offset: datetime.offset,
+      },
+      subseconds: self.subseconds.clone(),
+      offset: self.offset,
+      gps: self.gps,
+      make: self.make.clone(),
+      model: self.model.clone(),
+      lens: self.lens.clone(),
+      focal_length: self.focal_length,
+      image_number: self.image_number,
+      dimensions: self.dimensions,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+  read_options: String,
+  entries: HashMap<PathBuf, CacheEntry>,
+}
+
+  // where the cache for the files in dir is kept, or None if the system has no cache directory
+pub fn cache_path(dir: &Path)->Option<PathBuf> {
+  let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+  let mut hasher = DefaultHasher::new();
+  dir.hash(&mut hasher);
+  Some(dirs::cache_dir()?.join("datier").join(format!("{:016x}.json", hasher.finish())))
+}
+
+impl MetadataCache {
+    // loads the cache at path. a missing or unreadable cache, or one read with other options, is started over
+  pub fn load(path: PathBuf, read: &ReadOptions)->MetadataCache {
+    let read_options = format!("{:?}", read);
+    let loaded = fs::read_to_string(&path).ok()
+      .and_then(|contents| serde_json::from_str::<CacheFile>(&contents).ok())
+      .filter(|cache| cache.read_options == read_options)
+      .map(|cache| cache.entries)
+      .unwrap_or_default();
+    MetadataCache { path, read_options, loaded, used: Mutex::new(HashMap::new()) }
+  }
+
+    // like get_metadata, but returns the cached metadata if the file hasn't changed since it was cached
+    // files that can't be read aren't cached, so the error is found again on the next run
+  pub fn get_metadata(&self, path: &Path, opts: &ReadOptions)->Result<FileMetadata, GetDateTimeError> {
+    let (modified, size) = match file_stamp(path) {
+      Some(stamp) => stamp,
+      None => return get_metadata(path, opts),
+    };
+    if let Some(entry) = self.loaded.get(path).filter(|entry| entry.modified == modified && entry.size == size) {
+      self.used.lock().unwrap().insert(path.to_path_buf(), entry.clone());
+      return Ok(entry.metadata());
+    }
+
+    let metadata = get_metadata(path, opts)?;
+    self.used.lock().unwrap().insert(path.to_path_buf(), CacheEntry::new(modified, size, &metadata));
+    Ok(metadata)
+  }
+
+    // keeps the entry of a file that was moved, as moving doesn't change its modification time or size
+  pub fn renamed(&self, source: &Path, dest: &Path) {
+    let mut used = self.used.lock().unwrap();
+    if let Some(entry) = used.remove(source) {
+      used.insert(dest.to_path_buf(), entry);
+    }
+  }
+
+    // writes the entries of the files seen in this run, dropping those of files that are gone or were renamed
+  pub fn save(&self)->Result<(), CacheError> {
+    let cache = CacheFile { read_options: self.read_options.clone(), entries: self.used.lock().unwrap().clone() };
+    if let Some(dir) = self.path.parent() {
+      fs::create_dir_all(dir).context(CacheWrite { path: self.path.clone() })?;
+    }
+    let contents = serde_json::to_string(&cache).expect("cache entries are always serializable");
+    fs::write(&self.path, contents).context(CacheWrite { path: self.path.clone() })
+  }
+
+  pub fn clear(path: &Path)->Result<(), CacheError> {
+    match fs::remove_file(path) {
+      Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+      result => result.context(CacheRemove { path: path.to_path_buf() }),
+    }
+  }
+}
+
+  // the modification time (as seconds and nanoseconds since 1970) and size of a file
+fn file_stamp(path: &Path)->Option<((u64, u32), u64)> {
+  let metadata = fs::metadata(path).ok()?;
+  let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+  Some(((modified.as_secs(), modified.subsec_nanos()), metadata.len()))
+}
@@ -0,0 +1,105 @@
+use datier::{PlanAction, RenamePlan};
+use snafu::{ensure, ResultExt, Snafu};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Snafu)]
+pub enum CompareError {
+  #[snafu(display("Could not read report {}: {}", path.display(), source))]
+  ReportReadFailed {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("{} is not a report written by --report", path.display()))]
+  NotAReport {
+    path: PathBuf,
+  },
+}
+
+  // the files of a report written by --report in an earlier run, with the new name each of them got
+  // skipped files have no new name
+pub struct PreviousReport {
+  names: BTreeMap<String, Option<String>>,
+}
+
+  // how a file's plan differs from the report, by the paths as they were given in both runs
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    // found now, but not in the report
+  Added { source: String, now: Option<String> },
+    // in the report, but not found now
+  Removed { source: String },
+  NameChanged { source: String, before: Option<String>, now: Option<String> },
+}
+
+impl PreviousReport {
+  pub fn read(path: &Path)->Result<PreviousReport, CompareError> {
+    let contents = fs::read_to_string(path).context(ReportReadFailed { path: path.to_path_buf() })?;
+    let mut records = csv_records(&contents).into_iter();
+    let header = records.next().unwrap_or_default();
+    ensure!(header.len() >= 2 && header[0] == "original" && header[1] == "new", NotAReport { path: path.to_path_buf() });
+
+    let names = records
+      .filter(|record| record.len() >= 2)
+      .map(|mut record| {
+        let new = record.swap_remove(1);
+        let original = record.swap_remove(0);
+        (original, Some(new).filter(|new| !new.is_empty()))
+      })
+      .collect();
+    Ok(PreviousReport { names })
+  }
+
+    // the files that are new, gone or named differently, in the order of their paths
+  pub fn changes(&self, plan: &[RenamePlan])->Vec<Change> {
+    let planned: BTreeMap<String, Option<String>> = plan.iter().map(|entry| {
+      let now = match entry.action {
+        PlanAction::Rename(ref dest) => Some(dest.to_string_lossy().into_owned()),
+        PlanAction::Skip(_) => None,
+      };
+      (entry.source.to_string_lossy().into_owned(), now)
+    }).collect();
+
+    let mut changes: Vec<Change> = planned.iter().filter_map(|(source, now)| match self.names.get(source) {
+      None => Some(Change::Added { source: source.clone(), now: now.clone() }),
+      Some(before) if before != now => Some(Change::NameChanged { source: source.clone(), before: before.clone(), now: now.clone() }),
+      Some(_) => None,
+    }).collect();
+    changes.extend(self.names.keys()
+      .filter(|source| !planned.contains_key(*source))
+      .map(|source| Change::Removed { source: source.clone() }));
+    changes
+  }
+}
+
+  // splits CSV text as written by the report into its records and fields
+  // quoted fields can hold commas, line breaks and doubled quotes
+fn csv_records(text: &str)->Vec<Vec<String>> {
+  let mut records = Vec::new();
+  let mut record = Vec::new();
+  let mut field = String::new();
+  let mut quoted = false;
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' if quoted && chars.peek() == Some(&'"') => {
+        chars.next();
+        field.push('"');
+      },
+      '"' => quoted = !quoted,
+      ',' if !quoted => record.push(std::mem::take(&mut field)),
+      '\r' if !quoted => {},
+      '\n' if !quoted => {
+        record.push(std::mem::take(&mut field));
+        records.push(std::mem::take(&mut record));
+      },
+      _ => field.push(c),
+    }
+  }
+  if !field.is_empty() || !record.is_empty() {
+    record.push(field);
+    records.push(record);
+  }
+  records
+}
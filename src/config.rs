@@ -0,0 +1,355 @@
+use clap::ArgMatches;
+use datier::{parse_duration, parse_name_pattern, parse_offset, DateTag, Dimensions, GroupBy, NameCase, NumberingScope, OnError, TieBreak, MAX_FIELD_WIDTH};
+use serde::Deserialize;
+use snafu::{ResultExt, Snafu};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::logger::PlanDisplay;
+
+pub const CONFIG_FILE_NAME: &str = "datier.toml";
+
+#[derive(Debug, Snafu)]
+pub enum ConfigError {
+  #[snafu(display("Could not read config file {}: {}", path.display(), source))]
+  ConfigReadFailed {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("Could not parse config file {}: {}", path.display(), source))]
+  ConfigParseFailed {
+    path: PathBuf,
+    source: toml::de::Error,
+  },
+  #[snafu(display("Invalid value in config file {}: {}", path.display(), message))]
+  InvalidConfigValue {
+    path: PathBuf,
+    message: String,
+  },
+  #[snafu(display("The config file {} in the input folder can't set {}, which is only read from the one in the user's config folder", path.display(), key))]
+  UntrustedConfigKey {
+    path: PathBuf,
+    key: &'static str,
+  },
+}
+
+  // the command line flags that turn off a flag set in the config, e.g. --no-dry-run
+  // the flags that already are a negation are turned off by their positive form
+pub const FLAG_NEGATIONS: &[(&str, &str)] = &[
+  ("log", "no-log"),
+  ("quiet", "no-quiet"),
+  ("json", "no-json"),
+  ("no-color", "color"),
+  ("dry-run", "no-dry-run"),
+  ("deep", "no-deep"),
+  ("deep-in-place", "no-deep-in-place"),
+  ("include-hidden", "no-include-hidden"),
+  ("follow-symlinks", "no-follow-symlinks"),
+  ("flat", "no-flat"),
+  ("use-offset", "no-use-offset"),
+  ("ignore-subseconds", "no-ignore-subseconds"),
+  ("copy", "no-copy"),
+  ("strict", "no-strict"),
+  ("sidecar", "no-sidecar"),
+  ("fallback-to-mtime", "no-fallback-to-mtime"),
+  ("reject-future", "no-reject-future"),
+  ("force", "no-force"),
+  ("overwrite", "no-overwrite"),
+  ("only-missing", "no-only-missing"),
+  ("continue-counter", "no-continue-counter"),
+  ("transactional", "no-transactional"),
+  ("verify", "no-verify"),
+  ("auto-width", "no-auto-width"),
+  ("normalize-ext", "no-normalize-ext"),
+  ("keep-original-name", "no-keep-original-name"),
+  ("no-cache", "cache"),
+  ("stats", "no-stats"),
+];
+
+  // default values for the command line options, using the same names
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+  ext: Option<Vec<String>>,
+  add_ext: Option<Vec<String>>,
+  sidecar_ext: Option<Vec<String>>,
+  exclude: Option<Vec<String>>,
+  format: Option<String>,
+  template_file: Option<String>,
+  prefix: Option<String>,
+  suffix: Option<String>,
+  locale: Option<String>,
+  places: Option<String>,
+  place_default: Option<String>,
+  exec: Option<String>,
+  report: Option<String>,
+  camera_default: Option<String>,
+  lens_default: Option<String>,
+  group_by: Option<String>,
+  tie_break: Option<String>,
+  numbering: Option<String>,
+  on_error: Option<String>,
+  display: Option<String>,
+  case: Option<String>,
+  dedupe: Option<String>,
+  confirm_above: Option<usize>,
+  counter_start: Option<u32>,
+  counter_width: Option<usize>,
+  date_tag: Option<Vec<String>>,
+  assume_offset: Option<String>,
+  to_timezone: Option<String>,
+  mtime_tolerance: Option<String>,
+  session_gap: Option<String>,
+  parse_name: Option<String>,
+  min_dimension: Option<String>,
+  max_dimension: Option<String>,
+  unknown_dimension: Option<String>,
+  max_depth: Option<usize>,
+  threads: Option<usize>,
+  log: bool,
+  quiet: bool,
+  json: bool,
+  no_color: bool,
+  dry_run: bool,
+  deep: bool,
+  deep_in_place: bool,
+  include_hidden: bool,
+  follow_symlinks: bool,
+  flat: bool,
+  use_offset: bool,
+  ignore_subseconds: bool,
+  copy: bool,
+  strict: bool,
+  sidecar: bool,
+  fallback_to_mtime: bool,
+  reject_future: bool,
+  force: bool,
+  overwrite: bool,
+  only_missing: bool,
+  continue_counter: bool,
+  transactional: bool,
+  verify: bool,
+  auto_width: bool,
+  normalize_ext: bool,
+  keep_original_name: bool,
+  no_cache: bool,
+  stats: bool,
+}
+
+impl Config {
+    // reads the config from the input directory if it has one, and from the user's config directory otherwise
+    // having no config file at all is fine, and results in an empty config
+  pub fn find(input_dir: Option<&Path>)->Result<Config, ConfigError> {
+    if let Some(path) = input_dir.map(|dir| dir.join(CONFIG_FILE_NAME)).filter(|path| path.is_file()) {
+        // the folder may come from a card or a download, so its config must not be able to do more than rename the files in it
+      let config = Config::read(&path)?;
+      return match config.untrusted_key() {
+        Some(key) => Err(ConfigError::UntrustedConfigKey { path, key }),
+        None => Ok(config),
+      };
+    }
+    match dirs::config_dir().map(|dir| dir.join("datier").join(CONFIG_FILE_NAME)).filter(|path| path.is_file()) {
+      Some(path) => Config::read(&path),
+      None => Ok(Config::default()),
+    }
+  }
+
+  pub fn read(path: &Path)->Result<Config, ConfigError> {
+    let contents = fs::read_to_string(path).context(ConfigReadFailed { path: path.to_path_buf() })?;
+    let config: Config = toml::from_str(&contents).context(ConfigParseFailed { path: path.to_path_buf() })?;
+    config.validate().map_err(|message| ConfigError::InvalidConfigValue { path: path.to_path_buf(), message })?;
+    Ok(config)
+  }
+
+    // the same checks that the command line validators do, so the values can be used in the same way
+  fn validate(&self)->Result<(), String> {
+    if let Some(ref group_by) = self.group_by {
+      group_by.parse::<GroupBy>()?;
+    }
+    if let Some(ref tie_break) = self.tie_break {
+      tie_break.parse::<TieBreak>()?;
+    }
+    if let Some(ref numbering) = self.numbering {
+      numbering.parse::<NumberingScope>()?;
+    }
+    if let Some(ref on_error) = self.on_error {
+      on_error.parse::<OnError>()?;
+    }
+    if let Some(ref display) = self.display {
+      display.parse::<PlanDisplay>()?;
+    }
+    if let Some(ref case) = self.case {
+      case.parse::<NameCase>()?;
+    }
+    if self.dedupe.as_ref().is_some_and(|dedupe| dedupe != "report" && dedupe != "delete") {
+      return Err(String::from("dedupe must be report or delete"));
+    }
+    for pattern in self.exclude.iter().flatten() {
+      glob::Pattern::new(pattern).map_err(|error| format!("Invalid exclude pattern \"{}\": {}", pattern, error))?;
+    }
+    for tag in self.date_tag.iter().flatten() {
+      tag.parse::<DateTag>()?;
+    }
+    if let Some(ref offset) = self.assume_offset {
+      parse_offset(offset)?;
+    }
+    if let Some(ref timezone) = self.to_timezone {
+      timezone.parse::<chrono_tz::Tz>().map_err(|_| format!("Unknown time zone \"{}\", expected e.g. Europe/Berlin", timezone))?;
+    }
+    if let Some(ref pattern) = self.parse_name {
+      parse_name_pattern(pattern)?;
+    }
+    for dimensions in self.min_dimension.iter().chain(&self.max_dimension) {
+      dimensions.parse::<Dimensions>()?;
+    }
+    if self.unknown_dimension.as_ref().is_some_and(|policy| policy != "process" && policy != "skip") {
+      return Err(String::from("unknown-dimension must be process or skip"));
+    }
+    for duration in self.mtime_tolerance.iter().chain(&self.session_gap) {
+      parse_duration(duration)?;
+    }
+    if self.counter_width.is_some_and(|width| width == 0 || width > MAX_FIELD_WIDTH) {
+      return Err(format!("counter-width must be a number from 1 to {}", MAX_FIELD_WIDTH));
+    }
+    if self.exec.as_ref().is_some_and(|exec| exec.trim().is_empty()) {
+      return Err(String::from("exec must not be empty"));
+    }
+    if self.threads == Some(0) {
+      return Err(String::from("threads must be a positive number"));
+    }
+    Ok(())
+  }
+
+    // the first of the keys that run commands, write files elsewhere or remove or replace files
+  fn untrusted_key(&self)->Option<&'static str> {
+    if self.exec.is_some() {
+      Some("exec")
+    } else if self.report.is_some() {
+      Some("report")
+    } else if self.dedupe.as_deref() == Some("delete") {
+      Some("dedupe = \"delete\"")
+    } else if self.overwrite {
+      Some("overwrite")
+    } else {
+      None
+    }
+  }
+
+  fn flag(&self, name: &str)->bool {
+    match name {
+      "log" => self.log,
+      "quiet" => self.quiet,
+      "json" => self.json,
+      "no-color" => self.no_color,
+      "dry-run" => self.dry_run,
+      "deep" => self.deep,
+      "deep-in-place" => self.deep_in_place,
+      "include-hidden" => self.include_hidden,
+      "follow-symlinks" => self.follow_symlinks,
+      "flat" => self.flat,
+      "use-offset" => self.use_offset,
+      "ignore-subseconds" => self.ignore_subseconds,
+      "copy" => self.copy,
+      "strict" => self.strict,
+      "sidecar" => self.sidecar,
+      "fallback-to-mtime" => self.fallback_to_mtime,
+      "reject-future" => self.reject_future,
+      "force" => self.force,
+      "overwrite" => self.overwrite,
+      "only-missing" => self.only_missing,
+      "continue-counter" => self.continue_counter,
+      "transactional" => self.transactional,
+      "verify" => self.verify,
+      "auto-width" => self.auto_width,
+      "normalize-ext" => self.normalize_ext,
+      "keep-original-name" => self.keep_original_name,
+      "no-cache" => self.no_cache,
+      "stats" => self.stats,
+      _ => false,
+    }
+  }
+
+  fn value(&self, name: &str)->Option<String> {
+    match name {
+      "format" => self.format.clone(),
+      "template-file" => self.template_file.clone(),
+      "prefix" => self.prefix.clone(),
+      "suffix" => self.suffix.clone(),
+      "locale" => self.locale.clone(),
+      "places" => self.places.clone(),
+      "place-default" => self.place_default.clone(),
+      "exec" => self.exec.clone(),
+      "report" => self.report.clone(),
+      "camera-default" => self.camera_default.clone(),
+      "lens-default" => self.lens_default.clone(),
+      "group-by" => self.group_by.clone(),
+      "tie-break" => self.tie_break.clone(),
+      "numbering" => self.numbering.clone(),
+      "on-error" => self.on_error.clone(),
+      "display" => self.display.clone(),
+      "case" => self.case.clone(),
+      "dedupe" => self.dedupe.clone(),
+      "confirm-above" => self.confirm_above.map(|limit| limit.to_string()),
+      "counter-start" => self.counter_start.map(|start| start.to_string()),
+      "counter-width" => self.counter_width.map(|width| width.to_string()),
+      "assume-offset" => self.assume_offset.clone(),
+      "to-timezone" => self.to_timezone.clone(),
+      "mtime-tolerance" => self.mtime_tolerance.clone(),
+      "session-gap" => self.session_gap.clone(),
+      "parse-name" => self.parse_name.clone(),
+      "min-dimension" => self.min_dimension.clone(),
+      "max-dimension" => self.max_dimension.clone(),
+      "unknown-dimension" => self.unknown_dimension.clone(),
+      "max-depth" => self.max_depth.map(|depth| depth.to_string()),
+      "threads" => self.threads.map(|threads| threads.to_string()),
+      _ => None,
+    }
+  }
+
+  fn values(&self, name: &str)->Option<Vec<String>> {
+    match name {
+      "ext" => self.ext.clone(),
+      "add-ext" => self.add_ext.clone(),
+      "sidecar-ext" => self.sidecar_ext.clone(),
+      "exclude" => self.exclude.clone(),
+      "date-tag" => self.date_tag.clone(),
+      _ => None,
+    }
+  }
+}
+
+  // the command line arguments, falling back to the config for the ones that weren't given
+pub struct Settings<'a> {
+  matches: &'a ArgMatches<'a>,
+  config: Config,
+}
+
+impl<'a> Settings<'a> {
+  pub fn new(matches: &'a ArgMatches<'a>, config: Config)->Settings<'a> {
+    Settings { matches, config }
+  }
+
+    // a flag from the config is turned off by its negation on the command line
+  pub fn is_present(&self, name: &str)->bool {
+    if self.matches.is_present(name) {
+      return true;
+    }
+    let negated = FLAG_NEGATIONS.iter().any(|&(flag, negation)| flag == name && self.matches.is_present(negation));
+    !negated && self.config.flag(name)
+  }
+
+  pub fn on_command_line(&self, name: &str)->bool {
+    self.matches.is_present(name)
+  }
+
+  pub fn value_of(&self, name: &str)->Option<String> {
+    self.matches.value_of(name).map(String::from).or_else(|| self.config.value(name))
+  }
+
+  pub fn values_of(&self, name: &str)->Option<Vec<String>> {
+    match self.matches.values_of(name) {
+      Some(values) => Some(values.map(String::from).collect()),
+      None => self.config.values(name),
+    }
+  }
+}
@@ -0,0 +1,245 @@
+use std::cmp::Ordering;
+
+  // wrapper type around DateTime that adds ordering based on the time
+  // for ordering and equality, a missing offset counts as +00:00, so datetimes without offsets compare by their local time
+#[derive(Debug)]
+pub struct OrdDateTime(pub exif::DateTime);
+
+impl From<exif::DateTime> for OrdDateTime {
+  fn from(datetime: exif::DateTime)->Self {
+    Self(datetime)
+  }
+}
+
+  // exif::DateTime doesn't implement Clone itself
+impl Clone for OrdDateTime {
+  fn clone(&self)->Self {
+    Self(exif::DateTime {
+      year: self.0.year,
+      month: self.0.month,
+      day: self.0.day,
+      hour: self.0.hour,
+      minute: self.0.minute,
+      second: self.0.second,
+      nanosecond: self.0.nanosecond,
+      offset: self.0.offset,
+    })
+  }
+}
+
+impl std::fmt::Display for OrdDateTime {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>)->std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl Ord for OrdDateTime {
+  fn cmp(&self, other: &Self)->Ordering {
+    self.timestamp().cmp(&other.timestamp())
+      .then(self.0.nanosecond.cmp(&other.0.nanosecond))
+  }
+}
+
+impl PartialOrd for OrdDateTime {
+  fn partial_cmp(&self, other: &Self)->Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl PartialEq for OrdDateTime {
+  fn eq(&self, other: &Self)->bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for OrdDateTime {}
+
+impl OrdDateTime {
+    // compares the local dates, regardless of offset
+  pub fn date_eq(&self, other: &OrdDateTime)->bool {
+    self.0.year == other.0.year && self.0.month == other.0.month && self.0.day == other.0.day
+  }
+
+    // orders by the local dates only
+  pub fn date_cmp(&self, other: &OrdDateTime)->Ordering {
+    self.0.year.cmp(&other.0.year)
+      .then(self.0.month.cmp(&other.0.month))
+      .then(self.0.day.cmp(&other.0.day))
+  }
+
+    // parses a date like "2021-03-15", at midnight of that day
+  pub fn parse_date(date: &str)->Result<OrdDateTime, String> {
+    let invalid = || format!("Invalid date \"{}\" (expected YYYY-MM-DD)", date);
+    let parts: Vec<&str> = date.trim().split('-').collect();
+    if parts.len() != 3 {
+      return Err(invalid());
+    }
+
+    let year: u16 = parts[0].parse().map_err(|_| invalid())?;
+    let month: u8 = parts[1].parse().map_err(|_| invalid())?;
+    let day: u8 = parts[2].parse().map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+      return Err(invalid());
+    }
+
+    Ok(OrdDateTime(exif::DateTime {
+      year,
+      month,
+      day,
+      hour: 0,
+      minute: 0,
+      second: 0,
+      nanosecond: None,
+      offset: None,
+    }))
+  }
+
+    // seconds since 1970-01-01 00:00:00 UTC, treating a missing offset as +00:00
+  pub fn timestamp(&self)->i64 {
+    let days = days_from_civil(i64::from(self.0.year), u32::from(self.0.month), u32::from(self.0.day));
+    let seconds = i64::from(self.0.hour) * 3600 + i64::from(self.0.minute) * 60 + i64::from(self.0.second);
+    days * 86400 + seconds - i64::from(self.0.offset.unwrap_or(0)) * 60
+  }
+}
+
+pub fn is_leap_year(year: u16)->bool {
+  (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+pub fn days_in_month(year: u16, month: u8)->u8 {
+  match month {
+    2 if is_leap_year(year) => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  }
+}
+
+  // number of days since 1970-01-01 in the proleptic gregorian calendar
+  // see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+pub fn days_from_civil(year: i64, month: u32, day: u32)->i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = if year >= 0 { year } else { year - 399 } / 400;
+  let year_of_era = year - era * 400;
+  let month = i64::from(month);
+  let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146097 + day_of_era - 719468
+}
+
+  // the inverse of days_from_civil, returning (year, month, day)
+  // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub fn civil_from_days(days: i64)->(i64, u32, u32) {
+  let days = days + 719468;
+  let era = if days >= 0 { days } else { days - 146096 } / 146097;
+  let day_of_era = days - era * 146097;
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+  let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+  (year, month, day)
+}
+
+  // moves the local time of datetime by the given number of seconds, rolling over into other days, months and years as needed
+  // the offset and subseconds are kept as they are
+pub fn shift_datetime(datetime: &mut exif::DateTime, seconds: i64) {
+  let days = days_from_civil(i64::from(datetime.year), u32::from(datetime.month), u32::from(datetime.day));
+  let local = days * 86400 + i64::from(datetime.hour) * 3600 + i64::from(datetime.minute) * 60 + i64::from(datetime.second) + seconds;
+
+  let (year, month, day) = civil_from_days(local.div_euclid(86400));
+  let time_of_day = local.rem_euclid(86400);
+  datetime.year = year.clamp(0, i64::from(u16::MAX)) as u16;
+  datetime.month = month as u8;
+  datetime.day = day as u8;
+  datetime.hour = (time_of_day / 3600) as u8;
+  datetime.minute = (time_of_day / 60 % 60) as u8;
+  datetime.second = (time_of_day % 60) as u8;
+}
+
+  // parses a time shift like "+3h", "-2d" or "+1h30m" into seconds
+pub fn parse_shift(shift: &str)->Result<i64, String> {
+  let invalid = || format!("Invalid shift \"{}\" (expected e.g. +3h, -2d or +1h30m)", shift);
+  let trimmed = shift.trim();
+  let (sign, rest) = match trimmed.chars().next() {
+    Some('+') => (1, &trimmed[1..]),
+    Some('-') => (-1, &trimmed[1..]),
+    _ => return Err(invalid()),
+  };
+  parse_duration(rest).map(|seconds| sign * seconds).map_err(|_| invalid())
+}
+
+  // parses a duration like "7d", "12h" or "1h30m" into seconds
+  // the units are d (days), h (hours), m (minutes) and s (seconds)
+pub fn parse_duration(duration: &str)->Result<i64, String> {
+  let invalid = || format!("Invalid duration \"{}\" (expected e.g. 7d, 12h or 1h30m)", duration);
+  let trimmed = duration.trim();
+  if trimmed.is_empty() {
+    return Err(invalid());
+  }
+
+  let mut total: i64 = 0;
+  let mut number = String::new();
+  for c in trimmed.chars() {
+    if c.is_ascii_digit() {
+      number.push(c);
+      continue;
+    }
+
+    let unit = match c.to_ascii_lowercase() {
+      'd' => 86400,
+      'h' => 3600,
+      'm' => 60,
+      's' => 1,
+      _ => return Err(invalid()),
+    };
+    let value: i64 = number.parse().map_err(|_| invalid())?;
+    total = value.checked_mul(unit).and_then(|value| total.checked_add(value)).ok_or_else(invalid)?;
+    number.clear();
+  }
+  if !number.is_empty() {
+    return Err(invalid()); // a number without a unit
+  }
+
+  Ok(total)
+}
+
+  // parses a time zone offset like "+02:00", "-0530", "+9" or "Z" into minutes
+pub fn parse_offset(offset: &str)->Result<i16, String> {
+  let invalid = || format!("Invalid offset \"{}\" (expected e.g. +02:00)", offset);
+  let trimmed = offset.trim();
+  if trimmed.eq_ignore_ascii_case("z") || trimmed.eq_ignore_ascii_case("utc") {
+    return Ok(0);
+  }
+
+  let (sign, rest) = match trimmed.chars().next() {
+    Some('+') => (1, &trimmed[1..]),
+    Some('-') => (-1, &trimmed[1..]),
+    _ => return Err(invalid()),
+  };
+
+    // only digits and at most one colon, so the parts can't have signs of their own and splitting stays on char boundaries
+  if !rest.chars().all(|c| c.is_ascii_digit() || c == ':') || rest.matches(':').count() > 1 {
+    return Err(invalid());
+  }
+  let (hours, minutes) = match rest.split_once(':') {
+    Some(parts) => parts,
+    None if rest.len() > 2 => rest.split_at(rest.len() - 2),
+    None => (rest, "0"),
+  };
+
+  let hours: u16 = hours.parse().map_err(|_| invalid())?;
+  let minutes: u16 = minutes.parse().map_err(|_| invalid())?;
+  if hours > 14 || minutes >= 60 {
+    return Err(invalid());
+  }
+  Ok(sign * (hours * 60 + minutes) as i16)
+}
+
+  // e.g. 120 -> "+02:00", the way offsets are written in exif data
+pub fn format_offset(offset: i16)->String {
+  let sign = if offset < 0 { '-' } else { '+' };
+  let minutes = offset.unsigned_abs();
+  format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
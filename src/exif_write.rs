@@ -0,0 +1,168 @@
+use snafu::{OptionExt, ResultExt, Snafu};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::datetime::shift_datetime;
+use crate::metadata::parse_exif_datetime;
+
+#[derive(Debug, Snafu)]
+pub enum ExifWriteError {
+  #[snafu(display("Could not read file: {}", source))]
+  ExifFileRead {
+    source: std::io::Error,
+  },
+  #[snafu(display("No exif data found"))]
+  NoExifData,
+  #[snafu(display("Writing exif data into HEIF files is not supported"))]
+  UnsupportedContainer,
+  #[snafu(display("Exif data is malformed"))]
+  MalformedExif,
+  #[snafu(display("Could not write file: {}", source))]
+  ExifFileWrite {
+    source: std::io::Error,
+  },
+}
+
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+const TYPE_ASCII: u16 = 2;
+  // "YYYY:MM:DD HH:MM:SS" without the terminating NUL
+const DATE_LEN: usize = 19;
+
+  // shifts the DateTime, DateTimeOriginal and DateTimeDigitized fields of a JPEG or TIFF based file by the given number of seconds
+  // the dates have a fixed length, so they are overwritten where they are, and everything else in the file stays untouched
+  // fields that can't be parsed as a date are left alone. returns the number of fields that were changed
+pub fn rewrite_dates(path: &Path, shift: i64)->Result<usize, ExifWriteError> {
+  let data = fs::read(path).context(ExifFileRead)?;
+  if data.get(4..8) == Some(b"ftyp") {
+    return UnsupportedContainer.fail();
+  }
+  let tiff_start = find_tiff(&data).context(NoExifData)?;
+  let tiff = Tiff::new(&data[tiff_start..]).context(MalformedExif)?;
+
+  let mut date_positions = Vec::new();
+  let ifd0 = tiff.u32_at(4).context(MalformedExif)? as usize;
+  let mut exif_ifd = None;
+  for (tag, entry) in tiff.entries(ifd0).context(MalformedExif)? {
+    match tag {
+      TAG_DATE_TIME => date_positions.extend(tiff.date_position(entry)),
+      TAG_EXIF_IFD => exif_ifd = tiff.u32_at(entry + 8).map(|offset| offset as usize),
+      _ => {},
+    }
+  }
+  if let Some(exif_ifd) = exif_ifd {
+    for (tag, entry) in tiff.entries(exif_ifd).context(MalformedExif)? {
+      if tag == TAG_DATE_TIME_ORIGINAL || tag == TAG_DATE_TIME_DIGITIZED {
+        date_positions.extend(tiff.date_position(entry));
+      }
+    }
+  }
+
+  let mut changes = Vec::new();
+  for position in date_positions {
+    let old = &tiff.data[position..position + DATE_LEN];
+    if let Ok(mut datetime) = parse_exif_datetime(old) {
+      shift_datetime(&mut datetime, shift);
+      let new = format!("{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        datetime.year, datetime.month, datetime.day, datetime.hour, datetime.minute, datetime.second);
+      if new.as_bytes() != old {
+        changes.push((tiff_start + position, new));
+      }
+    }
+  }
+
+  if !changes.is_empty() {
+    let mut file = OpenOptions::new().write(true).open(path).context(ExifFileWrite)?;
+    for (position, new) in &changes {
+      file.seek(SeekFrom::Start(*position as u64)).context(ExifFileWrite)?;
+      file.write_all(new.as_bytes()).context(ExifFileWrite)?;
+    }
+  }
+  Ok(changes.len())
+}
+
+  // the offset of the TIFF header, either at the start of the file or in the Exif segment of a JPEG
+fn find_tiff(data: &[u8])->Option<usize> {
+  if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+    return Some(0);
+  }
+  if !data.starts_with(&[0xff, 0xd8]) {
+    return None;
+  }
+
+  let mut pos = 2;
+  while pos + 4 <= data.len() {
+    if data[pos] != 0xff {
+      return None;
+    }
+    let marker = data[pos + 1];
+    if marker == 0xd8 || (0xd0..=0xd7).contains(&marker) || marker == 0x01 {
+      pos += 2; // markers without a length
+      continue;
+    }
+    if marker == 0xda || marker == 0xd9 {
+      return None; // the image data starts, exif has to come before it
+    }
+
+    let len = usize::from(u16::from_be_bytes([data[pos + 2], data[pos + 3]]));
+    let segment = data.get(pos + 4..pos + 2 + len)?;
+    if marker == 0xe1 && segment.starts_with(b"Exif\0\0") {
+      return Some(pos + 4 + 6);
+    }
+    pos += 2 + len;
+  }
+  None
+}
+
+struct Tiff<'a> {
+  data: &'a [u8],
+  big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+  fn new(data: &'a [u8])->Option<Tiff<'a>> {
+    let big_endian = match data.get(0..2)? {
+      b"MM" => true,
+      b"II" => false,
+      _ => return None,
+    };
+    Some(Tiff { data, big_endian })
+  }
+
+  fn u16_at(&self, pos: usize)->Option<u16> {
+    let bytes = [*self.data.get(pos)?, *self.data.get(pos + 1)?];
+    Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+  }
+
+  fn u32_at(&self, pos: usize)->Option<u32> {
+    let bytes = [*self.data.get(pos)?, *self.data.get(pos + 1)?, *self.data.get(pos + 2)?, *self.data.get(pos + 3)?];
+    Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+  }
+
+    // (tag, position of the entry) for each entry of the IFD at the given offset
+  fn entries(&self, ifd: usize)->Option<Vec<(u16, usize)>> {
+    let count = usize::from(self.u16_at(ifd)?);
+    if self.data.len() < ifd + 2 + count * 12 {
+      return None;
+    }
+    Some((0..count).map(|index| {
+      let entry = ifd + 2 + index * 12;
+      (self.u16_at(entry).unwrap_or(0), entry)
+    }).collect())
+  }
+
+    // where the value of a date entry is stored, if it has the expected type and length
+  fn date_position(&self, entry: usize)->Option<usize> {
+    if self.u16_at(entry + 2)? != TYPE_ASCII || self.u32_at(entry + 4)? as usize != DATE_LEN + 1 {
+      return None;
+    }
+    let position = self.u32_at(entry + 8)? as usize;
+    if self.data.len() < position + DATE_LEN {
+      return None;
+    }
+    Some(position)
+  }
+}
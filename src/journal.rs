@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt, Snafu};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::plan::{RenameError, RenameResult};
+
+pub const JOURNAL_FILE_NAME: &str = ".datier-journal.json";
+
+  // a record of the renames done in the last run, which allows reverting them
+  // paths are stored relative to the directory the journal is in, so that it stays valid if that directory is moved
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+  pub entries: Vec<JournalEntry>,
+    // the name each file had before datier first renamed it, keyed by its current name
+    // unlike the entries, these are carried over from run to run. journals of older versions don't have them
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub first_names: BTreeMap<PathBuf, PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+  pub original: PathBuf,
+  pub renamed: PathBuf,
+}
+
+#[derive(Debug, Snafu)]
+pub enum JournalError {
+  #[snafu(display("Could not read journal {}: {}", path.display(), source))]
+  JournalRead {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("Could not parse journal {}: {}", path.display(), source))]
+  JournalParse {
+    path: PathBuf,
+    source: serde_json::Error,
+  },
+  #[snafu(display("Could not write journal {}: {}", path.display(), source))]
+  JournalWrite {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+}
+
+pub fn journal_path(dir: &Path)->PathBuf {
+  dir.join(JOURNAL_FILE_NAME)
+}
+
+impl Journal {
+    // builds a journal of all successful renames in results, and of those that went through but couldn't be verified
+  pub fn from_results(dir: &Path, results: &[RenameResult])->Journal {
+    let entries = results.iter()
+      .filter(|result| matches!(result.outcome, Ok(()) | Err(RenameError::NotFoundAfterRename { .. })))
+      .map(|result| JournalEntry {
+        original: relative_to(dir, &result.source),
+        renamed: relative_to(dir, &result.dest),
+      })
+      .collect();
+    Journal { entries, first_names: BTreeMap::new() }
+  }
+
+    // the journal for a new run in dir, which keeps the first names from the journal of the runs before it
+    // a journal of an older version only knows the names from before its last run, so those are used
+  pub fn for_next_run(dir: &Path)->Journal {
+    let previous = Journal::read(dir).unwrap_or_default();
+    let mut first_names = previous.first_names;
+    if first_names.is_empty() {
+      first_names = previous.entries.into_iter().map(|entry| (entry.renamed, entry.original)).collect();
+    }
+    Journal { entries: Vec::new(), first_names }
+  }
+
+    // adds the successful renames in results, following each file's first name to its new name
+  pub fn add_results(&mut self, dir: &Path, results: &[RenameResult]) {
+    for entry in Journal::from_results(dir, results).entries {
+      let first_name = self.first_names.remove(&entry.original).unwrap_or_else(|| entry.original.clone());
+      if first_name != entry.renamed {
+        self.first_names.insert(entry.renamed.clone(), first_name);
+      }
+      self.entries.push(entry);
+    }
+  }
+
+  fn is_empty(&self)->bool {
+    self.entries.is_empty() && self.first_names.is_empty()
+  }
+
+  pub fn read(dir: &Path)->Result<Journal, JournalError> {
+    let path = journal_path(dir);
+    let contents = fs::read_to_string(&path).context(JournalRead { path: path.clone() })?;
+    serde_json::from_str(&contents).context(JournalParse { path })
+  }
+
+    // the journal is written to a temporary file first and then moved into place,
+    // so an interrupted write never leaves a broken journal behind
+  pub fn write(&self, dir: &Path)->Result<(), JournalError> {
+    let path = journal_path(dir);
+    let tmp_path = dir.join(format!("{}.tmp", JOURNAL_FILE_NAME));
+    let contents = serde_json::to_string_pretty(self).expect("journal entries are always serializable");
+    fs::write(&tmp_path, contents).context(JournalWrite { path: tmp_path.clone() })?;
+    fs::rename(&tmp_path, &path).context(JournalWrite { path })
+  }
+
+  pub fn remove(dir: &Path)->Result<(), JournalError> {
+    let path = journal_path(dir);
+    fs::remove_file(&path).context(JournalWrite { path })
+  }
+
+  fn write_or_remove(&self, dir: &Path)->Result<(), JournalError> {
+    if self.is_empty() { Journal::remove(dir) } else { self.write(dir) }
+  }
+}
+
+fn relative_to(dir: &Path, path: &Path)->PathBuf {
+  path.strip_prefix(dir).map(Path::to_path_buf).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Debug, Snafu)]
+pub enum UndoError {
+  #[snafu(display("{} no longer exists", path.display()))]
+  RenamedMissing {
+    path: PathBuf,
+  },
+  #[snafu(display("{} already exists", path.display()))]
+  OriginalExists {
+    path: PathBuf,
+  },
+  #[snafu(display("Rename failed: {}", source))]
+  UndoRenameFailed {
+    source: std::io::Error,
+  },
+}
+
+#[derive(Debug)]
+pub struct UndoResult {
+  pub original: PathBuf,
+  pub renamed: PathBuf,
+  pub outcome: Result<(), UndoError>,
+}
+
+  // renames all files in the journal of dir back to their original names, latest rename first
+  // entries that could not be reverted stay in the journal, and the journal is removed once it is empty
+pub fn undo(dir: &Path)->Result<Vec<UndoResult>, JournalError> {
+  let mut journal = Journal::read(dir)?;
+
+  let mut results = Vec::new();
+  let mut remaining = Vec::new();
+  for entry in std::mem::take(&mut journal.entries).into_iter().rev() {
+    let original = dir.join(&entry.original);
+    let renamed = dir.join(&entry.renamed);
+    let outcome = undo_rename(&original, &renamed);
+    if outcome.is_err() {
+      remaining.push(entry);
+    } else if let Some(first_name) = journal.first_names.remove(&entry.renamed) {
+      if first_name != entry.original {
+        journal.first_names.insert(entry.original, first_name);
+      }
+    }
+    results.push(UndoResult { original, renamed, outcome });
+  }
+
+  remaining.reverse();
+  journal.entries = remaining;
+  journal.write_or_remove(dir)?;
+  Ok(results)
+}
+
+  // renames all files in the journal of dir back to the names they had before datier first renamed them, over all runs
+  // a file is only renamed once its first name is free, which may take another file being renamed first
+  // the files that could not be renamed stay in the journal
+pub fn restore_first_names(dir: &Path)->Result<Vec<UndoResult>, JournalError> {
+  let mut journal = Journal::read(dir)?;
+
+  let mut results = Vec::new();
+  let mut pending: Vec<(PathBuf, PathBuf)> = std::mem::take(&mut journal.first_names).into_iter().collect();
+  loop {
+    let (free, taken): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(_, first_name)| !dir.join(first_name).exists());
+    pending = taken;
+    if free.is_empty() {
+      break;
+    }
+    for (current, first_name) in free {
+      let original = dir.join(&first_name);
+      let renamed = dir.join(&current);
+      let outcome = undo_rename(&original, &renamed);
+      if outcome.is_ok() {
+        journal.entries.retain(|entry| entry.renamed != current);
+      } else {
+        journal.first_names.insert(current, first_name);
+      }
+      results.push(UndoResult { original, renamed, outcome });
+    }
+  }
+  for (current, first_name) in pending {
+    let original = dir.join(&first_name);
+    let renamed = dir.join(&current);
+    results.push(UndoResult { outcome: Err(UndoError::OriginalExists { path: original.clone() }), original, renamed });
+    journal.first_names.insert(current, first_name);
+  }
+
+  journal.write_or_remove(dir)?;
+  Ok(results)
+}
+
+fn undo_rename(original: &Path, renamed: &Path)->Result<(), UndoError> {
+  ensure!(renamed.exists(), RenamedMissing { path: renamed.to_path_buf() });
+  ensure!(!original.exists(), OriginalExists { path: original.to_path_buf() });
+  fs::rename(renamed, original).context(UndoRenameFailed)
+}
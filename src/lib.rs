@@ -0,0 +1,33 @@
+mod cache;
+mod datetime;
+mod exif_write;
+mod journal;
+mod locale;
+mod manifest;
+mod metadata;
+mod original_name;
+mod places;
+mod plan;
+mod png;
+mod raw;
+mod script;
+mod template;
+mod video;
+#[cfg(windows)]
+mod windows;
+
+pub use cache::{cache_path, CacheError, MetadataCache};
+pub use datetime::{format_offset, parse_duration, parse_offset, parse_shift, shift_datetime, OrdDateTime};
+pub use exif_write::{rewrite_dates, ExifWriteError};
+pub use journal::{journal_path, restore_first_names, undo, Journal, JournalEntry, JournalError, UndoError, UndoResult, JOURNAL_FILE_NAME};
+pub use locale::Locale;
+pub use manifest::{Manifest, ManifestEntry, ManifestError};
+pub use metadata::{consulted_tags, detect_kind, get_datetime, get_metadata, read_metadata, read_tag_values, system_timezone, DateTag, DateTimeReadError, Dimensions, FileKind, FileMetadata, GetDateTimeError, GpsPosition, ReadOptions, Subseconds, ValidationSummary, DEFAULT_DATE_TAGS};
+pub use original_name::{keep_original_name, original_name, ORIGINAL_NAME_ATTRIBUTE};
+pub use places::{Place, Places, PlacesError, DEFAULT_PLACE_RADIUS_KM};
+pub use plan::{check_destinations, distinct_offsets, exclude_paths, execute_plan, execute_plan_with_progress, find_duplicates, find_files, find_other_files, hidden_paths, ignored_paths, parse_name_pattern, plan_renames, plan_renames_with_progress, planned_dirs, stash_dest, stash_files, CounterStep, DateSource, FoundFiles, GroupBy, IgnoreFileError, Numbering, NumberingScope, OnError, PlanAction, PlanSummary, RenameError, RenameOptions, RenamePlan, RenameResult, SearchError, SkipReason, TieBreak, DEFAULT_EXTENSIONS, DEFAULT_SIDECAR_EXTENSIONS, IGNORE_FILE_NAME};
+pub use png::{is_png, PngReadError};
+pub use raw::{has_embedded_preview, PREVIEW_RAW_EXTENSIONS};
+pub use script::{write_script, ScriptStyle};
+pub use template::{NameCase, Template, TemplateParseError, TextValues, DEFAULT_TEMPLATE, MAX_FIELD_WIDTH};
+pub use video::{is_video, VideoReadError, VIDEO_EXTENSIONS};
@@ -0,0 +1,61 @@
+  // the language month names in file names are written in
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Locale {
+  #[default]
+  English,
+  German,
+  French,
+  Spanish,
+  Italian,
+  Dutch,
+  Portuguese,
+}
+
+impl Locale {
+    // the full name of a month from 1 to 12
+  pub fn month_name(self, month: u8)->&'static str {
+    let names = match self {
+      Locale::English => ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+      Locale::German => ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+      Locale::French => ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+      Locale::Spanish => ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+      Locale::Italian => ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+      Locale::Dutch => ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+      Locale::Portuguese => ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+    };
+    names[usize::from(month.clamp(1, 12)) - 1]
+  }
+
+    // the short name of a month from 1 to 12, without a trailing dot
+  pub fn month_abbr(self, month: u8)->&'static str {
+    let abbrs = match self {
+      Locale::English => ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"],
+      Locale::German => ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez"],
+      Locale::French => ["janv", "févr", "mars", "avr", "mai", "juin", "juil", "août", "sept", "oct", "nov", "déc"],
+      Locale::Spanish => ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sep", "oct", "nov", "dic"],
+      Locale::Italian => ["gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic"],
+      Locale::Dutch => ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"],
+      Locale::Portuguese => ["jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez"],
+    };
+    abbrs[usize::from(month.clamp(1, 12)) - 1]
+  }
+}
+
+impl std::str::FromStr for Locale {
+  type Err = String;
+
+    // accepts language codes like "de", as well as full locale names like "de_DE.UTF-8" or "pt-BR", ignoring case
+  fn from_str(s: &str)->Result<Locale, String> {
+    let language = s.split(['_', '-', '.']).next().unwrap_or("").to_lowercase();
+    match language.as_str() {
+      "en" | "c" | "posix" => Ok(Locale::English),
+      "de" => Ok(Locale::German),
+      "fr" => Ok(Locale::French),
+      "es" => Ok(Locale::Spanish),
+      "it" => Ok(Locale::Italian),
+      "nl" => Ok(Locale::Dutch),
+      "pt" => Ok(Locale::Portuguese),
+      _ => Err(format!("Unknown locale \"{}\" (available: en, de, fr, es, it, nl, pt)", s)),
+    }
+  }
+}
@@ -0,0 +1,621 @@
+use datier::{CounterStep, DateSource, OrdDateTime, PlanAction, PlanSummary, RenamePlan, ValidationSummary};
+use indicatif::{ProgressBar, ProgressStyle};
+use owo_colors::{AnsiColors, OwoColorize};
+use serde::Serialize;
+use serde_json::json;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::compare::Change;
+
+  // how much datier prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    // only errors
+  Quiet,
+    // errors, summaries and the JSON output
+  Normal,
+    // also a line for each file, and other details (-l)
+  Verbose,
+}
+
+  // how a dry run shows the planned renames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanDisplay {
+    // a line for each file, with -l
+  Plain,
+    // the files and their new names in aligned columns
+  Table,
+    // the folders and files as they will be after the renames
+  Tree,
+}
+
+impl std::str::FromStr for PlanDisplay {
+  type Err = String;
+
+  fn from_str(s: &str)->Result<PlanDisplay, String> {
+    match s {
+      "plain" => Ok(PlanDisplay::Plain),
+      "table" => Ok(PlanDisplay::Table),
+      "tree" => Ok(PlanDisplay::Tree),
+      _ => Err(format!("Invalid display \"{}\" (expected plain, table or tree)", s)),
+    }
+  }
+}
+
+  // what --print-plan writes the plan as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanFormat {
+  Json,
+  Toml,
+}
+
+impl std::str::FromStr for PlanFormat {
+  type Err = String;
+
+  fn from_str(s: &str)->Result<PlanFormat, String> {
+    match s {
+      "json" => Ok(PlanFormat::Json),
+      "toml" => Ok(PlanFormat::Toml),
+      _ => Err(format!("Invalid plan format \"{}\" (expected json or toml)", s)),
+    }
+  }
+}
+
+  // the plan as printed by --print-plan, which is wrapped in a table as TOML can't have a list at the top
+#[derive(Serialize)]
+struct PrintedPlan {
+  files: Vec<PrintedEntry>,
+}
+
+#[derive(Serialize)]
+struct PrintedEntry {
+  source: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  datetime: Option<String>,
+  date_source: &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  new_name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  skipped: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  sidecar_of: Option<String>,
+}
+
+  // how long each phase of a run took, as reported by --stats
+#[derive(Debug, Default)]
+pub struct PhaseTimes {
+  pub searching: Duration,
+  pub reading: Duration,
+    // sorting the files by date and choosing their names
+  pub planning: Duration,
+  pub renaming: Duration,
+}
+
+  // a folder in the tree of PlanDisplay::Tree, with the text shown for each file in it
+#[derive(Default)]
+struct TreeNode {
+  folders: BTreeMap<String, TreeNode>,
+  files: BTreeMap<String, String>,
+}
+
+impl TreeNode {
+  fn add(&mut self, path: &Path, text: String) {
+    let mut node = self;
+    let mut components: Vec<String> = path.components().map(|component| component.as_os_str().to_string_lossy().into_owned()).collect();
+    let name = components.pop().unwrap_or_default();
+    for folder in components {
+      node = node.folders.entry(folder).or_default();
+    }
+    node.files.insert(name, text);
+  }
+
+  fn print(&self, indent: &str) {
+    let count = self.folders.len() + self.files.len();
+    let branch = |index: usize| if index + 1 == count { "└── " } else { "├── " };
+    for (index, (name, folder)) in self.folders.iter().enumerate() {
+      println!("{}{}{}", indent, branch(index), name);
+      folder.print(&format!("{}{}", indent, if index + 1 == count { "    " } else { "│   " }));
+    }
+    for (index, text) in self.files.values().enumerate() {
+      println!("{}{}{}", indent, branch(self.folders.len() + index), text);
+    }
+  }
+}
+
+  // what happened to one file, as written to the --report file
+struct FileRecord {
+  original: String,
+  renamed: Option<String>,
+  status: &'static str,
+  reason: Option<String>,
+  datetime: Option<String>,
+}
+
+  // all output of datier goes through here, either as human-readable lines or as one JSON object per line
+  // errors go to stderr, unless they are part of the JSON output, and everything else goes to stdout
+pub struct Logger {
+  verbosity: Verbosity,
+  json: bool,
+  show_progress: bool,
+    // whether the lines on stdout and stderr are colored
+  colors: bool,
+  error_colors: bool,
+  renamed: Cell<usize>,
+  skipped: Cell<usize>,
+  errored: Cell<usize>,
+    // only kept if a report is written
+  records: Option<RefCell<Vec<FileRecord>>>,
+    // how many lines about files are shown, with --limit
+  limit: Option<usize>,
+  shown: Cell<usize>,
+  not_shown: Cell<usize>,
+}
+
+impl Logger {
+  pub fn new(verbosity: Verbosity, json: bool)->Logger {
+    Logger {
+      verbosity,
+      json,
+        // progress bars only make sense for a person watching, not when the output goes into another program
+      show_progress: verbosity != Verbosity::Quiet && !json && std::io::stdout().is_terminal(),
+      colors: false,
+      error_colors: false,
+      renamed: Cell::new(0),
+      skipped: Cell::new(0),
+      errored: Cell::new(0),
+      records: None,
+      limit: None,
+      shown: Cell::new(0),
+      not_shown: Cell::new(0),
+    }
+  }
+
+    // keeps a record of each file, so that it can be written out with write_report
+  pub fn with_records(mut self, keep: bool)->Logger {
+    self.records = if keep { Some(RefCell::new(Vec::new())) } else { None };
+    self
+  }
+
+    // colors the output where it goes to a terminal, unless colors are turned off by the NO_COLOR environment variable
+    // the JSON output is never colored
+  pub fn with_colors(mut self, allowed: bool)->Logger {
+    let allowed = allowed && !self.json && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+    self.colors = allowed && std::io::stdout().is_terminal();
+    self.error_colors = allowed && std::io::stderr().is_terminal();
+    self
+  }
+
+    // stops showing lines about files after the first limit ones, e.g. to preview a dry run of many files
+    // they are still counted, as well as kept for the report
+  pub fn with_limit(mut self, limit: Option<usize>)->Logger {
+    self.limit = limit;
+    self
+  }
+
+  pub fn is_json(&self)->bool {
+    self.json
+  }
+
+    // a progress bar on stderr for a phase that handles len files, which is hidden if progress isn't shown
+  pub fn progress(&self, len: usize, message: &'static str)->ProgressBar {
+    if !self.show_progress {
+      return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len as u64).with_message(message);
+    if let Ok(style) = ProgressStyle::with_template("{msg:16} [{bar:40}] {pos}/{len}") {
+      bar.set_style(style.progress_chars("=> "));
+    }
+    bar
+  }
+
+    // free-form information, which isn't part of the JSON output
+  pub fn log(&self, args: std::fmt::Arguments) {
+    if self.verbosity == Verbosity::Verbose && !self.json {
+      println!("{}", args);
+    }
+  }
+
+    // status messages for a person watching, which also aren't part of the JSON output
+  pub fn info(&self, args: std::fmt::Arguments) {
+    if self.verbosity != Verbosity::Quiet && !self.json {
+      println!("{}", args);
+    }
+  }
+
+    // something that the user should know about, but that doesn't stop the run
+  pub fn warn(&self, args: std::fmt::Arguments) {
+    if self.verbosity != Verbosity::Quiet {
+      eprintln!("{} {}", paint("Warning:", AnsiColors::Yellow, self.error_colors), args);
+    }
+  }
+
+  pub fn error(&self, args: std::fmt::Arguments) {
+    if self.json {
+      println!("{}", json!({ "error": args.to_string() }));
+    } else {
+      eprintln!("{} {}", paint("Error:", AnsiColors::Red, self.error_colors), args);
+    }
+  }
+
+  pub fn renamed(&self, original: &Path, renamed: &Path, datetime: Option<&OrdDateTime>) {
+    self.renamed.set(self.renamed.get() + 1);
+    self.file_event(original, Some(renamed), "renamed", None, datetime);
+    if !self.json {
+      self.file_line(format_args!("{} -> {}", original.display(), paint(renamed.display(), AnsiColors::Green, self.colors)));
+    }
+  }
+
+    // counted as a rename, as the file now also exists under its new name
+  pub fn copied(&self, original: &Path, copy: &Path, datetime: Option<&OrdDateTime>) {
+    self.renamed.set(self.renamed.get() + 1);
+    self.file_event(original, Some(copy), "copied", None, datetime);
+    if !self.json {
+      self.file_line(format_args!("{} -> {} (copy)", original.display(), paint(copy.display(), AnsiColors::Green, self.colors)));
+    }
+  }
+
+  pub fn skipped(&self, original: &Path, reason: &dyn Display, datetime: Option<&OrdDateTime>) {
+    self.skipped.set(self.skipped.get() + 1);
+    self.file_event(original, None, "skipped", Some(reason), datetime);
+    if !self.json {
+      self.file_line(format_args!("{} {}", original.display(), paint(format_args!("skipped ({})", reason), AnsiColors::Yellow, self.colors)));
+    }
+  }
+
+    // a duplicate that was deleted, which counts as skipped, as it wasn't renamed
+  pub fn deleted(&self, duplicate: &Path, original: &Path, datetime: Option<&OrdDateTime>) {
+    self.skipped.set(self.skipped.get() + 1);
+    let reason = format!("Duplicate of {}", original.display());
+    self.file_event(duplicate, None, "deleted", Some(&reason), datetime);
+    if !self.json {
+      self.file_line(format_args!("{} {}", duplicate.display(), paint(format_args!("deleted ({})", reason), AnsiColors::Yellow, self.colors)));
+    }
+  }
+
+    // a file that should have been renamed, but the rename itself went wrong
+  pub fn failed(&self, original: &Path, renamed: &Path, reason: &dyn Display, datetime: Option<&OrdDateTime>) {
+    self.errored.set(self.errored.get() + 1);
+    self.file_event(original, Some(renamed), "error", Some(reason), datetime);
+    if !self.json {
+      self.file_line(format_args!("{} {}", original.display(), paint(format_args!("skipped ({})", reason), AnsiColors::Red, self.colors)));
+    }
+  }
+
+    // the number of files that failed to be renamed so far
+  pub fn errored(&self)->usize {
+    self.errored.get()
+  }
+
+    // describes how the new name of a file came to be, for --explain
+    // counter_group is what the counter starts over for, e.g. "day"
+  pub fn explain(&self, entry: &RenamePlan, counter_group: &str) {
+    if !self.within_limit() {
+      return;
+    }
+    let mut line = format!("{}", entry.source.display());
+    if let Some(ref datetime) = entry.datetime {
+      line.push_str(&format!(": taken {}", datetime));
+    }
+
+    if let Some(ref numbering) = entry.numbering {
+      let previous = numbering.previous.as_ref().map(|previous| previous.display().to_string()).unwrap_or_default();
+      match numbering.step {
+        CounterStep::Reset => line.push_str(&format!(", n = {} as the first file of the {}", numbering.counter, counter_group)),
+        CounterStep::Increment => line.push_str(&format!(", n = {}, one more than {}", numbering.counter, previous)),
+        CounterStep::Tie => line.push_str(&format!(", n = {}, taken at the same time as {}", numbering.counter, previous)),
+      }
+    } else if let Some(ref main_file) = entry.sidecar_of {
+      line.push_str(&format!(", sidecar of {}", main_file.display()));
+    }
+
+    match entry.action {
+      PlanAction::Rename(ref dest) => line.push_str(&format!(" -> {}", paint(dest.display(), AnsiColors::Green, self.colors))),
+      PlanAction::Skip(ref reason) => line.push_str(&format!(" -> {}", paint(format_args!("skipped ({})", reason), AnsiColors::Yellow, self.colors))),
+    }
+    println!("{}", line);
+  }
+
+    // prints the whole plan as data, e.g. to compare the plans of different formats
+    // unlike the other output, this is printed whatever the verbosity, as it is all that is asked for
+  pub fn print_plan(&self, plan: &[RenamePlan], format: PlanFormat)->Result<(), toml::ser::Error> {
+    let files = plan.iter().map(|entry| {
+      let (new_name, skipped) = match entry.action {
+        PlanAction::Rename(ref dest) => (Some(dest.display().to_string()), None),
+        PlanAction::Skip(ref reason) => (None, Some(reason.to_string())),
+      };
+      PrintedEntry {
+        source: entry.source.display().to_string(),
+        datetime: entry.datetime.as_ref().map(|datetime| datetime.to_string()),
+        date_source: match entry.date_source {
+          DateSource::Metadata => "metadata",
+          DateSource::ModifiedTime => "modified-time",
+          DateSource::FileName => "file-name",
+        },
+        new_name,
+        skipped,
+        sidecar_of: entry.sidecar_of.as_ref().map(|main_file| main_file.display().to_string()),
+      }
+    }).collect();
+    let printed = PrintedPlan { files };
+    match format {
+      PlanFormat::Json => println!("{}", serde_json::to_string_pretty(&printed).expect("plans are always serializable")),
+      PlanFormat::Toml => print!("{}", toml::to_string(&printed)?),
+    }
+    Ok(())
+  }
+
+    // shows the plan of a dry run as a table or tree, with the paths relative to dir
+    // nothing is shown for PlanDisplay::Plain, which uses the lines of each file instead
+  pub fn plan(&self, plan: &[RenamePlan], dir: &Path, display: PlanDisplay) {
+    if self.verbosity == Verbosity::Quiet || self.json {
+      return;
+    }
+    let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).to_path_buf();
+    let (plan, not_shown) = plan.split_at(self.limit.unwrap_or(plan.len()).min(plan.len()));
+    match display {
+      PlanDisplay::Plain => {},
+      PlanDisplay::Table => {
+        let rows: Vec<(String, String, String)> = plan.iter().map(|entry| {
+          let new = match entry.action {
+            PlanAction::Rename(ref dest) => paint(relative(dest).display(), AnsiColors::Green, self.colors),
+            PlanAction::Skip(ref reason) => paint(format_args!("skipped ({})", reason), AnsiColors::Yellow, self.colors),
+          };
+          let datetime = entry.datetime.as_ref().map(|datetime| datetime.to_string()).unwrap_or_default();
+          (relative(&entry.source).display().to_string(), datetime, new)
+        }).collect();
+        let original_width = rows.iter().map(|row| row.0.chars().count()).max().unwrap_or(0).max("ORIGINAL".len());
+        let date_width = rows.iter().map(|row| row.1.chars().count()).max().unwrap_or(0).max("DATE".len());
+        println!("{:original_width$}  {:date_width$}  NEW NAME", "ORIGINAL", "DATE", original_width = original_width, date_width = date_width);
+        for (original, datetime, new) in rows {
+          println!("{:original_width$}  {:date_width$}  {}", original, datetime, new, original_width = original_width, date_width = date_width);
+        }
+      },
+      PlanDisplay::Tree => {
+        let mut root = TreeNode::default();
+        for entry in plan {
+          let original = relative(&entry.source);
+          match entry.action {
+            PlanAction::Rename(ref dest) => {
+              let dest = relative(dest);
+              let name = dest.file_name().unwrap_or_default().to_string_lossy().into_owned();
+              root.add(&dest, format!("{}  <- {}", paint(name, AnsiColors::Green, self.colors), original.display()));
+            },
+            PlanAction::Skip(ref reason) => {
+              let name = original.file_name().unwrap_or_default().to_string_lossy().into_owned();
+              root.add(&original, format!("{}  {}", name, paint(format_args!("skipped ({})", reason), AnsiColors::Yellow, self.colors)));
+            },
+          }
+        }
+        println!("{}", dir.display());
+        root.print("");
+      },
+    }
+    if display != PlanDisplay::Plain && !not_shown.is_empty() {
+      println!("... and {} more", not_shown.len());
+    }
+  }
+
+    // points out the lines about files that --limit left out, after the ones that were shown
+  pub fn not_shown(&self) {
+    if self.not_shown.get() > 0 {
+      println!("... and {} more", self.not_shown.get());
+    }
+  }
+
+    // only part of the JSON output, as the last object
+  pub fn summary(&self, dry_run: bool) {
+    if self.json && self.verbosity != Verbosity::Quiet {
+      println!("{}", json!({
+        "summary": {
+          "renamed": self.renamed.get(),
+          "skipped": self.skipped.get(),
+          "errored": self.errored.get(),
+          "dry_run": dry_run,
+        }
+      }));
+    }
+  }
+
+    // printed to stderr after a dry run, unless the output is quiet
+  pub fn dry_run_summary(&self, summary: &PlanSummary, copy: bool) {
+    if self.verbosity == Verbosity::Quiet {
+      return;
+    }
+    eprintln!("Dry run summary:");
+    if copy {
+      eprintln!("  would copy:             {}", summary.renames);
+    } else {
+      eprintln!("  would rename:           {}", summary.renames);
+    }
+    eprintln!("  would skip (collision): {}", summary.collisions);
+    eprintln!("  skipped (no date):      {}", summary.no_datetime);
+    eprintln!("  skipped (no extension): {}", summary.no_extension);
+    eprintln!("  skipped (out of range): {}", summary.out_of_range);
+    eprintln!("  skipped (suspicious):   {}", summary.suspicious);
+    if summary.future > 0 {
+      eprintln!("  skipped (future date):  {}", summary.future);
+    }
+    eprintln!("  skipped (correct name): {}", summary.already_named);
+    eprintln!("  skipped (duplicate):    {}", summary.duplicates);
+    eprintln!("  skipped (size):         {}", summary.wrong_size);
+    if summary.not_in_manifest > 0 {
+      eprintln!("  skipped (not in plan):  {}", summary.not_in_manifest);
+    }
+  }
+
+    // printed to stderr even if the output is quiet or JSON, as it was asked for explicitly
+  pub fn stats(&self, files: usize, times: &PhaseTimes) {
+    let total = times.searching + times.reading + times.planning + times.renaming;
+    eprintln!("Stats:");
+    eprintln!("  files:                  {}", files);
+    eprintln!("  searching:              {:.3}s", times.searching.as_secs_f64());
+    eprintln!("  reading dates:          {:.3}s", times.reading.as_secs_f64());
+    eprintln!("  sorting and naming:     {:.3}s", times.planning.as_secs_f64());
+    eprintln!("  renaming:               {:.3}s", times.renaming.as_secs_f64());
+    eprintln!("  total:                  {:.3}s", total.as_secs_f64());
+    if !total.is_zero() {
+      eprintln!("  files per second:       {:.0}", files as f64 / total.as_secs_f64());
+    }
+  }
+
+    // the differences between the plan and an earlier report, for --compare
+  pub fn changes(&self, changes: &[Change]) {
+    if self.verbosity == Verbosity::Quiet {
+      return;
+    }
+    let name = |name: &Option<String>| name.clone().unwrap_or_else(|| String::from("skipped"));
+    for change in changes {
+      if self.json {
+        let object = match change {
+          Change::Added { source, now } => json!({ "change": "added", "original": source, "now": now }),
+          Change::Removed { source } => json!({ "change": "removed", "original": source }),
+          Change::NameChanged { source, before, now } => json!({ "change": "name-changed", "original": source, "before": before, "now": now }),
+        };
+        println!("{}", object);
+        continue;
+      }
+      match change {
+        Change::Added { source, now } => println!("{}", paint(format_args!("+ {} -> {}", source, name(now)), AnsiColors::Green, self.colors)),
+        Change::Removed { source } => println!("{}", paint(format_args!("- {}", source), AnsiColors::Red, self.colors)),
+        Change::NameChanged { source, before, now } => println!("{}", paint(format_args!("~ {}: {} -> {}", source, name(before), name(now)), AnsiColors::Yellow, self.colors)),
+      }
+    }
+    if !self.json {
+      let count = |kind: fn(&Change)->bool| changes.iter().filter(|change| kind(change)).count();
+      eprintln!("Compared to the report: {} new, {} gone, {} named differently",
+        count(|change| matches!(change, Change::Added { .. })),
+        count(|change| matches!(change, Change::Removed { .. })),
+        count(|change| matches!(change, Change::NameChanged { .. })));
+    }
+  }
+
+    // a group of files with the same contents, found by --find-duplicates
+  pub fn duplicates(&self, group: &[PathBuf]) {
+    if self.verbosity == Verbosity::Quiet {
+      return;
+    }
+    if self.json {
+      println!("{}", json!({ "duplicates": group.iter().map(|path| path.to_string_lossy()).collect::<Vec<_>>() }));
+      return;
+    }
+    if let Some((first, others)) = group.split_first() {
+      println!("{}", first.display());
+      for other in others {
+        println!("  {}", paint(format_args!("= {}", other.display()), AnsiColors::Yellow, self.colors));
+      }
+    }
+  }
+
+  pub fn validation_summary(&self, summary: &ValidationSummary) {
+    if self.verbosity == Verbosity::Quiet {
+      return;
+    }
+    eprintln!("Validation summary:");
+    eprintln!("  valid:                  {}", summary.valid);
+    eprintln!("  no date field:          {}", summary.missing_field);
+    eprintln!("  empty date field:       {}", summary.empty_field);
+    eprintln!("  date field not ascii:   {}", summary.not_ascii);
+    eprintln!("  unparseable date:       {}", summary.parse_error);
+    eprintln!("  no extension:           {}", summary.no_extension);
+    eprintln!("  no exif data:           {}", summary.no_exif);
+    eprintln!("  unreadable:             {}", summary.unreadable);
+  }
+
+    // writes the records as CSV, with a header line
+  pub fn write_report(&self, path: &Path, dry_run: bool)->std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "original,new,status,datetime,reason,dry_run")?;
+    let records = match self.records {
+      Some(ref records) => records.borrow(),
+      None => return out.flush(),
+    };
+    for record in records.iter() {
+      writeln!(out, "{},{},{},{},{},{}",
+        csv_field(&record.original),
+        csv_field(record.renamed.as_deref().unwrap_or("")),
+        record.status,
+        csv_field(record.datetime.as_deref().unwrap_or("")),
+        csv_field(record.reason.as_deref().unwrap_or("")),
+        dry_run)?;
+    }
+    out.flush()
+  }
+
+    // a line about a file, which is only shown with -l, and only as long as the limit allows
+  fn file_line(&self, args: std::fmt::Arguments) {
+    if self.verbosity == Verbosity::Verbose && !self.json && self.within_limit() {
+      println!("{}", args);
+    }
+  }
+
+  fn within_limit(&self)->bool {
+    if self.limit.is_some_and(|limit| self.shown.get() >= limit) {
+      self.not_shown.set(self.not_shown.get() + 1);
+      return false;
+    }
+    self.shown.set(self.shown.get() + 1);
+    true
+  }
+
+    // records the planned files for the report without printing them, for --explain and --print-plan, which show the plan in their own way
+  pub fn record_plan(&self, plan: &[RenamePlan], copy: bool) {
+    for entry in plan {
+      match entry.action {
+        PlanAction::Rename(ref dest) => self.record(&entry.source, Some(dest), if copy { "copied" } else { "renamed" }, None, entry.datetime.as_ref()),
+        PlanAction::Skip(ref reason) => self.record(&entry.source, None, "skipped", Some(reason), entry.datetime.as_ref()),
+      }
+    }
+  }
+
+  fn record(&self, original: &Path, renamed: Option<&Path>, status: &'static str, reason: Option<&dyn Display>, datetime: Option<&OrdDateTime>) {
+    if let Some(ref records) = self.records {
+      records.borrow_mut().push(FileRecord {
+        original: original.to_string_lossy().into_owned(),
+        renamed: renamed.map(|path| path.to_string_lossy().into_owned()),
+        status,
+        reason: reason.map(|reason| reason.to_string()),
+        datetime: datetime.map(|datetime| datetime.to_string()),
+      });
+    }
+  }
+
+    // records the file for the report, and prints it as part of the JSON output
+  fn file_event(&self, original: &Path, renamed: Option<&Path>, status: &'static str, reason: Option<&dyn Display>, datetime: Option<&OrdDateTime>) {
+    self.record(original, renamed, status, reason, datetime);
+
+    if !self.json || self.verbosity == Verbosity::Quiet {
+      return;
+    }
+    println!("{}", json!({
+      "original": original.to_string_lossy(),
+      "renamed": renamed.map(|path| path.to_string_lossy()),
+      "status": status,
+      "reason": reason.map(|reason| reason.to_string()),
+      "datetime": datetime.map(|datetime| datetime.to_string()),
+    }));
+  }
+}
+
+  // quotes a value if it contains anything that would break the CSV structure
+fn csv_field(value: &str)->String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+  // text in the given color, or as it is if colors are off
+fn paint(text: impl Display, color: AnsiColors, colors: bool)->String {
+  if colors {
+    text.color(color).to_string()
+  } else {
+    text.to_string()
+  }
+}
@@ -1,283 +1,1612 @@
-use glob::{glob_with, MatchOptions};
 use clap::{Arg, App};
-use snafu::{ensure, ResultExt, Snafu};
-use std::fs::{self, File};
+use datier::{cache_path, check_destinations, consulted_tags, distinct_offsets, exclude_paths, execute_plan_with_progress, find_duplicates, find_files, find_other_files, format_offset, get_metadata, hidden_paths, ignored_paths, is_video, keep_original_name, parse_duration, parse_name_pattern, parse_offset, parse_shift, plan_renames_with_progress, planned_dirs, read_tag_values, restore_first_names, rewrite_dates, stash_dest, stash_files, system_timezone, undo, write_script, DateSource, DateTag, Dimensions, FoundFiles, GroupBy, IgnoreFileError, Journal, JournalError, Locale, Manifest, MetadataCache, NameCase, NumberingScope, OnError, OrdDateTime, PlanAction, Places, PlanSummary, ReadOptions, RenameError, RenameOptions, RenamePlan, RenameResult, ScriptStyle, SkipReason, Subseconds, Template, TieBreak, UndoResult, ValidationSummary, DEFAULT_DATE_TAGS, DEFAULT_EXTENSIONS, DEFAULT_SIDECAR_EXTENSIONS, DEFAULT_TEMPLATE, IGNORE_FILE_NAME, JOURNAL_FILE_NAME, MAX_FIELD_WIDTH};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::sync::Mutex;
+use std::time::Instant;
 
-fn main() {
-  let matches = App::new("Datier")
+mod compare;
+use compare::PreviousReport;
+
+mod config;
+use config::{Config, Settings, CONFIG_FILE_NAME, FLAG_NEGATIONS};
+
+mod logger;
+use logger::{Logger, PhaseTimes, PlanDisplay, PlanFormat, Verbosity};
+
+mod watch;
+use watch::{WatchActions, WatchedFiles};
+
+  // the run couldn't start, e.g. because of invalid options or a broken config file
+const EXIT_ERROR: u8 = 1;
+  // the input path doesn't exist or is neither a directory nor a file
+const EXIT_INVALID_INPUT: u8 = 2;
+  // the run went through, but some files could not be renamed
+const EXIT_FILES_FAILED: u8 = 3;
+  // none of the files found had a usable date, or no files were found at all
+const EXIT_NO_DATES: u8 = 4;
+
+  // runs renaming more files than this ask first, unless --yes is given
+const DEFAULT_CONFIRM_ABOVE: usize = 100;
+  // how many of the planned renames are shown when asking
+const CONFIRM_SAMPLE: usize = 5;
+
+fn main()->ExitCode {
+  let mut app = App::new("Datier")
     .version("1.0.0")
     .author("Tilman Schmidt")
     .about("Renames JPEGs and related images based on the date they were taken")
     .arg(Arg::with_name("input directory")
-      .required(true)
-      .help("The folder in which to rename images")
+      .required_unless_one(&["undo", "restore-original", "list-tags", "files-from"])
+      .help("The folder in which to rename images, or a single image to rename")
       .index(1)) // first positional argument
 
+    .arg(Arg::with_name("files-from")
+      .long("files-from")
+      .takes_value(true)
+      .value_name("FILE")
+      .conflicts_with_all(&["input directory", "watch"])
+      .help("Rename the files listed in FILE, or in the standard input if FILE is -, instead of searching a folder. \
+             The paths are separated by NUL characters, as written by find -print0. \
+             The files stay in their folders unless --output is given, and the journal is written to the current folder."))
+
     .arg(Arg::with_name("log")
       .short("l")
       .help("Log each file inspected by datier, and the action taken on it."))
 
+    .arg(Arg::with_name("quiet")
+      .short("q")
+      .long("quiet")
+      .conflicts_with_all(&["log", "explain"])
+      .help("Don't print anything but errors, not even the summaries or the JSON output."))
+
+    .arg(Arg::with_name("json")
+      .long("json")
+      .help("Print one JSON object per inspected file instead of the log lines, followed by a summary object."))
+
+    .arg(Arg::with_name("report")
+      .long("report")
+      .takes_value(true)
+      .value_name("FILE")
+      .help("Write a CSV file with a line for each inspected file, with the columns original, new, status, datetime, reason \
+             and dry_run. It is written in dry runs as well."))
+
+    .arg(Arg::with_name("stats")
+      .long("stats")
+      .help("At the end, print to stderr how many files were found, how long searching for them, reading their dates, \
+             sorting and naming them and renaming them took, and how many files were handled per second."))
+
+    .arg(Arg::with_name("compare")
+      .long("compare")
+      .takes_value(true)
+      .value_name("REPORT")
+      .conflicts_with_all(&["watch", "explain", "print-plan"])
+      .help("Don't rename anything, but compare the plan to REPORT, written by --report in an earlier run, and show the files \
+             that are new, no longer there, or would get another name than then. The files are matched by their paths as given."))
+
+    .arg(Arg::with_name("export-plan")
+      .long("export-plan")
+      .takes_value(true)
+      .value_name("FILE")
+      .help("Write the planned renames to FILE, keyed by the contents of the files instead of their paths, \
+             so they can be done to copies of the files elsewhere with --apply-plan. The files are read in full to hash them. \
+             It is written in dry runs as well."))
+
+    .arg(Arg::with_name("apply-plan")
+      .long("apply-plan")
+      .takes_value(true)
+      .value_name("FILE")
+      .conflicts_with_all(&["export-plan", "explain", "watch", "dedupe"])
+      .help("Instead of naming the files by their dates, give the files with the same contents as the ones in FILE, \
+             written by --export-plan, the names given there. Other files are left as they are."))
+
+    .arg(Arg::with_name("no-color")
+      .long("no-color")
+      .help("Don't color the output. It is only colored in a terminal anyway, and not if the NO_COLOR environment variable is set."))
+
+    .arg(Arg::with_name("include-hidden")
+      .long("include-hidden")
+      .help("Also rename hidden files, and files in hidden folders, e.g. .git. Otherwise, files whose name or folder starts with a dot are left alone. \
+             Files matched by a .datierignore file in the input folder, which uses the syntax of .gitignore files, are left alone either way."))
+
     .arg(Arg::with_name("dry-run")
       .long("dry-run")
       .help("Don't perform any actual renaming."))
 
+    .arg(Arg::with_name("watch")
+      .long("watch")
+      .conflicts_with_all(&["dry-run", "explain", "emit-script", "print-plan", "interactive", "rewrite-exif"])
+      .help("After renaming the files, keep watching the folder and rename new files as they appear, until Ctrl-C is pressed."))
+
+    .arg(Arg::with_name("emit-script")
+      .long("emit-script")
+      .value_name("FILE")
+      .takes_value(true)
+      .help("Don't rename anything, but write a script to FILE that does the planned renames when run. A .bat or .cmd file gets a Windows batch file, anything else a shell script (a batch file on Windows)."))
+
+    .arg(Arg::with_name("print-plan")
+      .long("print-plan")
+      .takes_value(true)
+      .min_values(0)
+      .value_name("FORMAT")
+      .possible_values(&["json", "toml"])
+      .help("Don't rename anything, but print the planned new name (or why it is skipped) and the date of every file, \
+             as JSON (the default) or TOML, e.g. to compare the plans of different formats."))
+
+    .arg(Arg::with_name("limit")
+      .long("limit")
+      .takes_value(true)
+      .value_name("N")
+      .conflicts_with_all(&["json", "print-plan"])
+      .validator(|value| value.parse::<usize>().map(|_| ()).map_err(|_| String::from("must be a non-negative number")))
+      .help("In a dry run, only show the first N planned renames or skips, whether with -l, --display or --explain. \
+             The summary still counts all files."))
+
+    .arg(Arg::with_name("explain")
+      .long("explain")
+      .conflicts_with("json")
+      .help("Don't rename anything, but show for each file the date it was taken and how its number was chosen, \
+             in the order the files are numbered in."))
+
     .arg(Arg::with_name("deep")
       .short("d")
       .long("deep")
       .help("Also search sub-directories for files, and move them into the working directory."))
 
-    .get_matches();
+    .arg(Arg::with_name("deep-in-place")
+      .long("deep-in-place")
+      .conflicts_with("deep")
+      .help("Like --deep, search sub-directories for files, but rename them where they are instead of moving them \
+             into the working directory. The counter within each day starts over for every directory."))
+
+    .arg(Arg::with_name("flat")
+      .long("flat")
+      .conflicts_with("deep-in-place")
+      .help("Like --deep, move the files of all sub-directories into the working directory, and also move files that have \
+             an identical copy there, giving them a -2, -3, ... suffix. Use {subdir} in --format to keep the folder names."))
+
+    .arg(Arg::with_name("follow-symlinks")
+      .long("follow-symlinks")
+      .help("Also rename the files that symbolic links lead to, and search the folders they lead to with --deep. \
+             By default, symbolic links are left out. A file that can be reached in several ways is only renamed once."))
+
+    .arg(Arg::with_name("max-depth")
+      .long("max-depth")
+      .takes_value(true)
+      .value_name("N")
+      .validator(|value| value.parse::<usize>().map(|_| ()).map_err(|_| String::from("must be a non-negative number")))
+      .help("With --deep, --deep-in-place or --flat, only search N levels of sub-directories below the input directory. \
+             0 only searches the input directory itself. Defaults to no limit."))
+
+    .arg(Arg::with_name("copy")
+      .long("copy")
+      .help("Copy the files under their new names instead of renaming them, leaving the originals untouched."))
+
+    .arg(Arg::with_name("interactive")
+      .short("i")
+      .long("interactive")
+      .conflicts_with_all(&["dry-run", "explain", "print-plan", "json"])
+      .help("Ask before each rename, showing the old and new name. Answer y to rename the file, n to leave it, \
+             a to rename it and all remaining files without asking, or q to leave it and all remaining files. \
+             Only works when run from a terminal."))
+
+    .arg(Arg::with_name("yes")
+      .short("y")
+      .long("yes")
+      .help("Don't ask before renaming more than --confirm-above files. \
+             Needed to rename that many files when not run from a terminal."))
+
+    .arg(Arg::with_name("confirm-above")
+      .long("confirm-above")
+      .takes_value(true)
+      .value_name("N")
+      .validator(|value| value.parse::<usize>().map(|_| ()).map_err(|_| String::from("must be a non-negative number")))
+      .help("Show how many files would be renamed and ask before going on, if it is more than N. Defaults to 100."))
+
+    .arg(Arg::with_name("force")
+      .long("force")
+      .help("Also rename files whose name already matches the format for their date, e.g. from an earlier run. \
+             By default, they are left as they are, so that their numbers don't change."))
+
+    .arg(Arg::with_name("overwrite")
+      .long("overwrite")
+      .help("If a file with the new name of a file already exists, replace it if it is identical to the file or smaller, \
+             e.g. a worse copy of the same image, instead of giving the file a -2 suffix. The replaced files are deleted, and can't be restored by --undo. \
+             This is what other tools call --force, which datier already uses for renaming files that are named correctly."))
+
+    .arg(Arg::with_name("only-missing")
+      .long("only-missing")
+      .conflicts_with("force")
+      .help("Leave out the files whose name already matches the format entirely, without listing them as skipped, \
+             to only see the files that still need renaming."))
+
+    .arg(Arg::with_name("case")
+      .long("case")
+      .takes_value(true)
+      .value_name("CASE")
+      .possible_values(&["lower", "upper", "preserve"])
+      .help("Write the new names, including their extensions, in lower or upper case, e.g. when {make} or {model} are used. \
+             preserve (the default) leaves the format and the values of its placeholders as they are."))
+
+    .arg(Arg::with_name("normalize-ext")
+      .long("normalize-ext")
+      .help("Write the extensions of the new names in lowercase, and use jpg for jpeg and jpe, and tiff for tif. \
+             Sidecars get lowercase extensions as well. By default, files keep their extension as it is."))
+
+    .arg(Arg::with_name("output")
+      .long("output")
+      .takes_value(true)
+      .value_name("DIRECTORY")
+      .conflicts_with("deep-in-place")
+      .help("Put the renamed (or with --copy, copied) files into this folder instead of the input folder. \
+             It is created if it doesn't exist."))
+
+    .arg(Arg::with_name("stash-others")
+      .long("stash-others")
+      .takes_value(true)
+      .value_name("DIRECTORY")
+      .conflicts_with_all(&["copy", "files-from"])
+      .help("Move the files that aren't images (or, with --sidecar, their sidecars) into this folder, e.g. the .LRV and .CTG files of a card dump, \
+             so that only the renamed images are left. They keep the folders they were in below the input folder, and are searched for \
+             like the images, e.g. in sub-folders with --deep. Like the renames, the moves can be undone with --undo."))
+
+    .arg(Arg::with_name("mirror")
+      .long("mirror")
+      .requires("output")
+      .conflicts_with("flat")
+      .help("Like --deep, also search sub-directories, but recreate them in the --output folder, \
+             so that input/trip/a.jpg is moved to output/trip. The counter within each day starts over for every directory."))
+
+    .arg(Arg::with_name("sidecar")
+      .long("sidecar")
+      .help("Also rename sidecar files next to each renamed file that share its name, e.g. IMG_1234.xmp for IMG_1234.cr2, \
+             so that they keep matching."))
+
+    .arg(Arg::with_name("sidecar-ext")
+      .long("sidecar-ext")
+      .takes_value(true)
+      .value_name("EXTENSIONS")
+      .use_delimiter(true)
+      .multiple(true)
+      .requires("sidecar")
+      .help("Comma-separated list of the extensions of sidecar files, replacing the default list (xmp, thm)."))
+
+    .arg(Arg::with_name("dedupe")
+      .long("dedupe")
+      .takes_value(true)
+      .min_values(0)
+      .value_name("MODE")
+      .possible_values(&["report", "delete"])
+      .help("Skip files that were taken at the same time as another file and have the same contents, only renaming the first of them. \
+             With --dedupe=delete, the duplicates are deleted, which can't be undone."))
+
+    .arg(Arg::with_name("strict")
+      .long("strict")
+      .help("Stop without renaming anything if any file or folder could not be read while searching for files, \
+             or if two files would end up with the same name, and stop renaming if an --exec command fails. \
+             By default, such paths are only reported with -l, and skipped."))
+
+    .arg(Arg::with_name("on-error")
+      .long("on-error")
+      .takes_value(true)
+      .value_name("POLICY")
+      .possible_values(&["skip", "abort", "retry"])
+      .help("What to do when a file can't be renamed. skip (the default) reports it and goes on with the other files, \
+             abort stops renaming, and retry tries it two more times before skipping it."))
+
+    .arg(Arg::with_name("min-dimension")
+      .long("min-dimension")
+      .takes_value(true)
+      .value_name("WxH")
+      .validator(|value| value.parse::<Dimensions>().map(|_| ()))
+      .help("Skip images smaller than this, e.g. 640x480 to leave out thumbnails. Portrait images are compared the other way round."))
+
+    .arg(Arg::with_name("max-dimension")
+      .long("max-dimension")
+      .takes_value(true)
+      .value_name("WxH")
+      .validator(|value| value.parse::<Dimensions>().map(|_| ()))
+      .help("Skip images larger than this. Portrait images are compared the other way round."))
+
+    .arg(Arg::with_name("unknown-dimension")
+      .long("unknown-dimension")
+      .takes_value(true)
+      .value_name("POLICY")
+      .possible_values(&["process", "skip"])
+      .help("Whether files whose size isn't known, e.g. videos, are renamed (process, the default) or skipped with --min-dimension or --max-dimension."))
+
+    .arg(Arg::with_name("transactional")
+      .long("transactional")
+      .help("Stop at the first file that can't be renamed, and rename the files renamed up to then back, so that either all files \
+             are renamed or none. Commands run by --exec are not undone."))
+
+    .arg(Arg::with_name("verify")
+      .long("verify")
+      .help("Check that each file is there under its new name after renaming it. Copies, and files moved to another drive, \
+             are also compared to the original by their hash, before the original is removed. A file that fails the check counts \
+             as not renamed, see --on-error, unless it was renamed but isn't a file under its new name, which is reported with that name."))
+
+    .arg(Arg::with_name("exec")
+      .long("exec")
+      .takes_value(true)
+      .value_name("COMMAND")
+      .allow_hyphen_values(true)
+      .validator(|value| if value.trim().is_empty() { Err(String::from("must not be empty")) } else { Ok(()) })
+      .help("Run a command after each successful rename, with {} replaced by the new path, e.g. --exec \"touch {}\". \
+             If the command has no {}, the path is added as its last argument. The command is split at whitespace \
+             and run directly, not through a shell. It is not run in a dry run."))
+
+    .arg(Arg::with_name("format")
+      .short("f")
+      .long("format")
+      .takes_value(true)
+      .value_name("TEMPLATE")
+      .help("Template for the new file names. Placeholders: {year}, {month}, {day}, {hour}, {minute}, {second}, {n} \
+             (the counter within each day), {month_name} and {month_abbr} (see --locale), {place} (see --places), {make} and {model} of the camera, {lens} and {focal} (e.g. 50mm), \
+             {subdir}, the folder of the file within the input folder, {parent}, the name of the folder the file is in, and {session} (see --session-gap). A width can be given as e.g. {n:4} to zero-pad the value. \
+             Defaults to \"{year}_{month}_{day}-{n:4}\"."))
+
+    .arg(Arg::with_name("template-file")
+      .long("template-file")
+      .takes_value(true)
+      .value_name("FILE")
+      .help("Read the template for the new file names from FILE, see --format. Its lines are trimmed and joined, \
+             and lines starting with # are comments. --format on the command line takes precedence."))
+
+    .arg(Arg::with_name("prefix")
+      .long("prefix")
+      .takes_value(true)
+      .value_name("TEXT")
+      .help("Put TEXT in front of every new file name, e.g. --prefix Wedding_. Characters that can't be part of a file name are replaced by _."))
+
+    .arg(Arg::with_name("suffix")
+      .long("suffix")
+      .takes_value(true)
+      .value_name("TEXT")
+      .help("Put TEXT at the end of every new file name, before the extension, like --prefix."))
+
+    .arg(Arg::with_name("locale")
+      .long("locale")
+      .takes_value(true)
+      .value_name("LANGUAGE")
+      .help("The language of {month_name} and {month_abbr}, e.g. de or fr_FR. Available are en, de, fr, es, it, nl and pt; \
+             others fall back to English. Defaults to English."))
+
+    .arg(Arg::with_name("places")
+      .long("places")
+      .takes_value(true)
+      .value_name("FILE")
+      .help("A file of named places for the {place} placeholder, with one place per line as \"name, latitude, longitude\", \
+             optionally followed by a radius in km (10 by default). Each photo gets the name of the closest place \
+             whose radius includes its GPS position."))
+
+    .arg(Arg::with_name("place-default")
+      .long("place-default")
+      .takes_value(true)
+      .value_name("TEXT")
+      .help("What {place} expands to for files without a GPS position, or one that isn't near any of the places. \
+             Defaults to \"unknown\"."))
+
+    .arg(Arg::with_name("camera-default")
+      .long("camera-default")
+      .takes_value(true)
+      .value_name("TEXT")
+      .help("What {make} and {model} expand to for files that don't name their camera. Defaults to nothing."))
+
+    .arg(Arg::with_name("lens-default")
+      .long("lens-default")
+      .takes_value(true)
+      .value_name("TEXT")
+      .help("What {lens} and {focal} expand to for files that don't name their lens or focal length. Defaults to nothing."))
+
+    .arg(Arg::with_name("counter-start")
+      .long("counter-start")
+      .takes_value(true)
+      .value_name("N")
+      .validator(|value| value.parse::<u32>().map(|_| ()).map_err(|_| String::from("must be a non-negative number")))
+      .help("The number {n} starts at for the first file of each day. Defaults to 1."))
+
+    .arg(Arg::with_name("session-gap")
+      .long("session-gap")
+      .takes_value(true)
+      .value_name("DURATION")
+      .validator(|value| parse_duration(&value).map(|_| ()))
+      .help("Start a new session whenever more than this passes between two photos, e.g. 30m. \
+             The counter then starts over for each session instead of each day, and {session} is the number of the session, starting at 1."))
+
+    .arg(Arg::with_name("continue-counter")
+      .long("continue-counter")
+      .help("Start the numbers of each day after the highest one used by files that already have a name of this format in the destination folder, e.g. when adding photos to a folder renamed before."))
+
+    .arg(Arg::with_name("counter-width")
+      .long("counter-width")
+      .takes_value(true)
+      .value_name("W")
+      .validator(|value| match value.parse::<usize>() {
+        Ok(width) if width > 0 && width <= MAX_FIELD_WIDTH => Ok(()),
+        _ => Err(format!("must be a number from 1 to {}", MAX_FIELD_WIDTH)),
+      })
+      .help("Zero-pad {n} to this width, overriding the width given in the template. \
+             Numbers that need more digits are never cut off."))
 
-  let l = Logger::new(matches.is_present("log"));
-  let dry_run = matches.is_present("dry-run");
-  let deep = matches.is_present("deep");
+    .arg(Arg::with_name("auto-width")
+      .long("auto-width")
+      .conflicts_with("counter-width")
+      .help("Zero-pad {n} only as far as the largest number of its day needs, e.g. 1 to 3 for three photos, \
+             and 001 to 150 for 150 photos, instead of to the width given in the template."))
 
-  let input_dir_str = String::from(matches.value_of("input directory").unwrap());
-  let input_dir = Path::new(&input_dir_str);
-  if !input_dir.is_dir() {
-    l.error(format_args!("Input path {} is not a directory!", input_dir.display()));
-    return;
+    .arg(Arg::with_name("group-by")
+      .long("group-by")
+      .takes_value(true)
+      .value_name("PERIOD")
+      .possible_values(&["year", "month", "day"])
+      .help("Move the renamed files into sub-folders by date, e.g. 2021/ for year, 2021/2021-03/ for month, \
+             or 2021/2021-03-15/ for day. Missing folders are created."))
+
+    .arg(Arg::with_name("tie-break")
+      .long("tie-break")
+      .takes_value(true)
+      .value_name("MODE")
+      .possible_values(&["collision-suffix", "keep-both-increment", "skip"])
+      .help("How to name files taken at exactly the same time. collision-suffix (the default) gives them the same number \
+             followed by -a, -b, ..., keep-both-increment gives each its own number, and skip only renames the first one."))
+
+    .arg(Arg::with_name("numbering")
+      .long("numbering")
+      .takes_value(true)
+      .value_name("SCOPE")
+      .possible_values(&["global", "per-day", "per-folder"])
+      .help("Which files share a counter. per-day (the default) starts {n} over for each day, per-folder for each folder \
+             the files end up in, and global numbers all files in one sequence. Larger numbers than the width of {n} \
+             are written out in full, so a wider --counter-width keeps the names aligned."))
+
+    .arg(Arg::with_name("since")
+      .long("since")
+      .takes_value(true)
+      .value_name("YYYY-MM-DD")
+      .validator(|value| OrdDateTime::parse_date(&value).map(|_| ()))
+      .help("Only rename files taken on or after this date."))
+
+    .arg(Arg::with_name("until")
+      .long("until")
+      .takes_value(true)
+      .value_name("YYYY-MM-DD")
+      .validator(|value| OrdDateTime::parse_date(&value).map(|_| ()))
+      .help("Only rename files taken on or before this date."))
+
+    .arg(Arg::with_name("ext")
+      .long("ext")
+      .takes_value(true)
+      .value_name("EXTENSIONS")
+      .use_delimiter(true)
+      .multiple(true)
+      .help("Comma-separated list of file extensions to rename, replacing the default list (jpg, jpeg, cr2, tif, tiff, dng, heic, heif, mp4, mov)."))
+
+    .arg(Arg::with_name("add-ext")
+      .long("add-ext")
+      .takes_value(true)
+      .value_name("EXTENSIONS")
+      .use_delimiter(true)
+      .multiple(true)
+      .help("Comma-separated list of file extensions to rename in addition to the default or --ext list."))
+
+    .arg(Arg::with_name("exclude")
+      .long("exclude")
+      .takes_value(true)
+      .value_name("PATTERN")
+      .multiple(true)
+      .number_of_values(1)
+      .validator(|value| glob::Pattern::new(&value).map(|_| ()).map_err(|error| error.to_string()))
+      .help("Skip files matching this glob pattern, relative to the input folder, e.g. \"thumbnails/*\" or \"*_small.jpg\". \
+             * also matches across folders. Can be given multiple times."))
+
+    .arg(Arg::with_name("date-tag")
+      .long("date-tag")
+      .takes_value(true)
+      .value_name("TAGS")
+      .use_delimiter(true)
+      .multiple(true)
+      .validator(|value| value.parse::<DateTag>().map(|_| ()))
+      .help("Comma-separated list of the exif fields to read the date from, in order of priority. \
+             Possible values: datetime, original, digitized. Defaults to datetime,original,digitized."))
+
+    .arg(Arg::with_name("undo")
+      .long("undo")
+      .takes_value(true)
+      .value_name("DIRECTORY")
+      .conflicts_with("input directory")
+      .help("Revert the renames of the last run in the given folder, as recorded in its journal file."))
+
+    .arg(Arg::with_name("restore-original")
+      .long("restore-original")
+      .takes_value(true)
+      .value_name("DIRECTORY")
+      .conflicts_with_all(&["input directory", "undo"])
+      .help("Rename the files in the given folder back to the names they had before datier first renamed them, \
+             e.g. the names the camera gave them, over all runs recorded in its journal file."))
+
+    .arg(Arg::with_name("list-tags")
+      .long("list-tags")
+      .takes_value(true)
+      .min_values(0)
+      .value_name("FILE")
+      .conflicts_with_all(&["input directory", "undo"])
+      .help("Print the exif fields datier reads, in the order they are tried, and exit. \
+             Given a file, also print the values these fields have in it, and the date datier would use. Nothing is renamed."))
+
+    .arg(Arg::with_name("display")
+      .long("display")
+      .takes_value(true)
+      .value_name("STYLE")
+      .possible_values(&["plain", "table", "tree"])
+      .help("How a dry run shows the planned renames: plain prints a line for each file with -l, table lines up the original names, \
+             dates and new names in columns, and tree shows the folders and files as they will be afterwards. Defaults to plain."))
+
+    .arg(Arg::with_name("validate")
+      .long("validate")
+      .conflicts_with_all(&["undo", "list-tags", "watch", "interactive", "apply-plan"])
+      .help("Only read the dates of the files, and print how many of them have a usable date and what is wrong with the others. \
+             Nothing is renamed, and no plan is made."))
+
+    .arg(Arg::with_name("find-duplicates")
+      .long("find-duplicates")
+      .conflicts_with_all(&["undo", "list-tags", "validate", "watch", "interactive", "apply-plan"])
+      .help("Only print the groups of files that have the same contents, e.g. to clean them up before renaming. \
+             Nothing is renamed, and no plan is made. With --json, each group is printed as one JSON object."))
+
+    .arg(Arg::with_name("use-offset")
+      .long("use-offset")
+      .help("Order files by the actual moment they were taken, using the time zone offset stored with the date. \
+             By default, files are ordered by their local time, ignoring any offsets."))
+
+    .arg(Arg::with_name("ignore-subseconds")
+      .long("ignore-subseconds")
+      .help("Don't read the subseconds of the dates, for cameras that write values which can't be relied on. \
+             Files taken in the same second are then ordered as if they had no subseconds."))
+
+    .arg(Arg::with_name("assume-offset")
+      .long("assume-offset")
+      .takes_value(true)
+      .value_name("OFFSET")
+      .allow_hyphen_values(true)
+      .validator(|value| parse_offset(&value).map(|_| ()))
+      .help("The time zone offset assumed for files without one when using --use-offset or --to-timezone, e.g. +02:00. Defaults to +00:00."))
+
+    .arg(Arg::with_name("to-timezone")
+      .long("to-timezone")
+      .takes_value(true)
+      .value_name("TIMEZONE")
+      .validator(|value| value.parse::<chrono_tz::Tz>().map(|_| ()).map_err(|_| format!("Unknown time zone \"{}\", expected e.g. Europe/Berlin", value)))
+      .help("Convert all dates into the local time of this time zone, e.g. Europe/Berlin, before sorting and naming the files. \
+             Each date is converted from the time zone offset stored with it, or from --assume-offset if it has none."))
+
+    .arg(Arg::with_name("shift")
+      .long("shift")
+      .takes_value(true)
+      .value_name("SHIFT")
+      .allow_hyphen_values(true)
+      .validator(|value| parse_shift(&value).map(|_| ()))
+      .help("Shift all dates by this amount before sorting and naming the files, to correct a camera clock that was set wrong. \
+             Given as e.g. +3h, -2d or +1h30m, using the units d, h, m and s. The dates stored in the files are not changed."))
+
+    .arg(Arg::with_name("rewrite-exif")
+      .long("rewrite-exif")
+      .requires("shift")
+      .help("Also write the dates shifted with --shift back into the exif data of the renamed files. \
+             Only the date fields are changed, all other metadata is kept."))
+
+    .arg(Arg::with_name("keep-original-name")
+      .long("keep-original-name")
+      .help("Store the name each file had before it was first renamed in its user.datier.original extended attribute. \
+             On file systems without extended attributes, the original names are only recorded in the journal."))
+
+    .arg(Arg::with_name("mtime-tolerance")
+      .long("mtime-tolerance")
+      .takes_value(true)
+      .value_name("DURATION")
+      .validator(|value| parse_duration(&value).map(|_| ()))
+      .help("Skip files whose date differs from their modification time by more than this, e.g. 7d or 12h, \
+             as the camera likely wrote a wrong date."))
+
+    .arg(Arg::with_name("fallback-to-mtime")
+      .long("fallback-to-mtime")
+      .help("Instead of skipping files with a suspicious date (see --mtime-tolerance), use their modification time. \
+             PNG files without any date in them are dated by their modification time as well."))
+
+    .arg(Arg::with_name("reject-future")
+      .long("reject-future")
+      .help("Skip files dated more than a day after the current time, as the camera's clock was likely set wrong, \
+             instead of numbering them after all the others."))
+
+    .arg(Arg::with_name("parse-name")
+      .long("parse-name")
+      .takes_value(true)
+      .value_name("REGEX")
+      .validator(|value| parse_name_pattern(&value).map(|_| ()))
+      .help("For files without a date in their metadata, such as scans or screenshots, read it from their file name instead, \
+             using a regular expression with the named groups year, month and day, and optionally hour, minute and second, \
+             e.g. \"(?P<year>\\d{4})-(?P<month>\\d{2})-(?P<day>\\d{2})\"."))
+
+    .arg(Arg::with_name("threads")
+      .long("threads")
+      .takes_value(true)
+      .value_name("N")
+      .validator(|value| match value.parse::<usize>() {
+        Ok(threads) if threads > 0 => Ok(()),
+        _ => Err(String::from("must be a positive number")),
+      })
+      .help("The number of threads used to read image metadata. Defaults to the number of logical cores."))
+
+    .arg(Arg::with_name("no-cache")
+      .long("no-cache")
+      .help("Read the metadata of every file, instead of using what was read from the unchanged files in earlier runs. \
+             The cache is kept in the user's cache folder."))
+
+    .arg(Arg::with_name("clear-cache")
+      .long("clear-cache")
+      .help("Remove the cached metadata of the input folder before reading the files, which are then cached anew."))
+
+    .arg(Arg::with_name("no-config")
+      .long("no-config")
+      .help("Ignore any datier.toml config file in the input folder or the user's config folder. \
+             A flag set in the config file can also be turned off with its --no- form, e.g. --no-dry-run, \
+             or --color and --cache for no-color and no-cache."));
+
+  for &(flag, negation) in FLAG_NEGATIONS {
+    app = app.arg(Arg::with_name(negation).long(negation).overrides_with(flag).hidden(true));
   }
+  let matches = app.get_matches();
 
-  let extensions = ["jpg", "jpeg", "cr2"];
-  let patterns = extensions.iter().map(|ext| {
-    let mut pattern = input_dir_str.clone();
-    if deep {
-      pattern.extend("/**".chars());
+  let config = if matches.is_present("no-config") {
+    Config::default()
+  } else {
+    let input = matches.value_of("input directory").or_else(|| matches.value_of("files-from").map(|_| "."));
+    match Config::find(input.map(|input| input_folder(Path::new(input)))) {
+      Ok(config) => config,
+      Err(error) => {
+        Logger::new(verbosity(matches.is_present("quiet"), matches.is_present("log")), matches.is_present("json")).error(format_args!("{}", error));
+        return ExitCode::from(EXIT_ERROR);
+      }
     }
-    pattern.extend("/*.".chars());
-    pattern.extend(ext.chars());
-    pattern
-  });
+  };
+  let matches = Settings::new(&matches, config);
+
+  let report = matches.value_of("report");
+  let print_plan = match matches.value_of("print-plan") {
+    Some(format) => Some(format.parse::<PlanFormat>().unwrap()), // checked by possible_values
+    None if matches.on_command_line("print-plan") => Some(PlanFormat::Json),
+    None => None,
+  };
+  let explain = matches.is_present("explain");
+  let emit_script = matches.value_of("emit-script");
+  let compare = matches.value_of("compare");
+  let dry_run = matches.is_present("dry-run") || explain || emit_script.is_some() || print_plan.is_some() || compare.is_some();
+  let limit = matches.value_of("limit").map(|value| value.parse::<usize>().unwrap()); // checked by the validator
+    // the plan is all that is printed, apart from errors
+    // -l can't be given together with --quiet, so it only meets a quiet from the config, which it beats
+  let quiet = matches.is_present("quiet") && !matches.on_command_line("log");
+  let l = Logger::new(verbosity(quiet || print_plan.is_some(), matches.is_present("log")), matches.is_present("json"))
+    .with_records(report.is_some())
+    .with_colors(!matches.is_present("no-color"))
+    .with_limit(limit);
+  if limit.is_some() && !dry_run {
+    l.error(format_args!("--limit only applies to dry runs, as all files are shown when renaming them"));
+    return ExitCode::from(EXIT_ERROR);
+  }
+    // read before anything else, as the report may be written again by this run
+  let previous_report = match compare {
+    Some(ref report) => match PreviousReport::read(Path::new(report)) {
+      Ok(previous) => Some(previous),
+      Err(error) => {
+        l.error(format_args!("{}", error));
+        return ExitCode::from(EXIT_ERROR);
+      }
+    },
+    None => None,
+  };
+  let display = matches.value_of("display").map_or(PlanDisplay::Plain, |display| display.parse().unwrap()); // checked by clap or the config
+  let in_place = matches.is_present("deep-in-place") || (matches.is_present("files-from") && !matches.is_present("output"));
+  let copy = matches.is_present("copy");
+    // only taken from the command line, as a config file can't know whether datier runs in a terminal
+  let interactive = matches.is_present("interactive");
+  let yes = matches.is_present("yes");
+  let confirm_above = matches.value_of("confirm-above").map_or(DEFAULT_CONFIRM_ABOVE, |value| value.parse().unwrap()); // checked by the validator
+  let flat = matches.is_present("flat");
+  let mirror = matches.is_present("mirror");
+  let dedupe = match matches.value_of("dedupe") {
+    Some(mode) => Some(mode),
+    None if matches.is_present("dedupe") => Some(String::from("report")),
+    None => None,
+  };
+  let delete_duplicates = dedupe.as_deref() == Some("delete");
+  let deep = matches.is_present("deep") || in_place || flat || mirror;
+  let follow_symlinks = matches.is_present("follow-symlinks");
+  let max_depth = matches.value_of("max-depth").map(|depth| depth.parse::<usize>().unwrap()); // checked by the validator
+    // like --interactive, only taken from the command line
+  let watch = matches.is_present("watch");
+
+  if let Some(threads) = matches.value_of("threads") {
+    let threads = threads.parse::<usize>().unwrap(); // checked by the validator
+    if let Err(error) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global() {
+      l.error(format_args!("Could not set up {} threads: {}", threads, error));
+      return ExitCode::from(EXIT_ERROR);
+    }
+  }
+
+  if matches.on_command_line("assume-offset") && !matches.is_present("use-offset") && !matches.is_present("to-timezone") {
+    l.error(format_args!("--assume-offset only applies with --use-offset or --to-timezone"));
+    return ExitCode::from(EXIT_ERROR);
+  }
+
+  if let Some(undo_dir) = matches.value_of("undo") {
+    return run_undo(undo(Path::new(&undo_dir)), &l);
+  }
+  if let Some(restore_dir) = matches.value_of("restore-original") {
+    return run_undo(restore_first_names(Path::new(&restore_dir)), &l);
+  }
+
+  if matches.is_present("list-tags") {
+    return list_tags(matches.value_of("list-tags").as_deref().map(Path::new), &read_options(&matches), &l);
+  }
+
+    // a --format given on the command line beats a --template-file, which beats a format from the config file
+  let template_file = if matches.on_command_line("format") { None } else { matches.value_of("template-file") };
+  let template_str = match template_file {
+    Some(template_file) => match read_template_file(Path::new(&template_file)) {
+      Ok(template_str) => template_str,
+      Err(error) => {
+        l.error(format_args!("Could not read template file {}: {}", template_file, error));
+        return ExitCode::from(EXIT_ERROR);
+      }
+    },
+    None => matches.value_of("format").unwrap_or_else(|| String::from(DEFAULT_TEMPLATE)),
+  };
+  let mut template = match Template::parse(&template_str) {
+    Ok(template) => template,
+    Err(error) => {
+      l.error(format_args!("Invalid format template \"{}\": {}", template_str, error));
+      return ExitCode::from(EXIT_ERROR);
+    }
+  };
+
+  if let Some(locale) = matches.value_of("locale") {
+    match locale.parse::<Locale>() {
+      Ok(locale) => template.set_locale(locale),
+      Err(error) => l.warn(format_args!("{}, using English month names", error)),
+    }
+  }
+
+  let places = match matches.value_of("places") {
+    Some(places_file) => match Places::read(Path::new(&places_file)) {
+      Ok(places) => Some(places),
+      Err(error) => {
+        l.error(format_args!("{}", error));
+        return ExitCode::from(EXIT_ERROR);
+      }
+    },
+    None if template.has_place() => {
+      l.error(format_args!("The {{place}} placeholder needs a list of places, given with --places"));
+      return ExitCode::from(EXIT_ERROR);
+    },
+    None => None,
+  };
+
+  if let Some(width) = matches.value_of("counter-width") {
+    template.set_counter_width(width.parse().unwrap()); // checked by the validator
+  }
+  template.set_auto_width(matches.is_present("auto-width"));
+  template.set_case(matches.value_of("case").map(|case| case.parse::<NameCase>().unwrap()).unwrap_or_default()); // checked by possible_values or the config
+  template.add_affixes(&matches.value_of("prefix").unwrap_or_default(), &matches.value_of("suffix").unwrap_or_default());
+
+  if interactive && !std::io::stdin().is_terminal() {
+    l.error(format_args!("--interactive needs a terminal to ask in, use --dry-run to check the renames instead"));
+    return ExitCode::from(EXIT_ERROR);
+  }
+
+    // a list of files is renamed as if the current folder had been searched with --deep-in-place, but finding only them
+  let files_from = matches.value_of("files-from");
+  let input_str = match files_from {
+    Some(_) => String::from("."),
+    None => matches.value_of("input directory").unwrap(),
+  };
+  let input_path = Path::new(&input_str);
+    // a single file is renamed on its own, as if it were the only file in its folder
+  let single_file = input_path.is_file();
+  let input_dir = input_folder(input_path);
+  if !single_file && !input_dir.is_dir() {
+    l.error(format_args!("Input path {} is neither a folder nor a file!", input_path.display()));
+    return ExitCode::from(EXIT_INVALID_INPUT);
+  }
+  if watch && (single_file || dry_run) {
+    l.error(format_args!("--watch needs a folder to watch, and can't be used in a dry run"));
+    return ExitCode::from(EXIT_ERROR);
+  }
 
-  let all_paths = patterns
-    .filter_map(|pattern| { // make a glob iterator for each pattern
-      let mut options = MatchOptions::new();
-      options.case_sensitive = false;
-      match glob_with(&pattern, options) {
-        Ok(paths) => Some(paths),
+  let mut extensions: Vec<String> = Vec::new();
+  let base_extensions: Vec<String> = match matches.values_of("ext") {
+    Some(values) => values,
+    None => DEFAULT_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+  };
+  for ext in base_extensions.into_iter().chain(matches.values_of("add-ext").into_iter().flatten()) {
+      // globbing is case-insensitive, so "JPG" and "jpg" would otherwise match the same files twice
+    let ext = ext.trim().trim_start_matches('.').to_lowercase();
+    if !ext.is_empty() && !extensions.contains(&ext) {
+      extensions.push(ext);
+    }
+  }
+
+  if extensions.is_empty() {
+    l.error(format_args!("No file extensions to search for!"));
+    return ExitCode::from(EXIT_ERROR);
+  }
+
+  let mut times = PhaseTimes::default();
+  let search_start = Instant::now();
+  let found = match input_path.file_name() {
+    _ if files_from.is_some() => match read_file_list(files_from.as_deref().unwrap()) {
+      Ok(paths) => FoundFiles { paths, errors: Vec::new() },
+      Err(error) => {
+        l.error(format_args!("Could not read the list of files: {}", error));
+        return ExitCode::from(EXIT_ERROR);
+      }
+    },
+    Some(name) if single_file => FoundFiles { paths: vec![input_dir.join(name)], errors: Vec::new() },
+    _ => match find_files(input_dir, &extensions, deep, max_depth, follow_symlinks) {
+      Ok(found) => found,
+      Err(error) => {
+        l.error(format_args!("Could not read glob pattern: {}", error));
+        return ExitCode::from(EXIT_ERROR);
+      }
+    },
+  };
+
+  if matches.is_present("strict") && !found.errors.is_empty() {
+    for error in &found.errors {
+      l.error(format_args!("Could not search {}: {}", error.path.display(), error.source));
+    }
+    l.error(format_args!("Stopping because of {} errors while searching for files", found.errors.len()));
+    return ExitCode::from(EXIT_ERROR);
+  }
+  for error in &found.errors {
+    l.log(format_args!("Could not search {}: {}", error.path.display(), error.source));
+  }
+  let exclude_patterns: Vec<glob::Pattern> = matches.values_of("exclude").into_iter().flatten()
+    .map(|pattern| glob::Pattern::new(&pattern).unwrap()) // checked by the validator
+    .collect();
+  let (paths, excluded) = exclude_paths(found.paths, input_dir, &exclude_patterns);
+  for path in &excluded {
+    l.log(format_args!("{} excluded", path.display()));
+  }
+    // a file given on its own, or in a list, is renamed even if it is hidden or ignored
+  let include_hidden = matches.is_present("include-hidden");
+  let paths = if single_file || files_from.is_some() {
+    paths
+  } else {
+    match skip_hidden_and_ignored(paths, input_dir, include_hidden, &l) {
+      Ok(paths) => paths,
+      Err(error) => {
+        l.error(format_args!("{}", error));
+        return ExitCode::from(EXIT_ERROR);
+      }
+    }
+  };
+
+  times.searching = search_start.elapsed();
+
+  let read = read_options(&matches);
+  if matches.is_present("validate") {
+    return validate(&paths, &read, &l);
+  }
+  if matches.is_present("find-duplicates") {
+    let hash_progress = l.progress(paths.len(), "Hashing files");
+    let groups = find_duplicates(&paths, &|| hash_progress.inc(1));
+    hash_progress.finish_and_clear();
+    for group in &groups {
+      l.duplicates(group);
+    }
+    let extra_copies: usize = groups.iter().map(|group| group.len() - 1).sum();
+    l.info(format_args!("Groups of identical files: {}, extra copies: {}", groups.len(), extra_copies));
+    return ExitCode::SUCCESS;
+  }
+  let cache_file = cache_path(input_dir);
+  if matches.is_present("clear-cache") {
+    if let Err(error) = cache_file.as_deref().map_or(Ok(()), MetadataCache::clear) {
+      l.warn(format_args!("{}", error));
+    }
+  }
+  let cache = match cache_file {
+    Some(cache_file) if !matches.is_present("no-cache") => Some(MetadataCache::load(cache_file, &read)),
+    _ => None,
+  };
+
+  let sidecar_extensions = if !matches.is_present("sidecar") {
+    Vec::new()
+  } else {
+    match matches.values_of("sidecar-ext") {
+      Some(values) => values.iter().map(|ext| ext.trim().trim_start_matches('.').to_lowercase()).filter(|ext| !ext.is_empty()).collect(),
+      None => DEFAULT_SIDECAR_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+    }
+  };
+
+    // the sidecars stay with their files, so only files that are neither are stashed
+  let stash_dir = matches.value_of("stash-others").map(PathBuf::from);
+  let others = match stash_dir {
+    Some(ref stash_dir) => {
+      let known: Vec<String> = extensions.iter().chain(&sidecar_extensions).cloned().collect();
+      let found = match find_other_files(input_dir, &known, deep, max_depth, follow_symlinks) {
+        Ok(found) => found,
+        Err(error) => {
+          l.error(format_args!("Could not read glob pattern: {}", error));
+          return ExitCode::from(EXIT_ERROR);
+        }
+      };
+      let (paths, _) = exclude_paths(found.paths, input_dir, &exclude_patterns);
+      match files_to_stash(paths, input_dir, stash_dir, include_hidden) {
+        Ok(paths) => paths,
+        Err(error) => {
+          l.error(format_args!("{}", error));
+          return ExitCode::from(EXIT_ERROR);
+        }
+      }
+    },
+    None => Vec::new(),
+  };
+
+  let opts = RenameOptions {
+    template,
+    target_dir: match matches.value_of("output") {
+      Some(output_dir) => Some(PathBuf::from(output_dir)),
+      None if in_place => None,
+      None => Some(input_dir.to_path_buf()),
+    },
+    mirror,
+    input_dir: input_dir.to_path_buf(),
+    read,
+    group_by: matches.value_of("group-by").map(|value| value.parse::<GroupBy>().unwrap()), // checked by possible_values
+    since: matches.value_of("since").map(|value| OrdDateTime::parse_date(&value).unwrap()), // checked by the validator
+    until: matches.value_of("until").map(|value| OrdDateTime::parse_date(&value).unwrap()),
+    tie_break: matches.value_of("tie-break").map(|value| value.parse::<TieBreak>().unwrap()).unwrap_or(TieBreak::CollisionSuffix),
+    numbering: matches.value_of("numbering").map(|value| value.parse::<NumberingScope>().unwrap()).unwrap_or_default(),
+    counter_start: matches.value_of("counter-start").map(|value| value.parse().unwrap()).unwrap_or(1),
+    continue_counter: matches.is_present("continue-counter"),
+    session_gap: matches.value_of("session-gap").map(|value| parse_duration(&value).unwrap()), // checked by the validator
+    copy,
+    mtime_tolerance: matches.value_of("mtime-tolerance").map(|value| parse_duration(&value).unwrap()), // checked by the validator
+    fallback_to_mtime: matches.is_present("fallback-to-mtime"),
+    reject_future: matches.is_present("reject-future"),
+    parse_name: matches.value_of("parse-name").map(|pattern| parse_name_pattern(&pattern).unwrap()), // checked by the validator
+    sidecar_extensions,
+    places,
+    place_default: matches.value_of("place-default").unwrap_or_else(|| String::from("unknown")),
+    camera_default: matches.value_of("camera-default").unwrap_or_default(),
+    lens_default: matches.value_of("lens-default").unwrap_or_default(),
+    force: matches.is_present("force"),
+    overwrite: matches.is_present("overwrite"),
+    normalize_ext: matches.is_present("normalize-ext"),
+    flat,
+    dedupe: dedupe.is_some(),
+    transactional: matches.is_present("transactional"),
+    verify: matches.is_present("verify"),
+    min_dimensions: matches.value_of("min-dimension").map(|value| value.parse().unwrap()), // checked by the validator
+    max_dimensions: matches.value_of("max-dimension").map(|value| value.parse().unwrap()),
+    skip_unknown_dimensions: matches.value_of("unknown-dimension").as_deref() == Some("skip"),
+    on_error: matches.value_of("on-error").map(|value| value.parse::<OnError>().unwrap()).unwrap_or(OnError::Skip), // checked by possible_values
+    cache,
+  };
+
+  let names_dir = opts.target_dir.as_deref().unwrap_or(input_dir);
+  let mut no_usable_dates = false;
+  let mut plan = match matches.value_of("apply-plan") {
+    Some(manifest_file) => {
+      let manifest = match Manifest::read(Path::new(&manifest_file)) {
+        Ok(manifest) => manifest,
         Err(error) => {
-          l.error(format_args!("Could not read glob pattern {}: {}", pattern, error));
-          None
+          l.error(format_args!("{}", error));
+          return ExitCode::from(EXIT_ERROR);
         }
+      };
+      let hash_progress = l.progress(paths.len(), "Hashing files");
+      let read_start = Instant::now();
+      let plan = manifest.plan(&paths, names_dir, &|| hash_progress.inc(1));
+      times.reading = read_start.elapsed();
+      hash_progress.finish_and_clear();
+      plan
+    },
+    None => {
+      let read_progress = l.progress(paths.len(), "Reading dates");
+        // the dates are read first, so the time after the last one was read is spent on sorting and naming the files
+      let read_start = Instant::now();
+      let last_read = Mutex::new(read_start);
+      let plan = plan_renames_with_progress(&paths, &opts, &|| {
+        read_progress.inc(1);
+        *last_read.lock().unwrap() = Instant::now();
+      });
+      let last_read = last_read.into_inner().unwrap();
+      times.reading = last_read - read_start;
+      times.planning = last_read.elapsed();
+      read_progress.finish_and_clear();
+      let offsets = distinct_offsets(&plan);
+      if offsets.len() > 1 && !opts.read.use_offset && opts.read.to_timezone.is_none() {
+        let offsets: Vec<String> = offsets.into_iter().map(format_offset).collect();
+        l.warn(format_args!("The files were taken in different time zones ({}), so they may be out of order. --use-offset orders them by the time they were actually taken", offsets.join(", ")));
+      }
+        // otherwise nothing at all would be printed, which looks as if datier didn't run. while watching, the files may still come
+      if !watch && plan.iter().all(|entry| entry.datetime.is_none()) {
+        l.warn(format_args!("No images with usable dates found (scanned {} files)", paths.len()));
+        no_usable_dates = true;
       }
-    }).flatten() // combine all iterators into a single iteratore over all matching items
-    .filter_map(|glob_result| glob_result.ok()); // unwrap, and filter out any matched items that still errored
+      plan
+    },
+  };
+  if matches.is_present("only-missing") {
+    plan.retain(|entry| !matches!(entry.action, PlanAction::Skip(SkipReason::AlreadyNamed)));
+  }
+  if interactive {
+    if let Err(error) = ask_for_renames(&mut plan) {
+      l.error(format_args!("Could not ask for the renames: {}", error));
+      return ExitCode::from(EXIT_ERROR);
+    }
+  }
+    // the plan avoids conflicts already, so this is only a safety net in case it missed one
+  let conflicts = check_destinations(&mut plan, copy, opts.overwrite);
+  if !conflicts.is_empty() && matches.is_present("strict") {
+    for dest in &conflicts {
+      l.error(format_args!("{} is the destination of more than one file, or of a file that stays where it is", dest.display()));
+    }
+    l.error(format_args!("Stopping because of {} conflicting destinations", conflicts.len()));
+    return ExitCode::from(EXIT_ERROR);
+  }
+  let renames = PlanSummary::of(&plan).renames;
+  if !dry_run && !interactive && !yes && renames > confirm_above {
+    if !std::io::stdin().is_terminal() {
+      l.error(format_args!("Stopping before renaming {} files, as that is more than {}. Pass --yes to rename them without asking", renames, confirm_above));
+      return ExitCode::from(EXIT_ERROR);
+    }
+    match confirm_renames(&plan, renames, copy) {
+      Ok(true) => {},
+      Ok(false) => {
+        l.info(format_args!("Nothing was renamed"));
+        return ExitCode::SUCCESS;
+      },
+      Err(error) => {
+        l.error(format_args!("Could not ask for the renames: {}", error));
+        return ExitCode::from(EXIT_ERROR);
+      },
+    }
+  }
+  if dry_run {
+    for dir in planned_dirs(&plan) {
+      l.log(format_args!("Would create directory {}", dir.display()));
+    }
+    if let Some(ref stash_dir) = stash_dir {
+      for path in &others {
+        l.log(format_args!("Would move {} to {}", path.display(), stash_dest(path, input_dir, stash_dir).display()));
+      }
+    }
+  }
 
-  let mut invalid_entries: Vec<(PathBuf, GetDateTimeError)> = Vec::new();
-  let mut valid_entries: Vec<(PathBuf, OrdDateTime)> = Vec::new();
+  let mut files_failed = false;
+  if let Some(ref script) = emit_script {
+    let script = Path::new(script);
+    if let Err(error) = write_script(&plan, copy, script, ScriptStyle::for_path(script)) {
+      l.error(format_args!("Could not write script {}: {}", script.display(), error));
+      files_failed = true;
+    }
+  }
+    // the files are hashed before they are renamed, as --rewrite-exif changes their contents
+  if let Some(ref manifest_file) = matches.value_of("export-plan") {
+    if let Err(error) = Manifest::from_plan(&plan, names_dir).and_then(|manifest| manifest.write(Path::new(manifest_file))) {
+      l.error(format_args!("{}", error));
+      files_failed = true;
+    }
+  }
+  let exec = matches.value_of("exec");
+  let strict = matches.is_present("strict");
+  let rename_start = Instant::now();
+  let results = if !dry_run {
+    let rename_progress = l.progress(PlanSummary::of(&plan).renames, if copy { "Copying" } else { "Renaming" });
+    let results = execute_plan_with_progress(&plan, &opts, &mut |result| {
+      rename_progress.inc(1);
+      if let (Some(ref command), Ok(())) = (&exec, &result.outcome) {
+        if let Err(error) = run_exec(command, &result.dest, l.is_json()) {
+          l.error(format_args!("Command for {} {}", result.dest.display(), error));
+          files_failed = true;
+          return !strict;
+        }
+      }
+      true
+    });
+    rename_progress.finish_and_clear();
+    warn_not_moved_back(&results, &l);
+    results
+  } else {
+    Vec::new()
+  };
+  times.renaming = rename_start.elapsed();
+  let stats = matches.is_present("stats");
 
-  for path in all_paths {
-    let datetime_res = get_datetime(&path);
-    match datetime_res {
-      Err(error) => invalid_entries.push((path, error)),
-      Ok(datetime) => valid_entries.push((path, datetime.into())),
+    // duplicates are only deleted once the files they duplicate have been renamed
+  let rolled_back = results.iter().any(|result| matches!(result.outcome, Err(RenameError::RolledBack)));
+  let mut deleted: HashMap<&Path, std::io::Result<()>> = HashMap::new();
+  if delete_duplicates && !dry_run && !rolled_back {
+    for entry in &plan {
+      if let PlanAction::Skip(SkipReason::Duplicate(_)) = entry.action {
+        deleted.insert(&entry.source, std::fs::remove_file(&entry.source));
+      }
     }
   }
 
-  for (path, error) in invalid_entries {
-    l.log(format_args!("{} skipped ({})", path.display(), error));
+  if matches.is_present("rewrite-exif") {
+      // videos have no exif data to update
+    for result in results.iter().filter(|result| result.outcome.is_ok() && !is_video(&result.dest)) {
+      if let Err(error) = rewrite_dates(&result.dest, opts.read.shift) {
+        l.error(format_args!("Could not update the dates of {}: {}", result.dest.display(), error));
+        files_failed = true;
+      }
+    }
   }
 
-  if valid_entries.len() == 0 {
-    return;
+  if matches.is_present("keep-original-name") {
+    files_failed |= keep_original_names(&results, copy, &l);
   }
 
-  valid_entries.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+  let stashed = match stash_dir {
+    Some(ref stash_dir) if !dry_run && !rolled_back => stash_files(&others, input_dir, stash_dir),
+    _ => Vec::new(),
+  };
+  for result in &stashed {
+    match result.outcome {
+      Ok(()) => l.log(format_args!("{} -> {} (stashed)", result.source.display(), result.dest.display())),
+      Err(ref error) => {
+        l.error(format_args!("Could not move {} to {}: {}", result.source.display(), result.dest.display(), error));
+        files_failed = true;
+      },
+    }
+  }
+  if let Some(ref stash_dir) = stash_dir {
+    let moved = stashed.iter().filter(|result| result.outcome.is_ok()).count();
+    if moved > 0 {
+      l.info(format_args!("Moved {} other files to {}", moved, stash_dir.display()));
+    }
+  }
 
-  let mut img_number = 1;
-  let mut prev_datetime = &valid_entries[0].1;
-  for (ref path, ref datetime) in &valid_entries {
-    if !datetime.date_eq(prev_datetime) {
-      img_number = 1;
+    // copies leave the originals in place, so there is nothing to undo
+  let mut journal = Journal::for_next_run(input_dir);
+  journal.add_results(input_dir, &results);
+  journal.add_results(input_dir, &stashed);
+  if !copy && !journal.entries.is_empty() {
+    if let Err(error) = journal.write(input_dir) {
+      l.error(format_args!("{}", error));
+      files_failed = true; // the renames can't be undone
     }
+  }
 
-    if datetime != prev_datetime {
-      if datetime.date_eq(prev_datetime) {
-        img_number += 1;
-      } else {
-        img_number = 1;
+  if let Some(ref cache) = opts.cache {
+    if !copy {
+      for result in results.iter().filter(|result| result.outcome.is_ok()) {
+        cache.renamed(&result.source, &result.dest);
       }
     }
+    if let Err(error) = cache.save() {
+      l.warn(format_args!("{}", error));
+    }
+  }
+
+  if let Some(format) = print_plan {
+    if let Err(error) = l.print_plan(&plan, format) {
+      l.error(format_args!("Could not print the plan: {}", error));
+      return ExitCode::from(EXIT_ERROR);
+    }
+    l.record_plan(&plan, copy);
+  } else if explain {
+    let counter_group = match opts.numbering {
+      NumberingScope::PerFolder if opts.session_gap.is_some() => "session in its folder",
+      _ if opts.session_gap.is_some() => "session",
+      NumberingScope::Global => "run",
+      NumberingScope::PerDay if in_place || mirror => "day in its folder",
+      NumberingScope::PerDay => "day",
+      NumberingScope::PerFolder => "folder",
+    };
+    for entry in &plan {
+      l.explain(entry, counter_group);
+    }
+    l.not_shown();
+    l.dry_run_summary(&PlanSummary::of(&plan), copy);
+    l.record_plan(&plan, copy);
+  } else {
+      // the results are looked up by file, as a stopped run has none for the files it didn't get to
+    let mut outcomes: HashMap<PathBuf, Result<(), RenameError>> = results.into_iter().map(|result| (result.source, result.outcome)).collect();
 
-    let new_stem = format!("{}_{:02}_{:02}-{:04}", datetime.0.year, datetime.0.month, datetime.0.day, img_number);
-    
-    prev_datetime = &datetime;
-
-    if let Some(ext) = path.extension() {
-      let new_filename = format!("{}.{}", new_stem, ext.to_string_lossy());
-      let mut rename_dest = input_dir.to_path_buf();
-      rename_dest.push(new_filename);
-      if !rename_dest.exists() {
-        let rename_action = if !dry_run {
-          let rename_res = fs::rename(&path, &rename_dest);
-          match rename_res {
-            Ok(()) => true,
-            Err(error) => {
-              l.log(format_args!("{} skipped (Rename failed: {})", path.display(), error));
-              false
-            }
+    for entry in &plan {
+      let datetime = entry.datetime.as_ref();
+      if let Subseconds::Invalid(ref value) = entry.subseconds {
+        l.log(format_args!("Could not parse the subseconds \"{}\" of {}, using its date without them", value, entry.source.display()));
+      }
+      match entry.action {
+        PlanAction::Skip(SkipReason::Duplicate(ref original)) if delete_duplicates => match deleted.remove(entry.source.as_path()) {
+          Some(Ok(())) => l.deleted(&entry.source, original, datetime),
+          Some(Err(error)) => l.failed(&entry.source, &entry.source, &format_args!("Could not delete duplicate: {}", error), datetime),
+          None => l.skipped(&entry.source, &format_args!("Duplicate of {}, would be deleted", original.display()), datetime),
+        },
+        PlanAction::Skip(ref reason) => l.skipped(&entry.source, reason, datetime),
+        PlanAction::Rename(ref dest) => {
+          if let Some(ref main_file) = entry.sidecar_of {
+            l.log(format_args!("{} is a sidecar of {}", entry.source.display(), main_file.display()));
+          }
+          match entry.date_source {
+            DateSource::ModifiedTime => l.log(format_args!("{} has a suspicious date, using its modification time instead", entry.source.display())),
+            DateSource::FileName => l.log(format_args!("{} has no date in its metadata, using the one in its name", entry.source.display())),
+            DateSource::Metadata => {},
           }
-        } else {
-          true
-        };
+          match outcomes.remove(&entry.source) { // there are no results in a dry run
+            Some(Err(error)) => l.failed(&entry.source, dest, &error, datetime),
+            None if !dry_run => l.skipped(&entry.source, &"Not renamed, as the run was stopped", datetime),
+            _ if copy => l.copied(&entry.source, dest, datetime),
+            _ => l.renamed(&entry.source, dest, datetime),
+          }
+        }
+      }
+    }
+    l.not_shown();
 
-        if rename_action {
-          l.log(format_args!("{} -> {}", path.display(), rename_dest.display()));
+    if watch {
+      let files = WatchedFiles { dir: input_dir, extensions: &extensions, deep, max_depth, follow_symlinks, exclude: &exclude_patterns, include_hidden };
+      let actions = WatchActions { copy, exec: exec.as_deref(), strict, keep_original_name: matches.is_present("keep-original-name") };
+      match watch::watch(&files, &actions, &opts, &mut journal, &l) {
+        Ok(failed) => files_failed |= failed,
+        Err(error) => {
+          l.error(format_args!("{}", error));
+          files_failed = true;
         }
-      } else {
-        l.log(format_args!("{} skipped (Would rename, but {} already exists", path.display(), rename_dest.display()));
       }
-    } else {
-      l.log(format_args!("{} skipped (Has no extension)", path.display()));
     }
   }
+
+  files_failed |= !write_report(report.as_deref(), dry_run, &l);
+
+  if print_plan.is_none() && !explain {
+    l.summary(dry_run);
+    if dry_run {
+      l.plan(&plan, names_dir, display);
+      if let Some(ref previous) = previous_report {
+        l.changes(&previous.changes(&plan));
+      }
+      l.dry_run_summary(&PlanSummary::of(&plan), copy);
+    }
+  }
+  if stats {
+    l.stats(paths.len(), &times);
+  }
+
+  if files_failed || l.errored() > 0 {
+    ExitCode::from(EXIT_FILES_FAILED)
+  } else if no_usable_dates {
+    ExitCode::from(EXIT_NO_DATES)
+  } else {
+    ExitCode::SUCCESS
+  }
 }
 
-struct Logger {
-  print_logs: bool
+fn read_options(matches: &Settings)->ReadOptions {
+  ReadOptions {
+    date_tags: match matches.values_of("date-tag") {
+      Some(values) => values.iter().map(|value| value.parse::<DateTag>().unwrap()).collect(), // checked by the validator
+      None => DEFAULT_DATE_TAGS.to_vec(),
+    },
+    use_offset: matches.is_present("use-offset"),
+    assumed_offset: matches.value_of("assume-offset").map(|value| parse_offset(&value).unwrap()).unwrap_or(0),
+    shift: matches.value_of("shift").map(|value| parse_shift(&value).unwrap()).unwrap_or(0),
+    to_timezone: matches.value_of("to-timezone").map(|value| value.parse().unwrap()),
+    local_timezone: system_timezone(),
+    ignore_subseconds: matches.is_present("ignore-subseconds"),
+  }
 }
 
-impl Logger {
-  fn new(print_logs: bool)->Logger {
-    Logger {
-      print_logs
+  // asks for each planned rename whether it should be done, and turns the declined ones into skips
+  // sidecars follow the answer for their file
+fn ask_for_renames(plan: &mut [RenamePlan])->std::io::Result<()> {
+  let mut declined: HashSet<PathBuf> = HashSet::new();
+  let mut answer_for_all = None;
+  for entry in plan.iter_mut() {
+    let dest = match entry.action {
+      PlanAction::Rename(ref dest) if entry.sidecar_of.is_none() => dest.clone(),
+      _ => continue,
+    };
+    let rename = match answer_for_all {
+      Some(rename) => rename,
+      None => loop {
+        print!("{} -> {} [y/N/a/q] ", entry.source.display(), dest.display());
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+          answer = String::from("q"); // the input ended, so there is nobody left to ask
+        }
+        match answer.trim().to_lowercase().as_str() {
+          "y" | "yes" => break true,
+          "" | "n" | "no" => break false,
+          "a" | "all" => {
+            answer_for_all = Some(true);
+            break true;
+          },
+          "q" | "quit" => {
+            answer_for_all = Some(false);
+            break false;
+          },
+          _ => continue,
+        }
+      },
+    };
+    if !rename {
+      declined.insert(entry.source.clone());
+      entry.action = PlanAction::Skip(SkipReason::Declined);
     }
   }
 
-  fn log(&self, args: std::fmt::Arguments) {
-    if self.print_logs {
-      println!("{}", args);
+  for entry in plan.iter_mut() {
+    if entry.sidecar_of.as_ref().is_some_and(|main_file| declined.contains(main_file)) {
+      entry.action = PlanAction::Skip(SkipReason::Declined);
     }
   }
+  Ok(())
+}
 
-  fn error(&self, args: std::fmt::Arguments) {
-    println!("Error: {}", args);
+  // shows how many files would be renamed, along with the first few of them, and asks whether to go on
+  // the question goes to stderr, so that it doesn't end up in --json output
+fn confirm_renames(plan: &[RenamePlan], renames: usize, copy: bool)->std::io::Result<bool> {
+  let mut stderr = std::io::stderr();
+  let sample = plan.iter().filter_map(|entry| match entry.action {
+    PlanAction::Rename(ref dest) => Some((&entry.source, dest)),
+    PlanAction::Skip(_) => None,
+  });
+  for (source, dest) in sample.take(CONFIRM_SAMPLE) {
+    writeln!(stderr, "  {} -> {}", source.display(), dest.display())?;
   }
+  if renames > CONFIRM_SAMPLE {
+    writeln!(stderr, "  ... and {} more", renames - CONFIRM_SAMPLE)?;
+  }
+  write!(stderr, "{} {} files? [y/N] ", if copy { "Copy" } else { "Rename" }, renames)?;
+  stderr.flush()?;
+  let mut answer = String::new();
+  std::io::stdin().read_line(&mut answer)?;
+  Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
 }
 
-#[derive(Debug, Snafu)]
-enum GetDateTimeError {
-  #[snafu(display("Could not open file: {}", source))]
-  FileOpenError {
-    source: std::io::Error,
-  },
-  #[snafu(display("Could not create exif reader: {}", source))]
-  ReaderCreateError {
-    source: exif::Error,
-  },
-  #[snafu(display("Could not read DateTime field: {}", source))]
-  FieldReadError {
-    source: DateTimeReadError
-  },
+  // the template in a template file, which can be spread over several lines and have comments
+fn read_template_file(path: &Path)->std::io::Result<String> {
+  let contents = std::fs::read_to_string(path)?;
+  Ok(contents.lines().map(str::trim).filter(|line| !line.starts_with('#')).collect())
 }
 
-fn get_datetime<P: AsRef<Path>>(path: P)->Result<exif::DateTime, GetDateTimeError> {
-  let file = File::open(path).context(FileOpenError)?;
-  let reader = exif::Reader::new(&mut std::io::BufReader::new(&file)).context(ReaderCreateError)?;
-
-  let datetime = read_datetime(&reader).context(FieldReadError)?;
-  Ok(datetime)
+  // the folder of the input path, which is the path itself unless it is a file
+fn input_folder(input: &Path)->&Path {
+  if input.is_file() {
+    input.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."))
+  } else {
+    input
+  }
 }
 
-#[derive(Debug, Snafu)]
-enum DateTimeReadError {
-  #[snafu(display("DateTime field is missing."))]
-  FieldMissing,
-  #[snafu(display("DateTime field is not in ascii format."))]
-  FieldNotAscii,
-  #[snafu(display("DateTime field contains no data."))]
-  FieldEmpty,
-  #[snafu(display("DateTime field data could not be parsed: {}", source))]
-  ParseError {
-    source: exif::Error,
-  },
+  // --quiet wins over -l if both come from the config file
+fn verbosity(quiet: bool, log: bool)->Verbosity {
+  if quiet {
+    Verbosity::Quiet
+  } else if log {
+    Verbosity::Verbose
+  } else {
+    Verbosity::Normal
+  }
 }
 
-fn read_datetime(exif_reader: &exif::Reader)->Result<exif::DateTime, DateTimeReadError> {
-  let date_time_field = exif_reader.get_field(exif::Tag::DateTime, false);
-
-  ensure!(date_time_field.is_some(), FieldMissing);
-  let date_time_data = date_time_field.unwrap();
+  // the NUL-separated paths in the file, or in the standard input for -, leaving out repeated ones
+fn read_file_list(source: &str)->std::io::Result<Vec<PathBuf>> {
+  let contents = if source == "-" {
+    let mut contents = Vec::new();
+    std::io::stdin().read_to_end(&mut contents)?;
+    contents
+  } else {
+    std::fs::read(source)?
+  };
+  let mut seen = HashSet::new();
+  Ok(contents.split(|&byte| byte == 0)
+    .filter(|path| !path.is_empty())
+    .map(path_from_bytes)
+    .filter(|path| seen.insert(path.clone()))
+    .collect())
+}
 
-  let mut date_time = 
-    if let exif::Value::Ascii(ref datetime_ascii) = date_time_data.value {
-      let datetime_string = datetime_ascii.first();
-      
-      ensure!(datetime_string.is_some(), FieldEmpty);
-      let datetime_string = datetime_string.unwrap();
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8])->PathBuf {
+  use std::os::unix::ffi::OsStrExt;
+  PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
 
-      exif::DateTime::from_ascii(datetime_string).context(ParseError)?
-    } else {
-      return FieldNotAscii.fail();
-    };
+  // elsewhere, paths aren't arbitrary bytes, and the list is expected to be UTF-8
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8])->PathBuf {
+  PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
 
-  if let Some(subsec_data) = exif_reader.get_field(exif::Tag::SubSecTime, false) {
-    if let exif::Value::Ascii(ref subsec_ascii) = subsec_data.value {
-      if let Some(subsec_string) = subsec_ascii.first() {
-        let _ = date_time.parse_subsec(subsec_string); // ignore any parse error
-      }
+  // leaves out the hidden files, unless include_hidden is set, and the files matched by the ignore file of dir
+fn skip_hidden_and_ignored(paths: Vec<PathBuf>, dir: &Path, include_hidden: bool, l: &Logger)->Result<Vec<PathBuf>, IgnoreFileError> {
+  let paths = if include_hidden {
+    paths
+  } else {
+    let (paths, hidden) = hidden_paths(paths, dir);
+    for path in &hidden {
+      l.log(format_args!("{} skipped, as it is hidden", path.display()));
     }
+    paths
+  };
+  let (paths, ignored) = ignored_paths(paths, dir)?;
+  for path in &ignored {
+    l.log(format_args!("{} ignored, as {} says so", path.display(), IGNORE_FILE_NAME));
   }
-
-  Ok(date_time)
+  Ok(paths)
 }
 
-  // wrapper type around DateTime that adds ordering based on the time
-  // note that for ordering and equality, the offset value is ignored
-#[derive(Debug)]
-struct OrdDateTime(exif::DateTime);
+  // the files found by find_other_files that --stash-others moves, which leaves out hidden and ignored files like skip_hidden_and_ignored,
+  // the files datier keeps in the input folder itself, and the files already in the stash folder
+fn files_to_stash(paths: Vec<PathBuf>, dir: &Path, stash_dir: &Path, include_hidden: bool)->Result<Vec<PathBuf>, IgnoreFileError> {
+  let paths = if include_hidden { paths } else { hidden_paths(paths, dir).0 };
+  let (paths, _) = ignored_paths(paths, dir)?;
+  let own_files = [JOURNAL_FILE_NAME, IGNORE_FILE_NAME, CONFIG_FILE_NAME];
+  let stash_dir = stash_dir.canonicalize().ok();
+  Ok(paths.into_iter()
+    .filter(|path| !path.file_name().is_some_and(|name| own_files.iter().any(|own| name == *own)))
+    .filter(|path| !stash_dir.as_ref().is_some_and(|stash_dir| path.canonicalize().is_ok_and(|path| path.starts_with(stash_dir))))
+    .collect())
+}
 
-impl From<exif::DateTime> for OrdDateTime {
-  fn from(datetime: exif::DateTime)->Self {
-    Self(datetime)
+  // writes the report if one was asked for, and returns whether that went well
+fn write_report(report: Option<&str>, dry_run: bool, l: &Logger)->bool {
+  match report.map(|report| (report, l.write_report(Path::new(report), dry_run))) {
+    Some((report, Err(error))) => {
+      l.error(format_args!("Could not write report {}: {}", report, error));
+      false
+    },
+    _ => true,
   }
 }
 
-impl std::fmt::Display for OrdDateTime {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>)->std::fmt::Result {
-    write!(f, "{}", self.0)
+  // points out the files that were left at a temporary name, whatever the verbosity, as they have to be renamed back by hand
+fn warn_not_moved_back(results: &[RenameResult], l: &Logger) {
+  for result in results {
+    if let Err(RenameError::NotMovedBack { ref temporary, ref restore_error, .. }) = result.outcome {
+      l.warn(format_args!("{} was left as {}, as it couldn't be moved back: {}", result.source.display(), temporary.display(), restore_error));
+    }
   }
 }
 
-use std::cmp::Ordering;
-impl Ord for OrdDateTime {
-    // Note that this ignores the offset field
-  fn cmp(&self, other: &Self)->Ordering {
-            self.0.year.cmp(&other.0.year)
-      .then(self.0.month.cmp(&other.0.month))
-      .then(self.0.day.cmp(&other.0.day))
-      .then(self.0.hour.cmp(&other.0.hour))
-      .then(self.0.minute.cmp(&other.0.minute))
-      .then(self.0.second.cmp(&other.0.second))
-      .then(self.0.nanosecond.cmp(&other.0.nanosecond))
+  // stores the original name of each renamed file in an extended attribute, and returns whether that failed for any of them
+  // a file system without extended attributes is only pointed out once, as the journal has the original names as well
+fn keep_original_names(results: &[RenameResult], copy: bool, l: &Logger)->bool {
+  let mut failed = false;
+  let mut unsupported = false;
+  for result in results.iter().filter(|result| result.outcome.is_ok()) {
+    match keep_original_name(&result.dest, &result.source) {
+      Ok(()) => {},
+      Err(ref error) if error.kind() == std::io::ErrorKind::Unsupported => unsupported = true,
+      Err(error) => {
+        l.error(format_args!("Could not keep the original name of {}: {}", result.dest.display(), error));
+        failed = true;
+      },
+    }
   }
+  if unsupported {
+    let fallback = if copy { "the originals still have them" } else { "they are only recorded in the journal" };
+    l.warn(format_args!("Could not keep the original names in extended attributes, as the file system doesn't support them, so {}", fallback));
+  }
+  failed
 }
 
-impl PartialOrd for OrdDateTime {
-  fn partial_cmp(&self, other: &Self)->Option<Ordering> {
-    Some(self.cmp(other))
+  // runs the --exec command for a renamed file
+  // with JSON output, the command's output goes to stderr, so that stdout only holds JSON
+fn run_exec(command: &str, path: &Path, json: bool)->Result<(), String> {
+  let mut args: Vec<OsString> = Vec::new();
+  let mut has_placeholder = false;
+  for arg in command.split_whitespace() {
+    if arg == "{}" {
+      args.push(path.as_os_str().to_os_string());
+    } else {
+      args.push(OsString::from(arg.replace("{}", &path.to_string_lossy())));
+    }
+    has_placeholder |= arg.contains("{}");
+  }
+  if !has_placeholder {
+    args.push(path.as_os_str().to_os_string());
+  }
+
+  let mut child = Command::new(&args[0]);
+  child.args(&args[1..]);
+  if json {
+    child.stdout(Stdio::from(std::io::stderr()));
+  }
+  match child.status() {
+    Ok(status) if status.success() => Ok(()),
+    Ok(status) => Err(format!("failed ({})", status)),
+    Err(error) => Err(format!("could not be started: {}", error)),
   }
 }
 
-impl PartialEq for OrdDateTime {
-    // Note that this ignores the offset field
-  fn eq(&self, other: &Self)->bool {
-    self.0.year       == other.0.year       &&
-    self.0.month      == other.0.month      &&
-    self.0.day        == other.0.day        &&
-    self.0.hour       == other.0.hour       &&
-    self.0.minute     == other.0.minute     &&
-    self.0.second     == other.0.second     &&
-    self.0.nanosecond == other.0.nanosecond
+  // prints the fields consulted with the given options, and their values in file if there is one
+fn list_tags(file: Option<&Path>, read: &ReadOptions, l: &Logger)->ExitCode {
+  let tags = consulted_tags(read);
+  let file = match file {
+    Some(file) => file,
+    None => {
+      for tag in &tags {
+        println!("{}", tag);
+      }
+      return ExitCode::SUCCESS;
+    },
+  };
+
+  if is_video(file) {
+    println!("{} is a video, its date is read from the creation time of its mp4/mov container", file.display());
+  } else {
+    match read_tag_values(file, &tags) {
+      Ok(values) => for (tag, value) in values {
+        println!("{}: {}", tag, value.as_deref().unwrap_or("(missing)"));
+      },
+      Err(error) => {
+        l.error(format_args!("Could not read {}: {}", file.display(), error));
+        return ExitCode::from(EXIT_FILES_FAILED);
+      },
+    }
+  }
+  match get_metadata(file, read) {
+    Ok(metadata) => println!("Date used: {}", metadata.datetime),
+    Err(error) => println!("No date can be used: {}", error),
   }
+  ExitCode::SUCCESS
 }
 
-impl Eq for OrdDateTime {}
+  // reads the date of each file, without the cache, and counts the ones that can be used and the problems with the others
+fn validate(paths: &[PathBuf], read: &ReadOptions, l: &Logger)->ExitCode {
+  let read_progress = l.progress(paths.len(), "Reading dates");
+  let results: Vec<_> = paths.par_iter().map(|path| {
+    let result = get_metadata(path, read);
+    read_progress.inc(1);
+    result
+  }).collect();
+  read_progress.finish_and_clear();
 
-impl OrdDateTime {
-  fn date_eq(&self, other: &OrdDateTime)->bool {
-    self.0.year == other.0.year && self.0.month == other.0.month && self.0.day == other.0.day
+  let mut summary = ValidationSummary::default();
+  for (path, result) in paths.iter().zip(&results) {
+    summary.add(path, result);
+    match *result {
+      Err(ref error) => l.info(format_args!("{}: {}", path.display(), error)),
+      Ok(_) if path.extension().is_none() => l.info(format_args!("{}: has no extension", path.display())),
+      Ok(ref metadata) => l.log(format_args!("{}: {}", path.display(), metadata.datetime)),
+    }
   }
-}
\ No newline at end of file
+  l.validation_summary(&summary);
+  if summary.invalid() > 0 { ExitCode::from(EXIT_FILES_FAILED) } else { ExitCode::SUCCESS }
+}
+
+fn run_undo(undone: Result<Vec<UndoResult>, JournalError>, l: &Logger)->ExitCode {
+  match undone {
+    Ok(results) => {
+      let mut files_failed = false;
+      for result in results {
+        match result.outcome {
+          Ok(()) => l.renamed(&result.renamed, &result.original, None),
+          Err(error) => {
+            l.skipped(&result.renamed, &error, None);
+            files_failed = true;
+          },
+        }
+      }
+      l.summary(false);
+      if files_failed { ExitCode::from(EXIT_FILES_FAILED) } else { ExitCode::SUCCESS }
+    },
+    Err(error) => {
+      l.error(format_args!("{}", error));
+      ExitCode::from(EXIT_ERROR)
+    },
+  }
+}
@@ -0,0 +1,115 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::metadata::Subseconds;
+use crate::plan::{content_hash, DateSource, PlanAction, RenamePlan, SkipReason};
+
+  // the planned renames of a run, keyed by the contents of the files rather than their paths
+  // so that the same names can be given to copies of the files somewhere else, e.g. on another machine
+  // the names are relative to the folder the files were renamed in
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+  pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    // the SHA-256 of the file's contents, in hex
+  pub hash: String,
+  pub name: PathBuf,
+}
+
+#[derive(Debug, Snafu)]
+pub enum ManifestError {
+  #[snafu(display("Could not read plan file {}: {}", path.display(), source))]
+  ManifestRead {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("Could not parse plan file {}: {}", path.display(), source))]
+  ManifestParse {
+    path: PathBuf,
+    source: serde_json::Error,
+  },
+  #[snafu(display("Could not write plan file {}: {}", path.display(), source))]
+  ManifestWrite {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("Could not read {} to hash it: {}", path.display(), source))]
+  HashFailed {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+}
+
+impl Manifest {
+    // hashes the files renamed by the plan, and records their new names relative to dir
+  pub fn from_plan(plan: &[RenamePlan], dir: &Path)->Result<Manifest, ManifestError> {
+    let renames: Vec<(&PathBuf, &PathBuf)> = plan.iter().filter_map(|entry| match entry.action {
+      PlanAction::Rename(ref dest) => Some((&entry.source, dest)),
+      PlanAction::Skip(_) => None,
+    }).collect();
+    let entries = renames.par_iter()
+      .map(|&(source, dest)| Ok(ManifestEntry {
+        hash: hex(&content_hash(source).context(HashFailed { path: source.clone() })?),
+        name: dest.strip_prefix(dir).unwrap_or(dest).to_path_buf(),
+      }))
+      .collect::<Result<Vec<ManifestEntry>, ManifestError>>()?;
+    Ok(Manifest { entries })
+  }
+
+  pub fn read(path: &Path)->Result<Manifest, ManifestError> {
+    let contents = fs::read_to_string(path).context(ManifestRead { path: path.to_path_buf() })?;
+    serde_json::from_str(&contents).context(ManifestParse { path: path.to_path_buf() })
+  }
+
+  pub fn write(&self, path: &Path)->Result<(), ManifestError> {
+    let contents = serde_json::to_string_pretty(self).expect("manifest entries are always serializable");
+    fs::write(path, contents).context(ManifestWrite { path: path.to_path_buf() })
+  }
+
+    // plans to rename each of the paths whose contents are in the manifest to the name given there, relative to dir
+    // if several files have the same contents, they are given the names of that contents in order, and the ones left over are skipped as duplicates
+  pub fn plan(&self, paths: &[PathBuf], dir: &Path, on_hashed: &(dyn Fn() + Sync))->Vec<RenamePlan> {
+    let mut names: HashMap<&str, VecDeque<&PathBuf>> = HashMap::new();
+    for entry in &self.entries {
+      names.entry(entry.hash.as_str()).or_default().push_back(&entry.name);
+    }
+    let hashes: Vec<std::io::Result<String>> = paths.par_iter()
+      .map(|path| {
+        let hash = content_hash(path).map(|hash| hex(&hash));
+        on_hashed();
+        hash
+      })
+      .collect();
+
+      // the file that was given a name, for the files left over with the same contents
+    let mut named: HashMap<String, PathBuf> = HashMap::new();
+    paths.iter().zip(hashes).map(|(path, hash)| {
+      let action = match hash {
+        Err(error) => PlanAction::Skip(SkipReason::Unhashable(error)),
+        Ok(hash) => match names.get_mut(hash.as_str()).and_then(VecDeque::pop_front) {
+          Some(name) => {
+            named.entry(hash).or_insert_with(|| path.clone());
+            let dest = dir.join(name);
+            if dest == *path { PlanAction::Skip(SkipReason::AlreadyNamed) } else { PlanAction::Rename(dest) }
+          },
+          None => match named.get(&hash) {
+            Some(original) => PlanAction::Skip(SkipReason::Duplicate(original.clone())),
+            None => PlanAction::Skip(SkipReason::NotInManifest),
+          },
+        },
+      };
+      RenamePlan { source: path.clone(), datetime: None, date_source: DateSource::Metadata, subseconds: Subseconds::Missing, offset: None, sidecar_of: None, numbering: None, modified: None, replaces: false, action }
+    }).collect()
+  }
+}
+
+fn hex(bytes: &[u8])->String {
+  bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,739 @@
+use chrono::{Offset, TimeZone};
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt, Snafu};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use crate::datetime::{shift_datetime, OrdDateTime};
+use crate::png;
+use crate::raw;
+use crate::video::{self, VideoReadError};
+
+#[derive(Debug, Snafu)]
+#[allow(clippy::enum_variant_names)]
+pub enum GetDateTimeError {
+  #[snafu(display("Could not open file: {}", source))]
+  FileOpenError {
+    source: std::io::Error,
+  },
+  #[snafu(display("Unsupported file format: the file is {}", detected_kind))]
+  UnsupportedFormat {
+    path: PathBuf,
+    detected_kind: FileKind,
+  },
+  #[snafu(display("Could not create exif reader: {}", source))]
+  ReaderCreateError {
+    source: exif::Error,
+  },
+  #[snafu(display("Could not find exif data in the HEIF container: {}", source))]
+  HeifReadError {
+    source: exif::Error,
+  },
+  #[snafu(display("Could not read date field: {}", source))]
+  FieldReadError {
+    source: DateTimeReadError
+  },
+  #[snafu(display("Could not read video date: {}", source))]
+  VideoDateError {
+    source: VideoReadError,
+  },
+}
+
+impl GetDateTimeError {
+    // whether the file could be read and simply has no date, rather than a broken one or none that could be read
+  pub fn is_missing_date(&self)->bool {
+    matches!(self,
+      GetDateTimeError::FieldReadError { source: DateTimeReadError::FieldMissing } |
+      GetDateTimeError::ReaderCreateError { source: exif::Error::NotFound(_) } |
+      GetDateTimeError::HeifReadError { source: exif::Error::NotFound(_) } |
+      GetDateTimeError::VideoDateError { source: VideoReadError::NoCreationTime })
+  }
+}
+
+  // what a file is, judging by its first bytes rather than its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileKind {
+  Jpeg,
+    // also most RAW formats, e.g. CR2, NEF or DNG
+  Tiff,
+  Heif,
+  Png,
+  WebP,
+  Gif,
+  Bmp,
+    // Canon's newer RAW format, which is an ISO media file like videos
+  Cr3,
+    // an mp4 or mov file, or another ISO media file that isn't HEIF
+  Video,
+  Pdf,
+  Zip,
+  Empty,
+  Unknown,
+}
+
+impl FileKind {
+    // whether the exif crate can read this kind of file. unknown files are handed to it anyway
+  fn has_exif_reader(self)->bool {
+    matches!(self, FileKind::Jpeg | FileKind::Tiff | FileKind::Heif | FileKind::Png | FileKind::WebP | FileKind::Unknown)
+  }
+}
+
+impl std::fmt::Display for FileKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter)->std::fmt::Result {
+    f.write_str(match *self {
+      FileKind::Jpeg => "a JPEG image",
+      FileKind::Tiff => "a TIFF image",
+      FileKind::Heif => "a HEIF image",
+      FileKind::Png => "a PNG image",
+      FileKind::WebP => "a WebP image",
+      FileKind::Gif => "a GIF image",
+      FileKind::Bmp => "a BMP image",
+      FileKind::Cr3 => "a CR3 RAW image",
+      FileKind::Video => "a video",
+      FileKind::Pdf => "a PDF document",
+      FileKind::Zip => "a ZIP archive",
+      FileKind::Empty => "empty",
+      FileKind::Unknown => "of unknown type",
+    })
+  }
+}
+
+  // how much of the start of a file detect_kind looks at
+const HEAD_LEN: usize = 16;
+
+  // finds out what a file is from its magic bytes
+pub fn detect_kind(path: &Path)->std::io::Result<FileKind> {
+  let mut head = Vec::with_capacity(HEAD_LEN);
+  File::open(path)?.take(HEAD_LEN as u64).read_to_end(&mut head)?;
+  Ok(kind_of(&head))
+}
+
+fn kind_of(head: &[u8])->FileKind {
+  if head.is_empty() {
+    return FileKind::Empty;
+  }
+  if head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+    return FileKind::Jpeg;
+  }
+  if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+    return FileKind::Tiff;
+  }
+  if head.starts_with(b"\x89PNG") {
+    return FileKind::Png;
+  }
+  if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+    return FileKind::WebP;
+  }
+  if head.starts_with(b"GIF8") {
+    return FileKind::Gif;
+  }
+  if head.starts_with(b"BM") {
+    return FileKind::Bmp;
+  }
+  if head.starts_with(b"%PDF") {
+    return FileKind::Pdf;
+  }
+  if head.starts_with(b"PK\x03\x04") {
+    return FileKind::Zip;
+  }
+  if head.get(4..8) == Some(b"ftyp") {
+    let brand = head.get(8..12).unwrap_or_default();
+    let heif_brands: [&[u8]; 7] = [b"heic", b"heix", b"heim", b"heis", b"mif1", b"msf1", b"avif"];
+    return match brand {
+      _ if heif_brands.contains(&brand) => FileKind::Heif,
+      b"crx " => FileKind::Cr3,
+      _ => FileKind::Video,
+    };
+  }
+  if matches!(head.get(4..8), Some(b"moov") | Some(b"mdat") | Some(b"wide") | Some(b"free")) {
+    return FileKind::Video;
+  }
+  FileKind::Unknown
+}
+
+  // controls which fields are used to determine the date of a file
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    // the exif fields to read the date from, in order of priority
+  pub date_tags: Vec<DateTag>,
+    // whether to read the time zone offset belonging to the date
+  pub use_offset: bool,
+    // the offset in minutes assumed for files without one, if use_offset is set
+  pub assumed_offset: i16,
+    // seconds added to every date, to correct a camera clock that was set wrong
+    // this only affects how files are sorted and named, the dates stored in the files stay as they are
+  pub shift: i64,
+    // converts every date into the local time of this time zone, from its own offset or assumed_offset
+  pub to_timezone: Option<chrono_tz::Tz>,
+    // the time zone that dates stored in UTC, such as the creation time of videos, are turned into the local time of
+    // if neither to_timezone nor use_offset is set, so that they match photos, which are in the local time of their camera
+    // if None, they stay in UTC
+  pub local_timezone: Option<chrono_tz::Tz>,
+    // leaves the subseconds out, for cameras that write values which can't be relied on
+  pub ignore_subseconds: bool,
+}
+
+impl Default for ReadOptions {
+  fn default()->Self {
+    ReadOptions {
+      date_tags: DEFAULT_DATE_TAGS.to_vec(),
+      use_offset: false,
+      assumed_offset: 0,
+      shift: 0,
+      to_timezone: None,
+      local_timezone: None,
+      ignore_subseconds: false,
+    }
+  }
+}
+
+  // whether the date of a file came with subseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Subseconds {
+  Missing,
+  Parsed,
+    // the file has a subsecond field, but its value couldn't be parsed, so the date is used without it
+  Invalid(String),
+}
+
+  // where a photo was taken, in degrees. south and west are negative
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GpsPosition {
+  pub latitude: f64,
+  pub longitude: f64,
+}
+
+  // the size of an image in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Dimensions {
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Dimensions {
+    // whether this image is at least as large as min on both sides, whichever way it is turned
+  pub fn at_least(self, min: Dimensions)->bool {
+    let (long, short, min_long, min_short) = self.sides(min);
+    long >= min_long && short >= min_short
+  }
+
+    // whether this image is at most as large as max on both sides, whichever way it is turned
+  pub fn at_most(self, max: Dimensions)->bool {
+    let (long, short, max_long, max_short) = self.sides(max);
+    long <= max_long && short <= max_short
+  }
+
+    // the long and short sides of self and other
+  fn sides(self, other: Dimensions)->(u32, u32, u32, u32) {
+    (self.width.max(self.height), self.width.min(self.height), other.width.max(other.height), other.width.min(other.height))
+  }
+}
+
+impl std::fmt::Display for Dimensions {
+  fn fmt(&self, f: &mut std::fmt::Formatter)->std::fmt::Result {
+    write!(f, "{}x{}", self.width, self.height)
+  }
+}
+
+impl std::str::FromStr for Dimensions {
+  type Err = String;
+
+    // e.g. "640x480"
+  fn from_str(s: &str)->Result<Dimensions, String> {
+    let invalid = || format!("Invalid dimensions \"{}\", expected e.g. 640x480", s);
+    let (width, height) = s.trim().split_once(['x', 'X']).ok_or_else(invalid)?;
+    Ok(Dimensions {
+      width: width.trim().parse().map_err(|_| invalid())?,
+      height: height.trim().parse().map_err(|_| invalid())?,
+    })
+  }
+}
+
+  // everything datier reads from a file. only the date is required, all other fields are None if the file doesn't have them
+#[derive(Debug)]
+pub struct FileMetadata {
+  pub datetime: exif::DateTime,
+  pub subseconds: Subseconds,
+    // the time zone offset stored with the date, in minutes, which is read even without use_offset
+  pub offset: Option<i16>,
+  pub gps: Option<GpsPosition>,
+    // the camera, as given by its manufacturer
+  pub make: Option<String>,
+  pub model: Option<String>,
+  pub lens: Option<String>,
+    // in millimeters
+  pub focal_length: Option<f64>,
+    // the number of the photo in the camera's sequence, which some cameras store
+  pub image_number: Option<u32>,
+  pub dimensions: Option<Dimensions>,
+}
+
+pub fn get_datetime<P: AsRef<Path>>(path: P, opts: &ReadOptions)->Result<exif::DateTime, GetDateTimeError> {
+  get_metadata(path, opts).map(|metadata| metadata.datetime)
+}
+
+  // like get_datetime, but also reads the other fields that file names can be based on
+pub fn get_metadata<P: AsRef<Path>>(path: P, opts: &ReadOptions)->Result<FileMetadata, GetDateTimeError> {
+  if video::is_video(path.as_ref()) {
+    let creation_time = video::creation_time(path.as_ref()).context(VideoDateError)?;
+    return Ok(metadata_from_unix(creation_time, opts));
+  }
+
+  let metadata = read_exif(path.as_ref()).and_then(|reader| match read_metadata(&reader, opts) {
+      // some RAW files only have a date in the exif data of their embedded preview
+    Err(DateTimeReadError::FieldMissing) if raw::has_embedded_preview(path.as_ref()) => preview_metadata(&reader, opts).context(FieldReadError),
+    metadata => metadata.context(FieldReadError),
+  });
+    // PNG files seldom have exif data, but may have the time they were last changed
+  match metadata {
+    Err(error) if png::is_png(path.as_ref()) => png::modification_time(path.as_ref()).map(|time| metadata_from_unix(time, opts)).map_err(|_| error),
+    metadata => metadata,
+  }
+}
+
+  // the metadata of the first embedded preview of a RAW file that has a date
+fn preview_metadata(exif: &exif::Exif, opts: &ReadOptions)->Result<FileMetadata, DateTimeReadError> {
+  raw::embedded_previews(exif).into_iter()
+    .filter_map(|preview| exif::Reader::new().read_from_container(&mut Cursor::new(preview)).ok())
+    .find_map(|preview_exif| read_metadata(&preview_exif, opts).ok())
+    .ok_or(DateTimeReadError::FieldMissing)
+}
+
+  // the metadata of a file whose only date is in UTC, such as a video
+fn metadata_from_unix(unix_time: i64, opts: &ReadOptions)->FileMetadata {
+  FileMetadata {
+    datetime: datetime_from_unix(unix_time + opts.shift, opts),
+    subseconds: Subseconds::Missing,
+    offset: None,
+    gps: None,
+    make: None,
+    model: None,
+    lens: None,
+    focal_length: None,
+    image_number: None,
+    dimensions: None,
+  }
+}
+
+  // files that can't hold exif data, e.g. a GIF with a .jpg extension, are pointed out before the exif crate tries to read them
+fn read_exif(path: &Path)->Result<exif::Exif, GetDateTimeError> {
+  let mut reader = BufReader::new(File::open(path).context(FileOpenError)?);
+    // the kind is told from the start of the buffered contents, so the file is only opened and read once
+  let head = reader.fill_buf().context(FileOpenError)?;
+  let detected_kind = kind_of(&head[..head.len().min(HEAD_LEN)]);
+  ensure!(detected_kind.has_exif_reader(), UnsupportedFormat { path, detected_kind });
+  exif::Reader::new().read_from_container(&mut reader).map_err(|source| {
+      // the exif data of HEIF files is stored as an item deep inside the container, so errors there are pointed out separately
+    if is_heif(path) { GetDateTimeError::HeifReadError { source } } else { GetDateTimeError::ReaderCreateError { source } }
+  })
+}
+
+  // the exif fields read_metadata looks at with the given options, in the order it looks at them
+pub fn consulted_tags(opts: &ReadOptions)->Vec<exif::Tag> {
+  let mut tags: Vec<exif::Tag> = opts.date_tags.iter().map(|tag| tag.exif_tag()).collect();
+    // the offset is read even if it isn't used for the date, to point out files taken in different time zones
+  tags.extend(opts.date_tags.iter().map(|tag| tag.offset_tag()));
+  if !opts.ignore_subseconds {
+    tags.extend(opts.date_tags.iter().map(|tag| tag.subsec_tag()));
+  }
+  tags.extend(&[
+    exif::Tag::Make,
+    exif::Tag::Model,
+    exif::Tag::LensModel,
+    exif::Tag::FocalLength,
+    IMAGE_NUMBER,
+    exif::Tag::PixelXDimension,
+    exif::Tag::PixelYDimension,
+    exif::Tag::ImageWidth,
+    exif::Tag::ImageLength,
+    exif::Tag::GPSLatitude,
+    exif::Tag::GPSLatitudeRef,
+    exif::Tag::GPSLongitude,
+    exif::Tag::GPSLongitudeRef,
+  ]);
+  tags
+}
+
+  // the values of the given fields in the file, as they are displayed by the exif crate, or None for missing fields
+  // meant to find out why a file couldn't be read. nothing is changed
+pub fn read_tag_values(path: &Path, tags: &[exif::Tag])->Result<Vec<(exif::Tag, Option<String>)>, GetDateTimeError> {
+  let reader = read_exif(path)?;
+  Ok(tags.iter().map(|&tag| {
+    let value = reader.get_field(tag, exif::In::PRIMARY).map(|field| field.display_value().to_string());
+    (tag, value)
+  }).collect())
+}
+
+fn is_heif(path: &Path)->bool {
+  path.extension()
+    .map(|ext| ext.to_string_lossy().to_lowercase())
+    .is_some_and(|ext| ext == "heic" || ext == "heif")
+}
+
+  // converts seconds since 1970-01-01 UTC, as used by videos and the file system, into a date
+  // with to_timezone, the date is in the local time of that zone, with use_offset in the local time at assumed_offset,
+  // and otherwise in the local time of local_timezone, or in UTC without one
+pub(crate) fn datetime_from_unix(unix_time: i64, opts: &ReadOptions)->exif::DateTime {
+  let mut datetime = exif::DateTime {
+    year: 1970,
+    month: 1,
+    day: 1,
+    hour: 0,
+    minute: 0,
+    second: 0,
+    nanosecond: None,
+    offset: None,
+  };
+  let mut local_time = unix_time;
+  if let Some(timezone) = opts.to_timezone {
+    let offset = timezone_offset(timezone, unix_time);
+    datetime.offset = Some(offset);
+    local_time += i64::from(offset) * 60;
+  } else if opts.use_offset {
+    datetime.offset = Some(opts.assumed_offset);
+    local_time += i64::from(opts.assumed_offset) * 60;
+  } else if let Some(timezone) = opts.local_timezone {
+      // like the local time of photos, this has no offset
+    local_time += i64::from(timezone_offset(timezone, unix_time)) * 60;
+  }
+  shift_datetime(&mut datetime, local_time);
+  datetime
+}
+
+  // with to_timezone, turns a date in the local time at offset into the local time of that zone
+  // the date then has the zone's offset at that moment, so that it is sorted by the moment it was taken
+pub(crate) fn convert_to_timezone(datetime: &mut exif::DateTime, offset: i16, opts: &ReadOptions) {
+  let timezone = match opts.to_timezone {
+    Some(timezone) => timezone,
+    None => return,
+  };
+  let moment = OrdDateTime::from(exif::DateTime { offset: Some(offset), ..*datetime }).timestamp();
+  let target_offset = timezone_offset(timezone, moment);
+  shift_datetime(datetime, i64::from(target_offset - offset) * 60);
+  datetime.offset = Some(target_offset);
+}
+
+  // the time zone the computer is set to, from the TZ variable or the zone file /etc/localtime links to
+  // None if it can't be told, e.g. on Windows
+pub fn system_timezone()->Option<chrono_tz::Tz> {
+  let name = match std::env::var("TZ") {
+    Ok(name) => name.trim_start_matches(':').to_string(),
+    Err(_) => {
+      let link = std::fs::read_link("/etc/localtime").ok()?;
+      link.to_string_lossy().split("zoneinfo/").nth(1)?.to_string()
+    },
+  };
+  name.parse().ok()
+}
+
+  // the offset from UTC in minutes that the time zone has at the given moment, in seconds since 1970
+fn timezone_offset(timezone: chrono_tz::Tz, unix_time: i64)->i16 {
+  let utc = chrono::DateTime::from_timestamp(unix_time, 0).unwrap_or_default().naive_utc();
+  (timezone.offset_from_utc_datetime(&utc).fix().local_minus_utc() / 60) as i16
+}
+
+  // the exif fields that a date can be read from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateTag {
+  DateTime,
+  DateTimeOriginal,
+  DateTimeDigitized,
+}
+
+  // the order in which date fields are tried if not configured otherwise
+pub const DEFAULT_DATE_TAGS: [DateTag; 3] = [DateTag::DateTime, DateTag::DateTimeOriginal, DateTag::DateTimeDigitized];
+
+impl DateTag {
+  pub fn exif_tag(self)->exif::Tag {
+    match self {
+      DateTag::DateTime => exif::Tag::DateTime,
+      DateTag::DateTimeOriginal => exif::Tag::DateTimeOriginal,
+      DateTag::DateTimeDigitized => exif::Tag::DateTimeDigitized,
+    }
+  }
+
+    // the field holding the time zone offset for this date
+  pub fn offset_tag(self)->exif::Tag {
+    match self {
+      DateTag::DateTime => exif::Tag::OffsetTime,
+      DateTag::DateTimeOriginal => exif::Tag::OffsetTimeOriginal,
+      DateTag::DateTimeDigitized => exif::Tag::OffsetTimeDigitized,
+    }
+  }
+
+    // the field holding the subseconds of this date
+  pub fn subsec_tag(self)->exif::Tag {
+    match self {
+      DateTag::DateTime => exif::Tag::SubSecTime,
+      DateTag::DateTimeOriginal => exif::Tag::SubSecTimeOriginal,
+      DateTag::DateTimeDigitized => exif::Tag::SubSecTimeDigitized,
+    }
+  }
+}
+
+impl std::fmt::Display for DateTag {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>)->std::fmt::Result {
+    match self {
+      DateTag::DateTime => write!(f, "DateTime"),
+      DateTag::DateTimeOriginal => write!(f, "DateTimeOriginal"),
+      DateTag::DateTimeDigitized => write!(f, "DateTimeDigitized"),
+    }
+  }
+}
+
+impl std::str::FromStr for DateTag {
+  type Err = String;
+
+    // accepts the full field names as well as the short forms "original" and "digitized", ignoring case
+  fn from_str(s: &str)->Result<DateTag, String> {
+    match s.to_lowercase().as_str() {
+      "datetime" => Ok(DateTag::DateTime),
+      "datetimeoriginal" | "original" => Ok(DateTag::DateTimeOriginal),
+      "datetimedigitized" | "digitized" => Ok(DateTag::DateTimeDigitized),
+      _ => Err(format!("Unknown date tag \"{}\" (expected datetime, original or digitized)", s)),
+    }
+  }
+}
+
+#[derive(Debug, Snafu)]
+pub enum DateTimeReadError {
+  #[snafu(display("No date field is present."))]
+  FieldMissing,
+  #[snafu(display("{} field is not in ascii format.", tag))]
+  FieldNotAscii {
+    tag: DateTag,
+  },
+  #[snafu(display("{} field contains no data.", tag))]
+  FieldEmpty {
+    tag: DateTag,
+  },
+  #[snafu(display("{} field data could not be parsed: {}", tag, source))]
+  ParseError {
+    tag: DateTag,
+    source: exif::Error,
+  },
+}
+
+  // how many files have a usable date, and why the others don't, as counted by --validate
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationSummary {
+  pub valid: usize,
+  pub missing_field: usize,
+  pub empty_field: usize,
+  pub not_ascii: usize,
+  pub parse_error: usize,
+    // files with a usable date, but no extension to keep when renaming them
+  pub no_extension: usize,
+    // files without exif data at all, or that the exif crate can't read
+  pub no_exif: usize,
+    // files that couldn't be opened, and videos whose creation time couldn't be read
+  pub unreadable: usize,
+}
+
+impl ValidationSummary {
+  pub fn add(&mut self, path: &Path, result: &Result<FileMetadata, GetDateTimeError>) {
+    let count = match *result {
+      Ok(_) if path.extension().is_none() => &mut self.no_extension,
+      Ok(_) => &mut self.valid,
+      Err(GetDateTimeError::FieldReadError { ref source }) => match *source {
+        DateTimeReadError::FieldMissing => &mut self.missing_field,
+        DateTimeReadError::FieldEmpty { .. } => &mut self.empty_field,
+        DateTimeReadError::FieldNotAscii { .. } => &mut self.not_ascii,
+        DateTimeReadError::ParseError { .. } => &mut self.parse_error,
+      },
+      Err(GetDateTimeError::UnsupportedFormat { .. }) | Err(GetDateTimeError::ReaderCreateError { .. }) | Err(GetDateTimeError::HeifReadError { .. }) => &mut self.no_exif,
+      Err(GetDateTimeError::FileOpenError { .. }) | Err(GetDateTimeError::VideoDateError { .. }) => &mut self.unreadable,
+    };
+    *count += 1;
+  }
+
+  pub fn invalid(&self)->usize {
+    self.missing_field + self.empty_field + self.not_ascii + self.parse_error + self.no_extension + self.no_exif + self.unreadable
+  }
+}
+
+  // reads the date from the first of the given tags that holds a valid one, along with the other metadata
+  // if all present tags fail to parse, the error of the first one is returned
+pub fn read_metadata(exif_reader: &exif::Exif, opts: &ReadOptions)->Result<FileMetadata, DateTimeReadError> {
+  let mut first_error = None;
+  let mut date_time = None;
+  for &tag in &opts.date_tags {
+    if let Some(date_time_data) = exif_reader.get_field(tag.exif_tag(), exif::In::PRIMARY) {
+      match parse_datetime_field(date_time_data, tag) {
+        Ok(parsed) => {
+          date_time = Some((parsed, tag));
+          break;
+        },
+        Err(error) => {
+          first_error.get_or_insert(error);
+        },
+      }
+    }
+  }
+
+  let (mut date_time, tag) = match date_time {
+    Some(date_time) => date_time,
+    None => return Err(first_error.unwrap_or(DateTimeReadError::FieldMissing)),
+  };
+
+  let offset = read_offset(exif_reader, tag);
+  if opts.use_offset {
+    date_time.offset = Some(offset.unwrap_or(opts.assumed_offset));
+  }
+
+  let mut subseconds = Subseconds::Missing;
+    // like the offset, the subseconds are those of the date that was used
+  let subsec_field = if opts.ignore_subseconds { None } else { exif_reader.get_field(tag.subsec_tag(), exif::In::PRIMARY) };
+  if let Some(subsec_data) = subsec_field {
+    if let exif::Value::Ascii(ref subsec_ascii) = subsec_data.value {
+      if let Some(subsec_string) = subsec_ascii.first() {
+        subseconds = match date_time.parse_subsec(subsec_string) {
+          Ok(()) if date_time.nanosecond.is_some() => Subseconds::Parsed,
+          Ok(()) => Subseconds::Missing, // blank values are a common way of leaving the field empty
+          Err(_) => Subseconds::Invalid(String::from_utf8_lossy(subsec_string).into_owned()),
+        };
+      }
+    }
+  }
+
+  if opts.shift != 0 {
+    shift_datetime(&mut date_time, opts.shift);
+  }
+  convert_to_timezone(&mut date_time, offset.unwrap_or(opts.assumed_offset), opts);
+
+  Ok(FileMetadata {
+    datetime: date_time,
+    subseconds,
+    offset,
+    gps: read_gps(exif_reader),
+    make: read_text(exif_reader, exif::Tag::Make),
+    model: read_text(exif_reader, exif::Tag::Model),
+    lens: read_text(exif_reader, exif::Tag::LensModel),
+    focal_length: read_focal_length(exif_reader),
+    image_number: read_image_number(exif_reader),
+    dimensions: read_dimensions(exif_reader),
+  })
+}
+
+  // the size from the exif fields, or from the TIFF fields, which e.g. RAW files use instead
+fn read_dimensions(exif_reader: &exif::Exif)->Option<Dimensions> {
+  let value = |tag: exif::Tag| exif_reader.get_field(tag, exif::In::PRIMARY)?.value.get_uint(0).filter(|&value| value > 0);
+  let width = value(exif::Tag::PixelXDimension).or_else(|| value(exif::Tag::ImageWidth))?;
+  let height = value(exif::Tag::PixelYDimension).or_else(|| value(exif::Tag::ImageLength))?;
+  Some(Dimensions { width, height })
+}
+
+  // the TIFF/EP ImageNumber field, which the exif crate doesn't know by name
+const IMAGE_NUMBER: exif::Tag = exif::Tag(exif::Context::Tiff, 0x9211);
+
+  // cameras put the ImageNumber into the first IFD or the Exif IFD
+fn read_image_number(exif_reader: &exif::Exif)->Option<u32> {
+  [IMAGE_NUMBER, exif::Tag(exif::Context::Exif, IMAGE_NUMBER.number())].iter()
+    .find_map(|&tag| exif_reader.get_field(tag, exif::In::PRIMARY))
+    .and_then(|field| field.value.get_uint(0))
+}
+
+  // an unknown focal length is often stored as 0/0 or 0
+fn read_focal_length(exif_reader: &exif::Exif)->Option<f64> {
+  match exif_reader.get_field(exif::Tag::FocalLength, exif::In::PRIMARY)?.value {
+    exif::Value::Rational(ref values) => values.first().map(|value| value.to_f64()).filter(|value| value.is_finite() && *value > 0.0),
+    _ => None,
+  }
+}
+
+  // the offset stored for the date read from tag, in minutes. a broken offset is treated like a missing one
+fn read_offset(exif_reader: &exif::Exif, tag: DateTag)->Option<i16> {
+  let field = exif_reader.get_field(tag.offset_tag(), exif::In::PRIMARY)?;
+  let offset_string = match field.value {
+    exif::Value::Ascii(ref values) => values.first()?,
+    _ => return None,
+  };
+  let mut parsed = exif::DateTime { year: 1970, month: 1, day: 1, hour: 0, minute: 0, second: 0, nanosecond: None, offset: None };
+  parsed.parse_offset(offset_string).ok()?;
+  parsed.offset
+}
+
+  // the value of a text field without the padding some cameras add, if it isn't empty
+fn read_text(exif_reader: &exif::Exif, tag: exif::Tag)->Option<String> {
+  match exif_reader.get_field(tag, exif::In::PRIMARY)?.value {
+    exif::Value::Ascii(ref values) => {
+      let text = String::from_utf8_lossy(values.first()?);
+      let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+      if text.is_empty() { None } else { Some(text.to_string()) }
+    },
+    _ => None,
+  }
+}
+
+  // the position from the GPS fields, if they are complete
+fn read_gps(exif_reader: &exif::Exif)->Option<GpsPosition> {
+  let coordinate = |tag: exif::Tag, ref_tag: exif::Tag, negative_ref: u8| {
+    let degrees = match exif_reader.get_field(tag, exif::In::PRIMARY)?.value {
+      exif::Value::Rational(ref parts) if parts.len() == 3 && parts.iter().all(|part| part.denom != 0) => {
+        parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+      },
+      _ => return None,
+    };
+    let is_negative = match exif_reader.get_field(ref_tag, exif::In::PRIMARY)?.value {
+      exif::Value::Ascii(ref values) => values.first()?.first()?.eq_ignore_ascii_case(&negative_ref),
+      _ => return None,
+    };
+    Some(if is_negative { -degrees } else { degrees })
+  };
+
+  Some(GpsPosition {
+    latitude: coordinate(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, b'S')?,
+    longitude: coordinate(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, b'W')?,
+  })
+}
+
+fn parse_datetime_field(date_time_data: &exif::Field, tag: DateTag)->Result<exif::DateTime, DateTimeReadError> {
+  if let exif::Value::Ascii(ref datetime_ascii) = date_time_data.value {
+      // a date padded with nulls shows up as empty strings around it
+    let datetime_string = datetime_ascii.iter().find(|value| !trim_padding(value).is_empty());
+
+    ensure!(datetime_string.is_some(), FieldEmpty { tag });
+    let datetime_string = datetime_string.unwrap();
+
+    parse_exif_datetime(datetime_string).context(ParseError { tag })
+  } else {
+    FieldNotAscii { tag }.fail()
+  }
+}
+
+  // like exif::DateTime::from_ascii, but also accepts the dates some cameras write with other separators,
+  // e.g. "2021/03/15 14:30:00" or "2021-03-15T14:30:00", or padded with spaces or nulls
+  // if the date can't be read either way, the error of the strict parse is returned
+pub(crate) fn parse_exif_datetime(data: &[u8])->Result<exif::DateTime, exif::Error> {
+  exif::DateTime::from_ascii(data).or_else(|error| match normalize_datetime(data) {
+    Some(normalized) => exif::DateTime::from_ascii(&normalized).map_err(|_| error),
+    None => Err(error),
+  })
+}
+
+  // the date in the standard "YYYY:MM:DD HH:MM:SS" form, if the numbers are where they should be
+fn normalize_datetime(data: &[u8])->Option<Vec<u8>> {
+  const SEPARATORS: [(usize, u8, &[u8]); 5] = [
+    (4, b':', b":/-."), (7, b':', b":/-."), (10, b' ', b" T_"), (13, b':', b":.-"), (16, b':', b":.-"),
+  ];
+  let trimmed = trim_padding(data);
+  if trimmed.len() < 19 {
+    return None;
+  }
+  let mut normalized = trimmed[..19].to_vec();
+  for &(index, separator, accepted) in &SEPARATORS {
+    if !accepted.contains(&normalized[index]) {
+      return None;
+    }
+    normalized[index] = separator;
+  }
+  Some(normalized)
+}
+
+fn trim_padding(data: &[u8])->&[u8] {
+  let is_padding = |byte: &u8| byte.is_ascii_whitespace() || *byte == 0;
+  let start = data.iter().position(|byte| !is_padding(byte)).unwrap_or(data.len());
+  let end = data.iter().rposition(|byte| !is_padding(byte)).map_or(start, |last| last + 1);
+  &data[start..end]
+}
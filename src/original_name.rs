@@ -0,0 +1,44 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+  // the extended attribute the name a file had before datier first renamed it is kept in
+pub const ORIGINAL_NAME_ATTRIBUTE: &str = "user.datier.original";
+
+  // stores the file name of original in an extended attribute of path, unless the file already has one from an earlier run
+  // that way the attribute keeps the name the camera gave the file, however often it is renamed
+  // fails with io::ErrorKind::Unsupported on systems and file systems without extended attributes
+pub fn keep_original_name(path: &Path, original: &Path)->io::Result<()> {
+  if original_name(path)?.is_some() {
+    return Ok(());
+  }
+  let name = original.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the original path has no file name"))?;
+  set_attribute(path, name)
+}
+
+  // the name stored by keep_original_name, if there is one
+pub fn original_name(path: &Path)->io::Result<Option<OsString>> {
+  get_attribute(path)
+}
+
+#[cfg(unix)]
+fn set_attribute(path: &Path, name: &std::ffi::OsStr)->io::Result<()> {
+  use std::os::unix::ffi::OsStrExt;
+  xattr::set(path, ORIGINAL_NAME_ATTRIBUTE, name.as_bytes())
+}
+
+#[cfg(unix)]
+fn get_attribute(path: &Path)->io::Result<Option<OsString>> {
+  use std::os::unix::ffi::OsStringExt;
+  Ok(xattr::get(path, ORIGINAL_NAME_ATTRIBUTE)?.map(OsString::from_vec))
+}
+
+#[cfg(not(unix))]
+fn set_attribute(_path: &Path, _name: &std::ffi::OsStr)->io::Result<()> {
+  Err(io::Error::new(io::ErrorKind::Unsupported, "extended attributes are not supported on this system"))
+}
+
+#[cfg(not(unix))]
+fn get_attribute(_path: &Path)->io::Result<Option<OsString>> {
+  Err(io::Error::new(io::ErrorKind::Unsupported, "extended attributes are not supported on this system"))
+}
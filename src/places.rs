@@ -0,0 +1,108 @@
+use snafu::{ResultExt, Snafu};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::metadata::GpsPosition;
+
+  // how far from its coordinates a place reaches if its line doesn't say
+pub const DEFAULT_PLACE_RADIUS_KM: f64 = 10.0;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Snafu)]
+pub enum PlacesError {
+  #[snafu(display("Could not read places file {}: {}", path.display(), source))]
+  PlacesFileRead {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("Invalid place on line {} of {}: {}", line, path.display(), message))]
+  InvalidPlace {
+    path: PathBuf,
+    line: usize,
+    message: String,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+  pub name: String,
+  pub position: GpsPosition,
+  pub radius_km: f64,
+}
+
+  // a list of named places to look up the GPS positions of photos in, for the {place} placeholder
+#[derive(Debug, Clone, Default)]
+pub struct Places {
+  places: Vec<Place>,
+}
+
+impl Places {
+    // reads a file with one place per line, as "name, latitude, longitude" with an optional radius in km as a fourth value
+    // empty lines and lines starting with # are ignored
+  pub fn read(path: &Path)->Result<Places, PlacesError> {
+    let contents = fs::read_to_string(path).context(PlacesFileRead { path: path.to_path_buf() })?;
+    Places::parse(&contents).map_err(|(line, message)| PlacesError::InvalidPlace { path: path.to_path_buf(), line, message })
+  }
+
+    // on failure, returns the line number and what is wrong with it
+  pub fn parse(contents: &str)->Result<Places, (usize, String)> {
+    let mut places = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      places.push(parse_place(line).map_err(|message| (index + 1, message))?);
+    }
+    Ok(Places { places })
+  }
+
+  pub fn len(&self)->usize {
+    self.places.len()
+  }
+
+  pub fn is_empty(&self)->bool {
+    self.places.is_empty()
+  }
+
+    // the name of the closest place whose radius includes the position
+  pub fn lookup(&self, position: GpsPosition)->Option<&str> {
+    self.places.iter()
+      .map(|place| (place, distance_km(place.position, position)))
+      .filter(|&(place, distance)| distance <= place.radius_km)
+      .min_by(|a, b| a.1.total_cmp(&b.1))
+      .map(|(place, _)| place.name.as_str())
+  }
+}
+
+fn parse_place(line: &str)->Result<Place, String> {
+  let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+  if parts.len() < 3 || parts.len() > 4 || parts[0].is_empty() {
+    return Err(String::from("expected name, latitude, longitude and an optional radius in km"));
+  }
+
+  let number = |value: &str, what: &str, range: std::ops::RangeInclusive<f64>| {
+    value.parse::<f64>().ok().filter(|number| range.contains(number))
+      .ok_or_else(|| format!("invalid {} \"{}\"", what, value))
+  };
+  Ok(Place {
+    name: parts[0].to_string(),
+    position: GpsPosition {
+      latitude: number(parts[1], "latitude", -90.0..=90.0)?,
+      longitude: number(parts[2], "longitude", -180.0..=180.0)?,
+    },
+    radius_km: match parts.get(3) {
+      Some(radius) => number(radius, "radius", 0.0..=f64::MAX)?,
+      None => DEFAULT_PLACE_RADIUS_KM,
+    },
+  })
+}
+
+  // the great-circle distance between two positions, using the haversine formula
+fn distance_km(a: GpsPosition, b: GpsPosition)->f64 {
+  let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+  let delta_lat = lat_b - lat_a;
+  let delta_lon = (b.longitude - a.longitude).to_radians();
+  let h = (delta_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (delta_lon / 2.0).sin().powi(2);
+  2.0 * EARTH_RADIUS_KM * h.sqrt().min(1.0).asin()
+}
@@ -0,0 +1,1650 @@
+use glob::{glob_with, MatchOptions, Pattern};
+use ignore::gitignore::GitignoreBuilder;
+use rayon::prelude::*;
+use regex::Regex;
+use snafu::{ensure, ResultExt, Snafu};
+use std::cmp::Ordering;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+use crate::cache::MetadataCache;
+use crate::datetime::{days_in_month, OrdDateTime};
+use crate::metadata::{convert_to_timezone, datetime_from_unix, get_metadata, Dimensions, FileMetadata, GetDateTimeError, ReadOptions, Subseconds};
+use crate::places::Places;
+use crate::png::is_png;
+use crate::template::{Template, TextValues};
+
+pub const DEFAULT_EXTENSIONS: [&str; 10] = ["jpg", "jpeg", "cr2", "tif", "tiff", "dng", "heic", "heif", "mp4", "mov"];
+pub const DEFAULT_SIDECAR_EXTENSIONS: [&str; 2] = ["xmp", "thm"];
+pub const IGNORE_FILE_NAME: &str = ".datierignore";
+
+  // the result of searching a directory for files
+#[derive(Debug, Default)]
+pub struct FoundFiles {
+  pub paths: Vec<PathBuf>,
+    // paths that matched, but couldn't be read while searching, e.g. because of missing permissions
+  pub errors: Vec<SearchError>,
+}
+
+#[derive(Debug)]
+pub struct SearchError {
+  pub path: PathBuf,
+  pub source: std::io::Error,
+}
+
+impl From<glob::GlobError> for SearchError {
+  fn from(error: glob::GlobError)->SearchError {
+    SearchError { path: error.path().to_path_buf(), source: error.into_error() }
+  }
+}
+
+  // collects all files in dir with one of the given extensions (matched case-insensitively), grouped by extension in the given order
+  // if deep is set, sub-directories are searched as well, down to max_depth levels below dir if it is given
+  // symbolic links are left out, unless follow_symlinks is set. then the files they lead to are only found once, under the first path to them
+  // the directories are only read once, with the extensions compared afterwards, instead of searching them once per extension
+pub fn find_files(dir: &Path, extensions: &[String], deep: bool, max_depth: Option<usize>, follow_symlinks: bool)->Result<FoundFiles, glob::PatternError> {
+  let suffixes: Vec<String> = extensions.iter().map(|ext| format!(".{}", ext.to_lowercase())).collect();
+  let mut found = FoundFiles::default();
+  let mut per_extension: Vec<Vec<PathBuf>> = vec![Vec::new(); extensions.len()];
+  found.errors = search_dir(dir, deep, max_depth, follow_symlinks, &mut |path: PathBuf| {
+    if let Some(index) = file_suffix_index(&path, &suffixes) {
+      per_extension[index].push(path);
+    }
+  })?;
+  found.paths = per_extension.into_iter().flatten().collect();
+  if follow_symlinks {
+    let mut seen = HashSet::new();
+    found.paths.retain(|path| seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone())));
+  }
+
+  Ok(found)
+}
+
+  // the files in the same places as find_files searches, but only those without any of the given extensions, e.g. to set them aside
+pub fn find_other_files(dir: &Path, extensions: &[String], deep: bool, max_depth: Option<usize>, follow_symlinks: bool)->Result<FoundFiles, glob::PatternError> {
+  let suffixes: Vec<String> = extensions.iter().map(|ext| format!(".{}", ext.to_lowercase())).collect();
+  let mut found = FoundFiles::default();
+  let mut paths = Vec::new();
+  found.errors = search_dir(dir, deep, max_depth, follow_symlinks, &mut |path: PathBuf| {
+      // the glob also finds the folders in dir
+    if file_suffix_index(&path, &suffixes).is_none() && !path.is_dir() {
+      paths.push(path);
+    }
+  })?;
+  found.paths = paths;
+  Ok(found)
+}
+
+  // which of the suffixes the name of path ends with, ignoring the case
+fn file_suffix_index(path: &Path, suffixes: &[String])->Option<usize> {
+  let name = path.file_name()?.to_string_lossy().to_lowercase();
+  suffixes.iter().position(|suffix| name.ends_with(suffix.as_str()))
+}
+
+  // calls add for each file in dir, or below it if deep is set, and returns the errors on the way
+fn search_dir(dir: &Path, deep: bool, max_depth: Option<usize>, follow_symlinks: bool, add: &mut dyn FnMut(PathBuf))->Result<Vec<SearchError>, glob::PatternError> {
+  let mut errors = Vec::new();
+  if deep {
+      // glob's ** can't be limited in depth, so the sub-directories are walked instead
+    let mut walker = WalkDir::new(dir).min_depth(1).follow_links(follow_symlinks).sort_by_file_name();
+    if let Some(max_depth) = max_depth {
+      walker = walker.max_depth(max_depth + 1);
+    }
+    for walk_result in walker {
+      match walk_result {
+        Ok(entry) if entry.file_type().is_dir() || entry.path_is_symlink() && !follow_symlinks => {},
+        Ok(entry) => add(entry.into_path()),
+        Err(error) => {
+          let path = error.path().unwrap_or(dir).to_path_buf();
+          let source = error.into_io_error().unwrap_or_else(|| std::io::Error::other("the directories contain a loop"));
+          errors.push(SearchError { path, source });
+        },
+      }
+    }
+  } else {
+    let mut pattern = Pattern::escape(&dir.to_string_lossy());
+    pattern.push_str("/*");
+    let mut options = MatchOptions::new();
+    options.case_sensitive = false;
+    for glob_result in glob_with(&pattern, options)? {
+      match glob_result {
+        Ok(path) if !follow_symlinks && path.is_symlink() => {},
+        Ok(path) => add(path),
+        Err(error) => errors.push(SearchError::from(error)),
+      }
+    }
+  }
+  Ok(errors)
+}
+
+  // splits paths into those that match none of the patterns and those that match one (matched case-insensitively)
+  // the patterns are matched against the paths relative to dir, and * also matches across directories, so "thumbnails/*" and "*.small.jpg" work at any depth
+pub fn exclude_paths(paths: Vec<PathBuf>, dir: &Path, patterns: &[Pattern])->(Vec<PathBuf>, Vec<PathBuf>) {
+  let mut options = MatchOptions::new();
+  options.case_sensitive = false;
+  paths.into_iter().partition(|path| {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    !patterns.iter().any(|pattern| pattern.matches_path_with(relative, options))
+  })
+}
+
+  // splits paths into those that aren't hidden and those that are, i.e. whose name or one of whose folders below dir starts with a dot, like .git
+pub fn hidden_paths(paths: Vec<PathBuf>, dir: &Path)->(Vec<PathBuf>, Vec<PathBuf>) {
+  paths.into_iter().partition(|path| {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    !relative.components().any(|component| matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.')))
+  })
+}
+
+#[derive(Debug, Snafu)]
+pub enum IgnoreFileError {
+  #[snafu(display("Could not read ignore file {}: {}", path.display(), source))]
+  IgnoreFileRead {
+    path: PathBuf,
+    source: ignore::Error,
+  },
+}
+
+  // splits paths into those that the .datierignore file in dir doesn't match and those that it does
+  // it uses the syntax of .gitignore files, with patterns relative to dir. without an ignore file, nothing is ignored
+pub fn ignored_paths(paths: Vec<PathBuf>, dir: &Path)->Result<(Vec<PathBuf>, Vec<PathBuf>), IgnoreFileError> {
+  let path = dir.join(IGNORE_FILE_NAME);
+  if !path.is_file() {
+    return Ok((paths, Vec::new()));
+  }
+  let mut builder = GitignoreBuilder::new(dir);
+  if let Some(error) = builder.add(&path) {
+    return Err(error).context(IgnoreFileRead { path });
+  }
+  let ignore = builder.build().context(IgnoreFileRead { path })?;
+  Ok(paths.into_iter().partition(|path| match path.strip_prefix(dir) {
+    Ok(relative) => !ignore.matched_path_or_any_parents(relative, false).is_ignore(),
+    Err(_) => true,
+  }))
+}
+
+  // how renamed files are sorted into dated sub-directories of their destination
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+  Year,
+  Month,
+  Day,
+}
+
+impl GroupBy {
+    // e.g. "2021", "2021/2021-03" or "2021/2021-03-15"
+  pub fn subdir(self, datetime: &exif::DateTime)->PathBuf {
+    let year = format!("{:04}", datetime.year);
+    match self {
+      GroupBy::Year => PathBuf::from(year),
+      GroupBy::Month => Path::new(&year).join(format!("{:04}-{:02}", datetime.year, datetime.month)),
+      GroupBy::Day => Path::new(&year).join(format!("{:04}-{:02}-{:02}", datetime.year, datetime.month, datetime.day)),
+    }
+  }
+}
+
+impl std::str::FromStr for GroupBy {
+  type Err = String;
+
+  fn from_str(s: &str)->Result<GroupBy, String> {
+    match s.to_lowercase().as_str() {
+      "year" => Ok(GroupBy::Year),
+      "month" => Ok(GroupBy::Month),
+      "day" => Ok(GroupBy::Day),
+      _ => Err(format!("Unknown grouping \"{}\" (expected year, month or day)", s)),
+    }
+  }
+}
+
+  // what to do with files that were taken at exactly the same time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TieBreak {
+    // all tied files get the same number, followed by -a, -b, ...
+  CollisionSuffix,
+    // each tied file gets its own number, as if they were taken one after another
+  KeepBothIncrement,
+    // only the first of the tied files is renamed
+  Skip,
+}
+
+impl std::str::FromStr for TieBreak {
+  type Err = String;
+
+  fn from_str(s: &str)->Result<TieBreak, String> {
+    match s.to_lowercase().as_str() {
+      "collision-suffix" => Ok(TieBreak::CollisionSuffix),
+      "keep-both-increment" => Ok(TieBreak::KeepBothIncrement),
+      "skip" => Ok(TieBreak::Skip),
+      _ => Err(format!("Unknown tie break mode \"{}\" (expected collision-suffix, keep-both-increment or skip)", s)),
+    }
+  }
+}
+
+  // which files share a counter, which starts over for each new group
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NumberingScope {
+    // one counter for all files
+  Global,
+    // a counter for each day, which is kept separately for each directory when renaming in place
+  #[default]
+  PerDay,
+    // a counter for each destination directory, running over all days
+  PerFolder,
+}
+
+impl std::str::FromStr for NumberingScope {
+  type Err = String;
+
+  fn from_str(s: &str)->Result<NumberingScope, String> {
+    match s.to_lowercase().as_str() {
+      "global" => Ok(NumberingScope::Global),
+      "per-day" => Ok(NumberingScope::PerDay),
+      "per-folder" => Ok(NumberingScope::PerFolder),
+      _ => Err(format!("Unknown numbering \"{}\" (expected global, per-day or per-folder)", s)),
+    }
+  }
+}
+
+  // what to do when a file can't be renamed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnError {
+    // report it and go on with the other files
+  Skip,
+    // stop, leaving the remaining files as they are
+  Abort,
+    // try again a few times before skipping it, for errors that may go away, like a file that is still being written
+  Retry,
+}
+
+impl std::str::FromStr for OnError {
+  type Err = String;
+
+  fn from_str(s: &str)->Result<OnError, String> {
+    match s.to_lowercase().as_str() {
+      "skip" => Ok(OnError::Skip),
+      "abort" => Ok(OnError::Abort),
+      "retry" => Ok(OnError::Retry),
+      _ => Err(format!("Unknown error policy \"{}\" (expected skip, abort or retry)", s)),
+    }
+  }
+}
+
+pub struct RenameOptions {
+  pub template: Template,
+    // the directory that renamed files are moved into
+    // if None, each file keeps its directory, and the counter runs separately for each directory
+  pub target_dir: Option<PathBuf>,
+    // with target_dir, each file is moved into the same sub-folder of target_dir as it was in below input_dir, instead of into target_dir itself
+    // the counter then runs separately for each folder, as if the files were renamed in place
+  pub mirror: bool,
+    // the folder the files were found in, which {subdir} is relative to
+  pub input_dir: PathBuf,
+  pub read: ReadOptions,
+    // if set, files are moved into dated sub-directories of their destination
+  pub group_by: Option<GroupBy>,
+    // only files taken on or after since, and on or before until are renamed. the time of day is ignored
+  pub since: Option<OrdDateTime>,
+  pub until: Option<OrdDateTime>,
+  pub tie_break: TieBreak,
+  pub numbering: NumberingScope,
+    // the number of the first file of each counter group
+  pub counter_start: u32,
+    // start each counter group after the highest number that files already in the destination use for it, instead of at counter_start
+    // only files that match the template for the date of a file in the group count, and the files being renamed don't
+  pub continue_counter: bool,
+    // copy the files to their destination instead of moving them, leaving the originals untouched
+  pub copy: bool,
+    // if set, the files are split into sessions wherever more than this many seconds pass between two of them, e.g. bursts of shooting at an event
+    // the counter starts over for each session instead of each day, and {session} is the number of the session
+  pub session_gap: Option<i64>,
+    // if set, files whose date is further than this many seconds away from their modification time are considered suspicious
+  pub mtime_tolerance: Option<i64>,
+    // use the modification time of suspicious files instead of skipping them
+  pub fallback_to_mtime: bool,
+    // skip files dated later than now, as their camera's clock was likely set wrong
+  pub reject_future: bool,
+    // files smaller than min_dimensions or larger than max_dimensions are skipped, e.g. to leave out thumbnails, whichever way they are turned
+    // files whose size isn't known, such as videos, are only skipped if skip_unknown_dimensions is set
+  pub min_dimensions: Option<Dimensions>,
+  pub max_dimensions: Option<Dimensions>,
+  pub skip_unknown_dimensions: bool,
+    // files without a date in their metadata get the one in their name, if this matches it
+    // it needs the named groups year, month and day, and can have hour, minute and second
+  pub parse_name: Option<Regex>,
+    // files next to a renamed file that share its name and have one of these extensions are renamed along with it
+  pub sidecar_extensions: Vec<String>,
+    // the places that {place} is looked up in
+  pub places: Option<Places>,
+    // what {place} expands to for files without a GPS position, or one that isn't near any of the places
+  pub place_default: String,
+    // what {make} and {model} expand to for files that don't name their camera
+  pub camera_default: String,
+    // what {lens} and {focal} expand to for files that don't have them
+  pub lens_default: String,
+    // rename files even if their name already matches the template, instead of leaving them as they are
+  pub force: bool,
+    // replace an existing file that has the name a file would get, if it is identical to the file or smaller, instead of picking another name
+  pub overwrite: bool,
+    // write extensions in lowercase, and use the common form of extensions that have several, e.g. jpg for jpeg
+  pub normalize_ext: bool,
+    // give names taken by identical copies a suffix as well, instead of leaving the file where it is
+    // this way, every file ends up in the target directory, e.g. when flattening nested folders
+  pub flat: bool,
+    // skip files taken at the same time as an earlier file in the plan with the same contents, instead of renaming both
+  pub dedupe: bool,
+  pub on_error: OnError,
+    // stop at the first file that can't be renamed, and undo the renames done before it, so that either all files are renamed or none
+  pub transactional: bool,
+    // check that each file arrived under its new name, and that copies have the same contents as their original before it is removed
+  pub verify: bool,
+    // if set, metadata is looked up in and added to this cache instead of always being read from the files
+  pub cache: Option<MetadataCache>,
+}
+
+  // renames into the current directory with the default template
+impl Default for RenameOptions {
+  fn default()->Self {
+    RenameOptions {
+      template: Template::default(),
+      target_dir: Some(PathBuf::from(".")),
+      mirror: false,
+      input_dir: PathBuf::from("."),
+      read: ReadOptions::default(),
+      group_by: None,
+      since: None,
+      until: None,
+      tie_break: TieBreak::CollisionSuffix,
+      numbering: NumberingScope::PerDay,
+      counter_start: 1,
+      continue_counter: false,
+      session_gap: None,
+      copy: false,
+      mtime_tolerance: None,
+      fallback_to_mtime: false,
+      reject_future: false,
+      min_dimensions: None,
+      max_dimensions: None,
+      skip_unknown_dimensions: false,
+      parse_name: None,
+      sidecar_extensions: Vec::new(),
+      places: None,
+      place_default: String::from("unknown"),
+      camera_default: String::new(),
+      lens_default: String::new(),
+      force: false,
+      overwrite: false,
+      normalize_ext: false,
+      flat: false,
+      dedupe: false,
+      on_error: OnError::Skip,
+      transactional: false,
+      verify: false,
+      cache: None,
+    }
+  }
+}
+
+impl RenameOptions {
+    // whether the files stay in folders of their own, rather than all going into target_dir
+  fn keeps_folders(&self)->bool {
+    self.target_dir.is_none() || self.mirror
+  }
+}
+
+#[derive(Debug)]
+pub enum SkipReason {
+  NoDateTime(GetDateTimeError),
+  NoExtension,
+  TargetExists(PathBuf),
+  IdenticalExists(PathBuf),
+  OutOfRange,
+  SameTimestamp(PathBuf),
+  SuspiciousDate(OrdDateTime),
+    // the date is later than the time of planning, by more than FUTURE_TOLERANCE
+  FutureDate,
+  AlreadyNamed,
+    // the file has the same date and contents as the given file, which is renamed instead
+  Duplicate(PathBuf),
+    // another file in the plan would be renamed to the same destination, or it is taken by a file that stays
+  Conflict(PathBuf),
+    // the rename was turned down when asking for each file
+  Declined,
+    // the image is smaller than min_dimensions or larger than max_dimensions
+  TooSmall(Dimensions),
+  TooLarge(Dimensions),
+    // a size range was given, but the size of the file isn't known
+  UnknownDimensions,
+    // the contents of the file aren't in the plan file it is renamed by
+  NotInManifest,
+    // the file couldn't be read to look it up in the plan file
+  Unhashable(std::io::Error),
+}
+
+impl std::fmt::Display for SkipReason {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>)->std::fmt::Result {
+    match self {
+      SkipReason::NoDateTime(error) => write!(f, "{}", error),
+      SkipReason::NoExtension => write!(f, "Has no extension"),
+      SkipReason::TargetExists(dest) => write!(f, "Would rename, but {} already exists", dest.display()),
+      SkipReason::IdenticalExists(dest) => write!(f, "An identical file already exists as {}", dest.display()),
+      SkipReason::OutOfRange => write!(f, "Date is outside of the selected range"),
+      SkipReason::SameTimestamp(other) => write!(f, "Taken at the same time as {}", other.display()),
+      SkipReason::SuspiciousDate(modified) => write!(f, "Suspicious date, the file was last modified at {}", modified),
+      SkipReason::FutureDate => write!(f, "Date is in the future"),
+      SkipReason::AlreadyNamed => write!(f, "Already named correctly"),
+      SkipReason::Conflict(dest) => write!(f, "Would rename, but {} is also the destination of another file", dest.display()),
+      SkipReason::Duplicate(original) => write!(f, "Duplicate of {}", original.display()),
+      SkipReason::Declined => write!(f, "Declined"),
+      SkipReason::TooSmall(dimensions) => write!(f, "Its size of {} is below the minimum", dimensions),
+      SkipReason::TooLarge(dimensions) => write!(f, "Its size of {} is above the maximum", dimensions),
+      SkipReason::UnknownDimensions => write!(f, "Its size is unknown"),
+      SkipReason::NotInManifest => write!(f, "Not in the plan file"),
+      SkipReason::Unhashable(error) => write!(f, "Could not read it to look it up in the plan file: {}", error),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub enum PlanAction {
+  Rename(PathBuf),
+  Skip(SkipReason),
+}
+
+  // where the date of a file in the plan came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateSource {
+    // the exif data of a photo, or the movie header of a video
+  Metadata,
+    // the file system, because the date in the metadata looked wrong
+  ModifiedTime,
+    // the file name, because the file has no date in its metadata
+  FileName,
+}
+
+#[derive(Debug)]
+pub struct RenamePlan {
+  pub source: PathBuf,
+  pub datetime: Option<OrdDateTime>,
+  pub date_source: DateSource,
+    // whether the subseconds of the date were read, which decides how well ties can be told apart
+  pub subseconds: Subseconds,
+    // the time zone offset stored with the date, in minutes, even if it wasn't used
+  pub offset: Option<i16>,
+    // for sidecar files, the file they belong to. they are renamed to the same name as that file
+  pub sidecar_of: Option<PathBuf>,
+    // how the counter of the file was chosen, for files that got that far
+  pub numbering: Option<Numbering>,
+    // when the file was last modified as its date was read, to notice if it changes before it is renamed
+  pub modified: Option<SystemTime>,
+    // with overwrite, whether the destination is an existing file that the file replaces, rather than one that is moved out of the way first
+  pub replaces: bool,
+  pub action: PlanAction,
+}
+
+  // how a file's counter relates to the file before it in the plan
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CounterStep {
+    // the first file of its counter group, e.g. its day, so the counter started over
+  Reset,
+    // one more than the previous file
+  Increment,
+    // taken at the same time as the previous file
+  Tie,
+}
+
+#[derive(Debug, Clone)]
+pub struct Numbering {
+  pub counter: u32,
+  pub step: CounterStep,
+    // the file the step is relative to, if there is one
+  pub previous: Option<PathBuf>,
+}
+
+  // a file with a usable date, while the plan is being made
+struct Entry {
+  path: PathBuf,
+  datetime: OrdDateTime,
+  date_source: DateSource,
+  subseconds: Subseconds,
+  offset: Option<i16>,
+  text: TextValues,
+    // what files taken in the same second without subseconds are ordered by, see capture_order
+  image_number: Option<u32>,
+  created: Option<SystemTime>,
+  modified: Option<SystemTime>,
+}
+
+  // reads the date of each file and decides on its new name
+  // files without a usable date or outside of the date range come first in the plan, followed by all others in the order they were taken
+pub fn plan_renames(paths: &[PathBuf], opts: &RenameOptions)->Vec<RenamePlan> {
+  plan_renames_with_progress(paths, opts, &|| {})
+}
+
+  // how far past the current time a date may be before reject_future skips it, in seconds
+  // dates without an offset are compared as if they were in UTC, so a day also covers local times far east of it
+const FUTURE_TOLERANCE: i64 = 24 * 60 * 60;
+
+  // like plan_renames, but calls on_read after the metadata of each file has been read
+  // the files are read in parallel, so on_read is called from several threads
+pub fn plan_renames_with_progress(paths: &[PathBuf], opts: &RenameOptions, on_read: &(dyn Fn() + Sync))->Vec<RenamePlan> {
+  let mut plan = Vec::new();
+  let mut valid_entries: Vec<Entry> = Vec::new();
+
+    // reading the metadata is the slow part, so it's done in parallel
+    // collect keeps the results in the order of paths, which makes the rest of the plan independent of thread timing
+    // the modification time is taken first, so that any change while the file is read shows up later
+  let datetimes: Vec<_> = paths.par_iter().map(|path| {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let datetime = match opts.cache {
+      Some(ref cache) => cache.get_metadata(path, &opts.read),
+      None => get_metadata(path, &opts.read),
+    };
+    on_read();
+    (datetime, modified)
+  }).collect();
+
+  let latest = SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64) + FUTURE_TOLERANCE;
+  let in_range = |datetime: &OrdDateTime| {
+    opts.since.as_ref().is_none_or(|since| datetime.date_cmp(since) != Ordering::Less) &&
+    opts.until.as_ref().is_none_or(|until| datetime.date_cmp(until) != Ordering::Greater)
+  };
+
+  for (path, (datetime_res, modified)) in paths.iter().zip(datetimes) {
+    let (metadata, mut date_source) = match datetime_res {
+      Ok(metadata) => (metadata, DateSource::Metadata),
+        // files that are damaged, rather than just without a date, are reported instead of being dated another way
+      Err(error) => match error.is_missing_date().then(|| date_without_metadata(path, opts)).flatten() {
+        Some((datetime, date_source)) => (FileMetadata { datetime, subseconds: Subseconds::Missing, offset: None, gps: None, make: None, model: None, lens: None, focal_length: None, image_number: None, dimensions: None }, date_source),
+        None => {
+          plan.push(RenamePlan { source: path.clone(), datetime: None, date_source: DateSource::Metadata, subseconds: Subseconds::Missing, offset: None, sidecar_of: None, numbering: None, modified: None, replaces: false, action: PlanAction::Skip(SkipReason::NoDateTime(error)) });
+          continue;
+        },
+      },
+    };
+
+    let mut datetime = OrdDateTime::from(metadata.datetime);
+    if let Some(reason) = size_skip(metadata.dimensions, opts) {
+      plan.push(RenamePlan { source: path.clone(), datetime: Some(datetime), date_source, subseconds: metadata.subseconds, offset: metadata.offset, sidecar_of: None, numbering: None, modified: None, replaces: false, action: PlanAction::Skip(reason) });
+      continue;
+    }
+      // a date from the file name is usually older than the file itself, e.g. for scans, so it isn't checked
+    if let (Some(tolerance), DateSource::Metadata) = (opts.mtime_tolerance, date_source) {
+      if let Some(modified) = modified_time(path) {
+        if (datetime.timestamp() - modified).abs() > tolerance {
+          let modified = OrdDateTime::from(datetime_from_unix(modified, &opts.read));
+          if !opts.fallback_to_mtime {
+            plan.push(RenamePlan { source: path.clone(), datetime: Some(datetime), date_source, subseconds: metadata.subseconds, offset: metadata.offset, sidecar_of: None, numbering: None, modified: None, replaces: false, action: PlanAction::Skip(SkipReason::SuspiciousDate(modified)) });
+            continue;
+          }
+          datetime = modified;
+          date_source = DateSource::ModifiedTime;
+        }
+      }
+    }
+      // the modification time has no subseconds, whatever the metadata had
+    let subseconds = if date_source == DateSource::ModifiedTime { Subseconds::Missing } else { metadata.subseconds };
+
+    if opts.reject_future && datetime.timestamp() > latest {
+      plan.push(RenamePlan { source: path.clone(), datetime: Some(datetime), date_source, subseconds, offset: metadata.offset, sidecar_of: None, numbering: None, modified: None, replaces: false, action: PlanAction::Skip(SkipReason::FutureDate) });
+      continue;
+    }
+    if in_range(&datetime) {
+      let place = match (&opts.places, metadata.gps) {
+        (Some(places), Some(gps)) => places.lookup(gps),
+        _ => None,
+      };
+      let text = TextValues {
+        place: place.unwrap_or(&opts.place_default).to_string(),
+        make: metadata.make.unwrap_or_else(|| opts.camera_default.clone()),
+        model: metadata.model.unwrap_or_else(|| opts.camera_default.clone()),
+        lens: metadata.lens.unwrap_or_else(|| opts.lens_default.clone()),
+        focal: metadata.focal_length.map_or_else(|| opts.lens_default.clone(), format_focal_length),
+        subdir: subdir(path, &opts.input_dir),
+        parent: parent_name(path, &opts.input_dir),
+        session: 1,
+      };
+      valid_entries.push(Entry { path: path.clone(), datetime, date_source, subseconds, offset: metadata.offset, text, image_number: metadata.image_number, created: None, modified });
+    } else {
+      plan.push(RenamePlan { source: path.clone(), datetime: Some(datetime), date_source, subseconds, offset: metadata.offset, sidecar_of: None, numbering: None, modified: None, replaces: false, action: PlanAction::Skip(SkipReason::OutOfRange) });
+    }
+  }
+
+  if valid_entries.is_empty() {
+    return plan;
+  }
+
+  add_creation_times(&mut valid_entries);
+    // files taken at the same time are ordered as they were taken if that can be told, and by their path otherwise,
+    // so that they are numbered the same way on every run
+  if !opts.keeps_folders() {
+    valid_entries.sort_unstable_by(|a, b| a.datetime.cmp(&b.datetime).then_with(|| capture_order(a, b)).then_with(|| a.path.cmp(&b.path)));
+  } else {
+    valid_entries.sort_unstable_by(|a, b| a.path.parent().cmp(&b.path.parent()).then(a.datetime.cmp(&b.datetime)).then_with(|| capture_order(a, b)).then_with(|| a.path.cmp(&b.path)));
+  }
+
+  if opts.dedupe {
+    valid_entries = remove_duplicates(valid_entries, &mut plan);
+  }
+
+  if let Some(gap) = opts.session_gap {
+    assign_sessions(&mut valid_entries, gap, opts.keeps_folders());
+  }
+
+  let sidecar_index = find_sidecars(&valid_entries, &opts.sidecar_extensions);
+  if opts.copy {
+    plan.extend(name_entries(&valid_entries, opts, &sidecar_index, HashSet::new()));
+    return plan;
+  }
+
+    // when moving, all files are moved out of the way before any of them gets its new name (see execute_plan),
+    // so each file that is renamed frees up its name for all others, not only for the ones after it
+    // files that end up staying where they are keep their names taken, which can change the names of others, so this is repeated until nothing changes
+  let sources: HashSet<&PathBuf> = valid_entries.iter().map(|entry| &entry.path).chain(sidecar_index.values().flatten()).collect();
+  let mut staying: HashSet<PathBuf> = HashSet::new();
+  loop {
+    let vacated = sources.iter().filter(|source| !staying.contains(**source)).map(|source| source.to_path_buf()).collect();
+    let named = name_entries(&valid_entries, opts, &sidecar_index, vacated);
+    let renamed: HashSet<&PathBuf> = named.iter().filter(|entry| matches!(entry.action, PlanAction::Rename(_))).map(|entry| &entry.source).collect();
+    let newly_staying: Vec<PathBuf> = sources.iter().filter(|source| !renamed.contains(**source) && !staying.contains(**source)).map(|source| source.to_path_buf()).collect();
+    if newly_staying.is_empty() {
+      plan.extend(named);
+      return plan;
+    }
+    staying.extend(newly_staying);
+  }
+}
+
+  // e.g. 50.0 -> "50mm", 4.25 -> "4.3mm"
+fn format_focal_length(millimeters: f64)->String {
+  let tenths = (millimeters * 10.0).round();
+  if tenths % 10.0 == 0.0 { format!("{}mm", tenths / 10.0) } else { format!("{:.1}mm", tenths / 10.0) }
+}
+
+  // reads the creation times of the files that share their date with another file, which are the only ones that need them
+fn add_creation_times(entries: &mut [Entry]) {
+  let mut per_datetime: BTreeMap<&OrdDateTime, usize> = BTreeMap::new();
+  for entry in entries.iter() {
+    *per_datetime.entry(&entry.datetime).or_insert(0) += 1;
+  }
+  let shared: HashSet<usize> = entries.iter().enumerate()
+    .filter(|(_, entry)| per_datetime[&entry.datetime] > 1)
+    .map(|(index, _)| index)
+    .collect();
+  for index in shared {
+    let entry = &mut entries[index];
+    entry.created = fs::metadata(&entry.path).and_then(|metadata| metadata.created()).ok();
+  }
+}
+
+  // the order of files taken at the same time, e.g. in a burst without subseconds
+  // files with an ImageNumber come first, in its order, and the others by their creation time, where the file system keeps one
+fn capture_order(a: &Entry, b: &Entry)->Ordering {
+  a.image_number.is_none().cmp(&b.image_number.is_none())
+    .then(a.image_number.cmp(&b.image_number))
+    .then(a.created.cmp(&b.created))
+}
+
+  // why a file of the given size is skipped, if it is
+fn size_skip(dimensions: Option<Dimensions>, opts: &RenameOptions)->Option<SkipReason> {
+  if opts.min_dimensions.is_none() && opts.max_dimensions.is_none() {
+    return None;
+  }
+  match dimensions {
+    None if opts.skip_unknown_dimensions => Some(SkipReason::UnknownDimensions),
+    None => None,
+    Some(dimensions) if opts.min_dimensions.is_some_and(|min| !dimensions.at_least(min)) => Some(SkipReason::TooSmall(dimensions)),
+    Some(dimensions) if opts.max_dimensions.is_some_and(|max| !dimensions.at_most(max)) => Some(SkipReason::TooLarge(dimensions)),
+    Some(_) => None,
+  }
+}
+
+  // takes the files that have the same date and contents as an earlier entry out of entries, and adds them to the plan as skipped
+  // only files whose date is shared with another file are read, as all others can't be duplicates
+fn remove_duplicates(entries: Vec<Entry>, plan: &mut Vec<RenamePlan>)->Vec<Entry> {
+  let mut per_datetime: BTreeMap<&OrdDateTime, usize> = BTreeMap::new();
+  for entry in &entries {
+    *per_datetime.entry(&entry.datetime).or_insert(0) += 1;
+  }
+  let shared: HashSet<usize> = entries.iter().enumerate()
+    .filter(|(_, entry)| per_datetime[&entry.datetime] > 1)
+    .map(|(index, _)| index)
+    .collect();
+  let hashes: HashMap<usize, Option<Vec<u8>>> = shared.par_iter().map(|&index| (index, content_hash(&entries[index].path).ok())).collect();
+
+  let mut originals: BTreeMap<(OrdDateTime, Vec<u8>), PathBuf> = BTreeMap::new();
+  let mut kept = Vec::new();
+  for (index, entry) in entries.into_iter().enumerate() {
+    let hash = match hashes.get(&index) {
+      Some(Some(hash)) => hash.clone(),
+      _ => {
+        kept.push(entry);
+        continue;
+      },
+    };
+    match originals.get(&(entry.datetime.clone(), hash.clone())) {
+      Some(original) => {
+        let Entry { path, datetime, date_source, subseconds, offset, .. } = entry;
+        plan.push(RenamePlan { source: path, datetime: Some(datetime), date_source, subseconds, offset, sidecar_of: None, numbering: None, modified: None, replaces: false, action: PlanAction::Skip(SkipReason::Duplicate(original.clone())) });
+      },
+      None => {
+        originals.insert((entry.datetime.clone(), hash), entry.path.clone());
+        kept.push(entry);
+      },
+    }
+  }
+  kept
+}
+
+  // groups the paths that have the same contents, in the order of their first file, with each group in the order of paths
+  // only files that have the same size as another one are hashed, and files that can't be read are left out
+  // on_hashed is called once for each path, from several threads
+pub fn find_duplicates(paths: &[PathBuf], on_hashed: &(dyn Fn() + Sync))->Vec<Vec<PathBuf>> {
+  let sizes: Vec<Option<u64>> = paths.iter().map(|path| fs::metadata(path).ok().map(|metadata| metadata.len())).collect();
+  let mut per_size: HashMap<u64, usize> = HashMap::new();
+  for &size in sizes.iter().flatten() {
+    *per_size.entry(size).or_insert(0) += 1;
+  }
+  let hashes: Vec<Option<Vec<u8>>> = paths.par_iter().zip(&sizes).map(|(path, size)| {
+    let hash = size.filter(|size| per_size[size] > 1).and_then(|_| content_hash(path).ok());
+    on_hashed();
+    hash
+  }).collect();
+
+  let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+  let mut group_of: HashMap<(u64, Vec<u8>), usize> = HashMap::new();
+  for ((path, size), hash) in paths.iter().zip(sizes).zip(hashes) {
+    if let (Some(size), Some(hash)) = (size, hash) {
+      let index = *group_of.entry((size, hash)).or_insert_with(|| {
+        groups.push(Vec::new());
+        groups.len() - 1
+      });
+      groups[index].push(path.clone());
+    }
+  }
+  groups.retain(|group| group.len() > 1);
+  groups
+}
+
+  // the SHA-256 of a file's contents. files that can't be read aren't treated as a duplicate of anything
+pub(crate) fn content_hash(path: &Path)->std::io::Result<Vec<u8>> {
+  let mut file = fs::File::open(path)?;
+  let mut hasher = Sha256::new();
+  std::io::copy(&mut file, &mut hasher)?;
+  Ok(hasher.finalize().to_vec())
+}
+
+  // numbers the sessions of the sorted entries, starting at 1, with a new one whenever more than gap seconds pass between two files
+  // with in_place, a new folder starts a new session as well
+fn assign_sessions(entries: &mut [Entry], gap: i64, in_place: bool) {
+  let mut session = 1;
+  for index in 1..entries.len() {
+    let (prev, entry) = (&entries[index - 1], &entries[index]);
+    if entry.datetime.timestamp() - prev.datetime.timestamp() > gap || (in_place && entry.path.parent() != prev.path.parent()) {
+      session += 1;
+    }
+    entries[index].text.session = session;
+  }
+}
+
+  // numbers the entries, which are sorted in the order they were taken, and picks their new names
+  // vacated are the files that will be out of the way by the time the entries are renamed
+fn name_entries(entries: &[Entry], opts: &RenameOptions, sidecar_index: &HashMap<(PathBuf, OsString), Vec<PathBuf>>, vacated: HashSet<PathBuf>)->Vec<RenamePlan> {
+  let mut plan = Vec::new();
+  let mut dests = DestTracker { vacated, copy: opts.copy, keep_identical: opts.flat, overwrite: opts.overwrite, ..DestTracker::default() };
+    // a sidecar shared by several files, e.g. a RAW and JPEG pair, only goes along with the first of them
+  let mut used_sidecars: HashSet<&PathBuf> = HashSet::new();
+
+  let dest_dir = |entry: &Entry| {
+    let parent = entry.path.parent().unwrap_or(Path::new(""));
+    let mut dest_dir = match opts.target_dir {
+      Some(ref target_dir) if opts.mirror => target_dir.join(parent.strip_prefix(&opts.input_dir).unwrap_or(Path::new(""))),
+      Some(ref target_dir) => target_dir.clone(),
+      None => parent.to_path_buf(),
+    };
+    if let Some(group_by) = opts.group_by {
+      dest_dir.push(group_by.subdir(&entry.datetime.0));
+    }
+    dest_dir
+  };
+    // with a session gap, the counter starts over for each session instead of each day
+  let same_session = |a: &Entry, b: &Entry| a.text.session == b.text.session;
+  let same_group = |a: &Entry, b: &Entry| match opts.numbering {
+    NumberingScope::Global => same_session(a, b),
+    NumberingScope::PerDay if opts.session_gap.is_some() => same_session(a, b) && (!opts.keeps_folders() || a.path.parent() == b.path.parent()),
+    NumberingScope::PerDay => a.datetime.date_eq(&b.datetime) && (!opts.keeps_folders() || a.path.parent() == b.path.parent()),
+    NumberingScope::PerFolder => same_session(a, b) && dest_dir(a) == dest_dir(b),
+  };
+    // files in different directories can't collide, so they are never tied
+  let tied = |a: &Entry, b: &Entry| same_group(a, b) && a.datetime == b.datetime && dest_dir(a) == dest_dir(b);
+
+  let existing = if opts.continue_counter { existing_counters(entries, opts, sidecar_index, &dest_dir) } else { Vec::new() };
+
+    // the counter goes up for each new timestamp within a group, see NumberingScope
+  let mut counters: Vec<u32> = Vec::with_capacity(entries.len());
+  for (index, entry) in entries.iter().enumerate() {
+    let counter = match index.checked_sub(1).map(|prev| (&entries[prev], counters[prev])) {
+      Some((prev, prev_counter)) if same_group(prev, entry) => {
+        if !tied(prev, entry) || opts.tie_break == TieBreak::KeepBothIncrement { prev_counter.saturating_add(1) } else { prev_counter }
+      },
+      _ if opts.continue_counter => {
+        let group_len = 1 + entries[index..].windows(2).take_while(|pair| same_group(&pair[0], &pair[1])).count();
+        let highest = existing[index..index + group_len].iter().flatten().max();
+        highest.map_or(opts.counter_start, |&highest| opts.counter_start.max(highest.saturating_add(1)))
+      },
+      _ => opts.counter_start,
+    };
+    counters.push(counter);
+  }
+    // the largest counter in the group of each entry, which is that of the last entry in the group
+  let mut largest_counters = counters.clone();
+  for index in (1..entries.len()).rev() {
+    if same_group(&entries[index - 1], &entries[index]) {
+      largest_counters[index - 1] = largest_counters[index];
+    }
+  }
+
+    // the position of a file within a run of files with the same timestamp
+  let mut tie_index = 0;
+  let mut tie_source: Option<&PathBuf> = None;
+    // the new name of each file is built in the same buffer
+  let mut new_stem = String::new();
+  let case = opts.template.case();
+  for (index, entry) in entries.iter().enumerate() {
+    let Entry { ref path, ref datetime, date_source, ref subseconds, offset, ref text, modified, .. } = *entry;
+    let prev = if index > 0 { Some(&entries[index - 1]) } else { None };
+    let next = entries.get(index + 1);
+
+    let is_tie = prev.is_some_and(|prev| tied(prev, entry));
+    tie_index = if is_tie { tie_index + 1 } else { 0 };
+    if !is_tie {
+      tie_source = Some(path);
+    }
+
+    let img_number = counters[index];
+    let numbering = Some(Numbering {
+      counter: img_number,
+      step: match prev {
+        Some(prev) if same_group(prev, entry) => if is_tie { CounterStep::Tie } else { CounterStep::Increment },
+        _ => CounterStep::Reset,
+      },
+      previous: prev.map(|prev| prev.path.clone()),
+    });
+
+    opts.template.expand_into(&mut new_stem, &datetime.0, img_number, largest_counters[index], text);
+    let has_ties = is_tie || next.is_some_and(|next| tied(entry, next));
+    if has_ties && opts.tie_break == TieBreak::CollisionSuffix {
+      new_stem.push('-');
+      new_stem.push_str(&case.apply(&letter_suffix(tie_index)));
+    }
+      // Windows can't create files named like devices, e.g. from a {model} of "CON"
+    #[cfg(windows)]
+    {
+      new_stem = crate::windows::safe_stem(std::mem::take(&mut new_stem));
+    }
+
+    if is_tie && opts.tie_break == TieBreak::Skip {
+      let first = tie_source.cloned().unwrap_or_default();
+      plan.push(RenamePlan { source: path.clone(), datetime: Some(datetime.clone()), date_source, subseconds: subseconds.clone(), offset, sidecar_of: None, numbering, modified: None, replaces: false, action: PlanAction::Skip(SkipReason::SameTimestamp(first)) });
+      continue;
+    }
+
+    let dest_dir = dest_dir(entry);
+
+      // renaming files that were named by an earlier run would only shuffle their numbers around
+    let already_named = path.parent() == Some(dest_dir.as_path()) &&
+      path.file_stem().is_some_and(|stem| opts.template.matches(&stem.to_string_lossy(), &datetime.0, text)) &&
+      path.extension().is_some_and(|ext| new_extension(&ext.to_string_lossy(), opts) == ext.to_string_lossy());
+    if already_named && !opts.force {
+      plan.push(RenamePlan { source: path.clone(), datetime: Some(datetime.clone()), date_source, subseconds: subseconds.clone(), offset, sidecar_of: None, numbering, modified: None, replaces: false, action: PlanAction::Skip(SkipReason::AlreadyNamed) });
+      continue;
+    }
+
+    let sidecars: Vec<&PathBuf> = match (path.parent(), path.file_stem()) {
+      (Some(dir), Some(stem)) => sidecar_index.get(&(dir.to_path_buf(), stem.to_os_string())).into_iter().flatten()
+        .filter(|sidecar| !used_sidecars.contains(sidecar))
+        .collect(),
+      _ => Vec::new(),
+    };
+      // the sidecars' extensions are written the same way as the file's, so that e.g. IMG.CR2 and IMG.XMP stay alike
+    let sidecar_exts: Vec<String> = sidecars.iter().filter_map(|sidecar| sidecar.extension()).map(|ext| new_extension(&ext.to_string_lossy(), opts)).collect();
+
+    let mut sidecar_plans = Vec::new();
+    let mut replaces = false;
+    let action = if let Some(ext) = path.extension() {
+      let ext = new_extension(&ext.to_string_lossy(), opts);
+      let action = dests.choose(path, &dest_dir, &new_stem, &ext, &sidecar_exts);
+      if let PlanAction::Rename(ref dest) = action {
+          // choose only picks a name that is still in use with overwrite, and never for the sidecars
+        replaces = dests.exists_on_disk(dest);
+        dests.claim(path, dest);
+        for (sidecar, sidecar_ext) in sidecars.into_iter().zip(&sidecar_exts) {
+          let sidecar_dest = dest.with_extension(sidecar_ext);
+          dests.claim(sidecar, &sidecar_dest);
+          used_sidecars.insert(sidecar);
+          sidecar_plans.push(RenamePlan {
+            source: sidecar.clone(),
+            datetime: Some(datetime.clone()),
+            date_source,
+            subseconds: subseconds.clone(),
+            offset,
+            sidecar_of: Some(path.clone()),
+            numbering: None,
+            modified: fs::metadata(sidecar).and_then(|metadata| metadata.modified()).ok(),
+            replaces: false,
+            action: PlanAction::Rename(sidecar_dest),
+          });
+        }
+      }
+      action
+    } else {
+      PlanAction::Skip(SkipReason::NoExtension)
+    };
+
+    plan.push(RenamePlan { source: path.clone(), datetime: Some(datetime.clone()), date_source, subseconds: subseconds.clone(), offset, sidecar_of: None, numbering, modified, replaces, action });
+    plan.extend(sidecar_plans);
+  }
+
+  plan
+}
+
+  // the extension of a file's new name, in the case of the template
+fn new_extension(ext: &str, opts: &RenameOptions)->String {
+  let ext = if opts.normalize_ext { normalize_extension(ext) } else { ext.to_string() };
+  opts.template.case().apply(&ext).into_owned()
+}
+
+  // e.g. "JPEG" -> "jpg"
+fn normalize_extension(ext: &str)->String {
+  let ext = ext.to_lowercase();
+  match ext.as_str() {
+    "jpeg" | "jpe" => String::from("jpg"),
+    "tif" => String::from("tiff"),
+    _ => ext,
+  }
+}
+
+  // the sidecar files next to the given files, by their directory and file stem
+  // only files with one of the given extensions (matched case-insensitively) are sidecars
+fn find_sidecars(entries: &[Entry], extensions: &[String])->HashMap<(PathBuf, OsString), Vec<PathBuf>> {
+  let mut index: HashMap<(PathBuf, OsString), Vec<PathBuf>> = HashMap::new();
+  if extensions.is_empty() {
+    return index;
+  }
+
+  let dirs: HashSet<&Path> = entries.iter().filter_map(|entry| entry.path.parent()).collect();
+  for dir in dirs {
+    let dir_entries = match fs::read_dir(dir) {
+      Ok(dir_entries) => dir_entries,
+      Err(_) => continue, // the files in it couldn't be renamed either
+    };
+    for dir_entry in dir_entries.filter_map(Result::ok) {
+      let path = dir_entry.path();
+      let is_sidecar = path.extension()
+        .is_some_and(|ext| extensions.iter().any(|sidecar_ext| ext.to_string_lossy().eq_ignore_ascii_case(sidecar_ext)));
+      if !is_sidecar || !path.is_file() {
+        continue;
+      }
+      if let Some(stem) = path.file_stem() {
+        index.entry((dir.to_path_buf(), stem.to_os_string())).or_default().push(path.clone());
+      }
+    }
+  }
+
+  for sidecars in index.values_mut() {
+    sidecars.sort(); // read_dir has no defined order
+  }
+  index
+}
+
+  // compiles a pattern for RenameOptions::parse_name, making sure it has the groups needed for a date
+pub fn parse_name_pattern(pattern: &str)->Result<Regex, String> {
+  let regex = Regex::new(pattern).map_err(|error| format!("Invalid name pattern \"{}\": {}", pattern, error))?;
+  for group in &["year", "month", "day"] {
+    if !regex.capture_names().any(|name| name == Some(group)) {
+      return Err(format!("The name pattern \"{}\" has no (?P<{}>...) group", pattern, group));
+    }
+  }
+  Ok(regex)
+}
+
+  // the date given by the named groups of pattern in the file name of path, if it matches and the date is valid
+  // it is taken as local time, at the assumed offset if use_offset is set
+fn datetime_from_name(path: &Path, pattern: &Regex, opts: &ReadOptions)->Option<exif::DateTime> {
+  let name = path.file_name()?.to_string_lossy();
+  let captures = pattern.captures(&name)?;
+  let group = |name: &str, default: u16, range: std::ops::RangeInclusive<u16>| match captures.name(name) {
+    Some(value) => value.as_str().parse::<u16>().ok().filter(|value| range.contains(value)),
+    None => Some(default),
+  };
+  let year = captures.name("year")?.as_str().parse::<u16>().ok()?;
+  let month = captures.name("month")?.as_str().parse::<u8>().ok().filter(|month| (1..=12).contains(month))?;
+  let day = captures.name("day")?.as_str().parse::<u8>().ok().filter(|&day| day >= 1 && day <= days_in_month(year, month))?;
+  let mut datetime = exif::DateTime {
+    year,
+    month,
+    day,
+    hour: group("hour", 0, 0..=23)? as u8,
+    minute: group("minute", 0, 0..=59)? as u8,
+    second: group("second", 0, 0..=59)? as u8,
+    nanosecond: None,
+    offset: if opts.use_offset { Some(opts.assumed_offset) } else { None },
+  };
+  convert_to_timezone(&mut datetime, opts.assumed_offset, opts);
+  Some(datetime)
+}
+
+  // the modification time of a file in seconds since 1970-01-01 UTC, if the file system provides one
+fn modified_time(path: &Path)->Option<i64> {
+  let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+  match modified.duration_since(std::time::UNIX_EPOCH) {
+    Ok(since_epoch) => Some(since_epoch.as_secs() as i64),
+    Err(error) => Some(-(error.duration().as_secs() as i64)),
+  }
+}
+
+  // counts of the different outcomes in a plan
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PlanSummary {
+  pub renames: usize,
+  pub collisions: usize,
+  pub no_datetime: usize,
+  pub no_extension: usize,
+  pub out_of_range: usize,
+  pub suspicious: usize,
+  pub future: usize,
+  pub already_named: usize,
+  pub declined: usize,
+  pub duplicates: usize,
+  pub wrong_size: usize,
+  pub not_in_manifest: usize,
+}
+
+impl PlanSummary {
+  pub fn of(plan: &[RenamePlan])->PlanSummary {
+    let mut summary = PlanSummary::default();
+    for entry in plan {
+      match entry.action {
+        PlanAction::Rename(_) => summary.renames += 1,
+        PlanAction::Skip(SkipReason::TargetExists(_)) | PlanAction::Skip(SkipReason::IdenticalExists(_)) |
+        PlanAction::Skip(SkipReason::SameTimestamp(_)) | PlanAction::Skip(SkipReason::Conflict(_)) => summary.collisions += 1,
+        PlanAction::Skip(SkipReason::NoDateTime(_)) => summary.no_datetime += 1,
+        PlanAction::Skip(SkipReason::NoExtension) => summary.no_extension += 1,
+        PlanAction::Skip(SkipReason::OutOfRange) => summary.out_of_range += 1,
+        PlanAction::Skip(SkipReason::SuspiciousDate(_)) => summary.suspicious += 1,
+        PlanAction::Skip(SkipReason::FutureDate) => summary.future += 1,
+        PlanAction::Skip(SkipReason::AlreadyNamed) => summary.already_named += 1,
+        PlanAction::Skip(SkipReason::Declined) => summary.declined += 1,
+        PlanAction::Skip(SkipReason::Duplicate(_)) => summary.duplicates += 1,
+        PlanAction::Skip(SkipReason::TooSmall(_)) | PlanAction::Skip(SkipReason::TooLarge(_)) |
+        PlanAction::Skip(SkipReason::UnknownDimensions) => summary.wrong_size += 1,
+        PlanAction::Skip(SkipReason::NotInManifest) | PlanAction::Skip(SkipReason::Unhashable(_)) => summary.not_in_manifest += 1,
+      }
+    }
+    summary
+  }
+}
+
+  // the different time zone offsets stored with the dates in the plan, in minutes and in order
+  // without use_offset, files with different offsets are ordered by their local times, which may not be the order they were taken in
+pub fn distinct_offsets(plan: &[RenamePlan])->Vec<i16> {
+  let offsets: BTreeSet<i16> = plan.iter().filter(|entry| entry.sidecar_of.is_none()).filter_map(|entry| entry.offset).collect();
+  offsets.into_iter().collect()
+}
+
+  // 0 -> "a", 25 -> "z", 26 -> "aa", ...
+fn letter_suffix(index: usize)->String {
+  let mut letters = Vec::new();
+  let mut remaining = index + 1;
+  while remaining > 0 {
+    remaining -= 1;
+    letters.push((b'a' + (remaining % 26) as u8) as char);
+    remaining /= 26;
+  }
+  letters.iter().rev().collect()
+}
+
+  // checks the finished plan for renames that would run into each other, and turns them into skips
+  // that is two renames with the same destination, or a destination that exists and won't be moved away first
+  // the sidecars of a skipped file are skipped with it. returns the conflicting destinations
+  // with overwrite, existing destinations that may be replaced by their file don't conflict, and are marked as replaced
+pub fn check_destinations(plan: &mut [RenamePlan], copy: bool, overwrite: bool)->Vec<PathBuf> {
+  let mut dest_sources: HashMap<PathBuf, Vec<&Path>> = HashMap::new();
+  let mut moving: HashSet<PathBuf> = HashSet::new();
+  for entry in plan.iter() {
+    if let PlanAction::Rename(ref dest) = entry.action {
+      dest_sources.entry(dest.clone()).or_default().push(&entry.source);
+      if !copy {
+        moving.insert(entry.source.clone());
+      }
+    }
+  }
+
+  let mut replaced: HashSet<PathBuf> = HashSet::new();
+  let mut conflicts: Vec<PathBuf> = Vec::new();
+  for (dest, sources) in dest_sources {
+    match sources[..] {
+      [_] if !dest.exists() || moving.contains(&dest) => {},
+      [source] if overwrite && replaceable(source, &dest) => {
+        replaced.insert(dest);
+      },
+      _ => conflicts.push(dest),
+    }
+  }
+  for entry in plan.iter_mut() {
+    entry.replaces = matches!(entry.action, PlanAction::Rename(ref dest) if replaced.contains(dest));
+  }
+  if conflicts.is_empty() {
+    return conflicts;
+  }
+  conflicts.sort();
+
+  let mut skipped: HashMap<PathBuf, PathBuf> = HashMap::new();
+  for entry in plan.iter() {
+    let conflict = match entry.action {
+      PlanAction::Rename(ref dest) if conflicts.contains(dest) => dest.clone(),
+      _ => continue,
+    };
+    let main_file = entry.sidecar_of.clone().unwrap_or_else(|| entry.source.clone());
+    skipped.insert(main_file, conflict);
+  }
+  for entry in plan.iter_mut() {
+    if let PlanAction::Rename(_) = entry.action {
+      let main_file = entry.sidecar_of.as_ref().unwrap_or(&entry.source);
+      if let Some(conflict) = skipped.get(main_file) {
+        entry.action = PlanAction::Skip(SkipReason::Conflict(conflict.clone()));
+      }
+    }
+  }
+  conflicts
+}
+
+  // the directories that don't exist yet, but will be created to hold the renamed files of plan
+pub fn planned_dirs(plan: &[RenamePlan])->Vec<PathBuf> {
+  let mut dirs: Vec<PathBuf> = plan.iter()
+    .filter_map(|entry| match entry.action {
+      PlanAction::Rename(ref dest) => dest.parent().map(Path::to_path_buf),
+      PlanAction::Skip(_) => None,
+    })
+    .filter(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+    .collect();
+  dirs.sort();
+  dirs.dedup();
+  dirs
+}
+
+  // the highest suffix tried when a destination is already taken
+const MAX_COLLISION_SUFFIX: u32 = 99;
+
+  // keeps track of which destinations are available at each point of the plan
+#[derive(Default)]
+struct DestTracker {
+    // destinations taken by earlier files in the plan
+  claimed: HashSet<PathBuf>,
+    // sources that will have been moved away by earlier files in the plan
+  vacated: HashSet<PathBuf>,
+    // copied sources stay where they are, so they never free up their name
+  copy: bool,
+    // identical copies don't stop a file from being renamed, it gets a suffixed name instead
+  keep_identical: bool,
+    // existing files that may be replaced take the file's name anyway
+  overwrite: bool,
+}
+
+impl DestTracker {
+  fn exists_on_disk(&self, dest: &Path)->bool {
+    fs_path(dest).exists() && !self.vacated.contains(dest)
+  }
+
+  fn is_free(&self, dest: &Path)->bool {
+    !self.claimed.contains(dest) && !self.exists_on_disk(dest)
+  }
+
+  fn claim(&mut self, source: &Path, dest: &Path) {
+    self.claimed.insert(dest.to_path_buf());
+    if !self.copy {
+      self.vacated.insert(source.to_path_buf());
+    }
+  }
+
+    // picks the first free name out of stem.ext, stem-2.ext, stem-3.ext, ...
+    // the name also has to be free for each of the sidecar extensions, so that the sidecars can keep matching the file
+    // gives up if the file already has the wanted name, or if an identical copy of it is found along the way
+    // with overwrite, an existing file that may be replaced by the file doesn't count as taking its name
+  fn choose(&self, source: &Path, dir: &Path, stem: &str, ext: &str, sidecar_exts: &[String])->PlanAction {
+    let first_dest = dir.join(format!("{}.{}", stem, ext));
+    let mut dest = first_dest.clone();
+    for suffix in 2..=MAX_COLLISION_SUFFIX + 1 {
+      if dest == source {
+        return PlanAction::Skip(SkipReason::TargetExists(dest));
+      }
+
+      let sidecars_free = sidecar_exts.iter().all(|sidecar_ext| self.is_free(&dest.with_extension(sidecar_ext)));
+      if self.is_free(&dest) && sidecars_free {
+        return PlanAction::Rename(dest);
+      }
+      if self.overwrite && sidecars_free && !self.claimed.contains(&dest) && self.exists_on_disk(&dest) && replaceable(source, &dest) {
+        return PlanAction::Rename(dest);
+      }
+
+      if !self.keep_identical && self.exists_on_disk(&dest) && files_identical(source, &dest) {
+        return PlanAction::Skip(SkipReason::IdenticalExists(dest));
+      }
+
+      dest = dir.join(format!("{}-{}.{}", stem, suffix, ext));
+    }
+
+    PlanAction::Skip(SkipReason::TargetExists(first_dest))
+  }
+}
+
+  // for each entry, the highest counter among the files in its destination that match the template for it
+  // the files of the plan and their sidecars are left out, as they are moved away or keep their place in the numbering anyway
+fn existing_counters(entries: &[Entry], opts: &RenameOptions, sidecar_index: &HashMap<(PathBuf, OsString), Vec<PathBuf>>, dest_dir: &dyn Fn(&Entry)->PathBuf)->Vec<Option<u32>> {
+  let planned: HashSet<&PathBuf> = entries.iter().map(|entry| &entry.path).chain(sidecar_index.values().flatten()).collect();
+  let mut stems: HashMap<PathBuf, Vec<String>> = HashMap::new();
+  entries.iter().map(|entry| {
+    let dir = dest_dir(entry);
+    let dir_stems = stems.entry(dir.clone()).or_insert_with(|| {
+      fs::read_dir(&dir).into_iter().flatten().flatten()
+        .map(|dir_entry| dir_entry.path())
+        .filter(|path| !planned.contains(path))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect()
+    });
+    dir_stems.iter().filter_map(|stem| opts.template.counter_of(stem, &entry.datetime.0, &entry.text)).max()
+  }).collect()
+}
+
+  // the date of a file whose metadata has none, from its name if it matches parse_name
+  // PNG files, e.g. screenshots, seldom have a date in them, so they can fall back to their modification time as well
+fn date_without_metadata(path: &Path, opts: &RenameOptions)->Option<(exif::DateTime, DateSource)> {
+  if let Some(datetime) = opts.parse_name.as_ref().and_then(|pattern| datetime_from_name(path, pattern, &opts.read)) {
+    return Some((datetime, DateSource::FileName));
+  }
+  if opts.fallback_to_mtime && is_png(path) {
+    return modified_time(path).map(|modified| (datetime_from_unix(modified, &opts.read), DateSource::ModifiedTime));
+  }
+  None
+}
+
+  // e.g. "trip-day 1" for input_dir/trip/day 1/a.jpg, and "" for files directly in input_dir
+fn subdir(path: &Path, input_dir: &Path)->String {
+  let parent = path.parent().unwrap_or(Path::new(""));
+  let relative = parent.strip_prefix(input_dir).unwrap_or(parent);
+  let names: Vec<_> = relative.components()
+    .filter_map(|component| match component {
+      Component::Normal(name) => Some(name.to_string_lossy()),
+      _ => None,
+    })
+    .collect();
+  names.join("-")
+}
+
+  // e.g. "day 1" for input_dir/trip/day 1/a.jpg, and "" for files directly in input_dir
+fn parent_name(path: &Path, input_dir: &Path)->String {
+  match path.parent() {
+    Some(parent) if parent != input_dir => parent.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+    _ => String::new(),
+  }
+}
+
+  // whether source may replace the existing file, because it is an identical copy or it is smaller, e.g. a worse export of the same image
+fn replaceable(source: &Path, existing: &Path)->bool {
+  match (fs::metadata(source), fs::metadata(existing)) {
+    (Ok(source_meta), Ok(existing_meta)) if existing_meta.is_file() => existing_meta.len() < source_meta.len() || files_identical(source, existing),
+    _ => false,
+  }
+}
+
+  // compares the contents of two files, treating any read error as a difference
+fn files_identical(a: &Path, b: &Path)->bool {
+  let same_len = match (fs::metadata(a), fs::metadata(b)) {
+    (Ok(a_meta), Ok(b_meta)) => a_meta.len() == b_meta.len(),
+    _ => false,
+  };
+  if !same_len {
+    return false;
+  }
+
+  let (mut a_file, mut b_file) = match (fs::File::open(a), fs::File::open(b)) {
+    (Ok(a_file), Ok(b_file)) => (a_file, b_file),
+    _ => return false,
+  };
+
+  let mut a_buf = [0u8; 8192];
+  let mut b_buf = [0u8; 8192];
+  loop {
+    let a_read = match read_full(&mut a_file, &mut a_buf) {
+      Ok(read) => read,
+      Err(_) => return false,
+    };
+    let b_read = match read_full(&mut b_file, &mut b_buf) {
+      Ok(read) => read,
+      Err(_) => return false,
+    };
+    if a_read != b_read || a_buf[..a_read] != b_buf[..b_read] {
+      return false;
+    }
+    if a_read == 0 {
+      return true;
+    }
+  }
+}
+
+  // like Read::read, but only returns less than buf.len() bytes at the end of the file
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8])->std::io::Result<usize> {
+  let mut total = 0;
+  while total < buf.len() {
+    match reader.read(&mut buf[total..])? {
+      0 => break,
+      read => total += read,
+    }
+  }
+  Ok(total)
+}
+
+#[derive(Debug, Snafu)]
+pub enum RenameError {
+  #[snafu(display("{} already exists", dest.display()))]
+  DestinationExists {
+    dest: PathBuf,
+  },
+  #[snafu(display("Could not create directory {}: {}", dir.display(), source))]
+  CreateDirFailed {
+    dir: PathBuf,
+    source: std::io::Error,
+  },
+  #[snafu(display("Rename failed: {}", source))]
+  RenameFailed {
+    source: std::io::Error,
+  },
+  #[snafu(display("Permission denied, make sure that the file isn't read-only and that you can write to {}{}", source_dir.display(),
+    if dest_dir == source_dir { String::new() } else { format!(" and {}", dest_dir.display()) }))]
+  PermissionDenied {
+    source_dir: PathBuf,
+    dest_dir: PathBuf,
+  },
+  #[snafu(display("Could not remove the original after copying it to the other drive: {}", source))]
+  RemoveAfterCopyFailed {
+    source: std::io::Error,
+  },
+  #[snafu(display("Copy failed: {}", source))]
+  CopyFailed {
+    source: std::io::Error,
+  },
+  #[snafu(display("Undone, as another file could not be renamed"))]
+  RolledBack,
+  #[snafu(display("Could not verify {}: {}", dest.display(), reason))]
+  VerifyFailed {
+    dest: PathBuf,
+    reason: &'static str,
+  },
+    // the rename went through, so the file left its temporary name, but what is at its new name now isn't a file
+  #[snafu(display("Could not verify {}: the file was renamed to it, but isn't a file there", dest.display()))]
+  NotFoundAfterRename {
+    dest: PathBuf,
+  },
+  #[snafu(display("Source changed during run, as it was moved, deleted or modified after its date was read"))]
+  SourceChanged,
+    // a file that wasn't renamed couldn't get its name back, e.g. because another file has taken it, so it was left at its temporary name
+    // error is why it wasn't renamed, or None if the run stopped before it
+  #[snafu(display("{}Could not move it back from {}: {}", error.as_ref().map_or(String::new(), |error| format!("{}. ", error)), temporary.display(), restore_error))]
+  NotMovedBack {
+    temporary: PathBuf,
+    restore_error: Box<RenameError>,
+    error: Option<Box<RenameError>>,
+  },
+}
+
+#[derive(Debug)]
+pub struct RenameResult {
+  pub source: PathBuf,
+  pub dest: PathBuf,
+  pub outcome: Result<(), RenameError>,
+}
+
+  // performs all renames in the plan, in order, copying the files instead if opts.copy is set
+  // when moving, all files are first moved to temporary names next to them, and then to their new names,
+  // so that files can take over each other's names without anything being overwritten
+  // returns one result for each entry with a rename action
+pub fn execute_plan(plan: &[RenamePlan], opts: &RenameOptions)->Vec<RenameResult> {
+  execute_plan_with_progress(plan, opts, &mut |_| true)
+}
+
+  // like execute_plan, but calls on_rename with the result of each rename
+  // if on_rename returns false, the remaining renames are left out, and only the results so far are returned,
+  // along with those of the remaining files that couldn't be moved back to their names
+pub fn execute_plan_with_progress(plan: &[RenamePlan], opts: &RenameOptions, on_rename: &mut dyn FnMut(&RenameResult)->bool)->Vec<RenameResult> {
+  let renames: Vec<(&PathBuf, &PathBuf)> = plan.iter().filter_map(|entry| match entry.action {
+    PlanAction::Rename(ref dest) => Some((&entry.source, dest)),
+    PlanAction::Skip(_) => None,
+  }).collect();
+  let renamed = || plan.iter().filter(|entry| matches!(entry.action, PlanAction::Rename(_)));
+  let modified = renamed().map(|entry| entry.modified);
+  let replaces: Vec<bool> = renamed().map(|entry| entry.replaces).collect();
+    // a file in the plan is never replaced, even if it is still there because it couldn't be moved away
+  let sources: HashSet<&PathBuf> = plan.iter().map(|entry| &entry.source).collect();
+
+    // the first pass, which leaves the files where they are if copying
+    // files that changed since they were planned are left alone, as their date may have changed as well
+  let moved: Vec<Result<PathBuf, RenameError>> = renames.iter().zip(modified).enumerate().map(|(index, (&(source, dest), modified))| {
+    ensure!(!source_changed(source, modified), SourceChanged);
+    if opts.copy {
+      Ok(source.clone())
+    } else {
+      with_retries(opts, || move_to_temporary(source, dest, index))
+    }
+  }).collect();
+
+  let abort = opts.on_error == OnError::Abort || opts.transactional;
+  let mut results = Vec::new();
+  let mut stopped = false;
+  for ((&(source, dest), moved), replaces) in renames.iter().zip(moved).zip(replaces) {
+    let current = match moved {
+      Ok(ref current) => current,
+      Err(_) if stopped => continue,
+      Err(error) => {
+        let result = RenameResult { source: source.clone(), dest: dest.clone(), outcome: Err(error) };
+        stopped = !on_rename(&result) || abort;
+        results.push(result);
+        continue;
+      },
+    };
+    if stopped {
+        // the files that weren't renamed go back to where they were, unless another file has their name now
+      if let Err(restore_error) = move_back(current, source) {
+        let outcome = Err(RenameError::NotMovedBack { temporary: current.clone(), restore_error: Box::new(restore_error), error: None });
+        results.push(RenameResult { source: source.clone(), dest: dest.clone(), outcome });
+      }
+      continue;
+    }
+
+      // the file in the way is only replaced if the plan chose to, and if that is still allowed, as it may have changed since the plan was made
+    let outcome = with_retries(opts, || {
+      if replaces && !sources.contains(dest) && fs_path(dest).exists() && replaceable(current, dest) {
+        fs::remove_file(fs_path(dest)).map_err(|error| rename_error(source, dest, error, |source| RenameError::RenameFailed { source }))?;
+      }
+      rename_file(current, dest, opts.copy, opts.verify)
+    });
+    let outcome = outcome.map_err(|error| match error {
+      RenameError::NotFoundAfterRename { .. } => error,
+      error => match move_back(current, source) {
+        Ok(()) => error,
+        Err(restore_error) => RenameError::NotMovedBack { temporary: current.clone(), restore_error: Box::new(restore_error), error: Some(Box::new(error)) },
+      },
+    });
+    let result = RenameResult { source: source.clone(), dest: dest.clone(), outcome };
+    stopped = !on_rename(&result) || (result.outcome.is_err() && abort);
+    results.push(result);
+  }
+
+  if opts.transactional && results.iter().any(|result| result.outcome.is_err()) {
+    roll_back(&mut results, opts.copy);
+  }
+  results
+}
+
+  // moves a file that wasn't renamed back from its temporary name, without replacing a file that has taken its name in the meantime
+  // copies never left their name, so they stay where they are
+fn move_back(temporary: &Path, source: &Path)->Result<(), RenameError> {
+  if temporary == source {
+    return Ok(());
+  }
+  rename_file(temporary, source, false, false)
+}
+
+  // whether the file is gone, or was modified after the given time, if there is one
+fn source_changed(source: &Path, modified: Option<SystemTime>)->bool {
+  match fs::metadata(fs_path(source)) {
+    Ok(metadata) => modified.is_some_and(|modified| metadata.modified().ok() != Some(modified)),
+    Err(_) => true,
+  }
+}
+
+  // where stash_files moves path, in the same folders below stash_dir as below input_dir
+pub fn stash_dest(path: &Path, input_dir: &Path, stash_dir: &Path)->PathBuf {
+  match path.strip_prefix(input_dir) {
+    Ok(relative) => stash_dir.join(relative),
+    Err(_) => stash_dir.join(path.file_name().unwrap_or_default()),
+  }
+}
+
+  // moves the paths into stash_dir, e.g. to set aside the files that aren't images. nothing is overwritten
+  // the results can be added to the journal like those of execute_plan, so that the moves can be undone
+pub fn stash_files(paths: &[PathBuf], input_dir: &Path, stash_dir: &Path)->Vec<RenameResult> {
+  paths.iter().map(|source| {
+    let dest = stash_dest(source, input_dir, stash_dir);
+    let outcome = rename_file(source, &dest, false, false);
+    RenameResult { source: source.clone(), dest, outcome }
+  }).collect()
+}
+
+  // undoes the successful renames in results, whose outcome becomes RolledBack, or removes the copies
+  // like in execute_plan, the files are moved to temporary names first, so that files that swapped names can get them back
+  // files that can't be moved back stay renamed, with their outcome left as it is
+fn roll_back(results: &mut [RenameResult], copy: bool) {
+  if copy {
+    for result in results.iter_mut().filter(|result| result.outcome.is_ok()) {
+      if fs::remove_file(fs_path(&result.dest)).is_ok() {
+        result.outcome = Err(RenameError::RolledBack);
+      }
+    }
+    return;
+  }
+
+  let moved: Vec<(usize, PathBuf)> = results.iter().enumerate()
+    .filter(|(_, result)| result.outcome.is_ok())
+    .filter_map(|(index, result)| move_to_temporary(&result.dest, &result.source, index).ok().map(|temporary| (index, temporary)))
+    .collect();
+  for (index, temporary) in moved {
+    let result = &mut results[index];
+    if rename_file(&temporary, &result.source, false, false).is_ok() {
+      result.outcome = Err(RenameError::RolledBack);
+    } else {
+      let _ = fs::rename(fs_path(&temporary), fs_path(&result.dest));
+    }
+  }
+}
+
+  // with OnError::Retry, tries again a few times after errors that may go away
+fn with_retries<T>(opts: &RenameOptions, mut attempt: impl FnMut()->Result<T, RenameError>)->Result<T, RenameError> {
+  let mut outcome = attempt();
+  if opts.on_error == OnError::Retry {
+    for _ in 1..RENAME_ATTEMPTS {
+      match outcome {
+          // the destination won't go away by itself, and a file that was renamed can't be renamed again
+        Err(ref error) if !matches!(*error, RenameError::DestinationExists { .. } | RenameError::NotFoundAfterRename { .. }) => {
+          std::thread::sleep(RETRY_DELAY);
+          outcome = attempt();
+        },
+        _ => break,
+      }
+    }
+  }
+  outcome
+}
+
+  // moves source to a temporary name in its directory, where it can't be in the way of any other file, and returns that name
+fn move_to_temporary(source: &Path, dest: &Path, index: usize)->Result<PathBuf, RenameError> {
+  let dir = source.parent().unwrap_or(Path::new(""));
+  let mut temporary = dir.join(format!(".datier-{}-{}.tmp", std::process::id(), index));
+  let mut attempt = 0;
+  while fs_path(&temporary).exists() {
+    attempt += 1;
+    temporary = dir.join(format!(".datier-{}-{}-{}.tmp", std::process::id(), index, attempt));
+  }
+  fs::rename(fs_path(source), fs_path(&temporary)).map_err(|error| rename_error(source, dest, error, |source| RenameError::RenameFailed { source }))?;
+  Ok(temporary)
+}
+
+  // how often a rename is tried with OnError::Retry, and how long to wait in between
+const RENAME_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+  // with verify, the new file is checked before the rename counts as done, see RenameOptions::verify
+fn rename_file(source: &Path, dest: &Path, copy: bool, verify: bool)->Result<(), RenameError> {
+    // fs::rename silently replaces existing files on some platforms, so make sure nothing is overwritten
+  ensure!(!fs_path(dest).exists(), DestinationExists { dest: dest.to_path_buf() });
+  if let Some(dir) = dest.parent() {
+    fs::create_dir_all(fs_path(dir)).context(CreateDirFailed { dir: dir.to_path_buf() })?;
+  }
+  if copy {
+    fs::copy(fs_path(source), fs_path(dest)).map_err(|error| rename_error(source, dest, error, |source| RenameError::CopyFailed { source }))?;
+    return if verify { verify_copy(source, dest) } else { Ok(()) };
+  }
+
+  match fs::rename(fs_path(source), fs_path(dest)) {
+    Ok(()) if verify && !fs_path(dest).is_file() => NotFoundAfterRename { dest }.fail(),
+    Ok(()) => Ok(()),
+      // files can't be moved to another drive or file system, so they are copied there instead
+    Err(ref error) if error.kind() == std::io::ErrorKind::CrossesDevices => {
+      fs::copy(fs_path(source), fs_path(dest)).map_err(|error| rename_error(source, dest, error, |source| RenameError::CopyFailed { source }))?;
+      if verify {
+        verify_copy(source, dest)?;
+      }
+      if let Err(error) = fs::remove_file(fs_path(source)) {
+        let _ = fs::remove_file(fs_path(dest)); // don't leave a second copy behind
+        return Err(rename_error(source, dest, error, |source| RenameError::RemoveAfterCopyFailed { source }));
+      }
+      Ok(())
+    },
+    Err(error) => Err(rename_error(source, dest, error, |source| RenameError::RenameFailed { source })),
+  }
+}
+
+  // compares the hashes of a copy and its original, and removes the copy if they differ, so that it can be tried again
+fn verify_copy(source: &Path, dest: &Path)->Result<(), RenameError> {
+  match (content_hash(source), content_hash(dest)) {
+    (Ok(source_hash), Ok(dest_hash)) if source_hash == dest_hash => Ok(()),
+    _ => {
+      let _ = fs::remove_file(fs_path(dest));
+      VerifyFailed { dest, reason: "the copy differs from the original" }.fail()
+    },
+  }
+}
+
+  // the path to hand to the file system, which on Windows needs a prefix if it is longer than the usual limit
+#[cfg(windows)]
+fn fs_path(path: &Path)->PathBuf {
+  crate::windows::long_path(path)
+}
+
+#[cfg(not(windows))]
+fn fs_path(path: &Path)->&Path {
+  path
+}
+
+  // permission errors get a message that says where the permissions are missing, all other errors are wrapped by other
+fn rename_error(source: &Path, dest: &Path, error: std::io::Error, other: impl FnOnce(std::io::Error)->RenameError)->RenameError {
+  if error.kind() != std::io::ErrorKind::PermissionDenied {
+    return other(error);
+  }
+  let dir_of = |path: &Path| path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+  RenameError::PermissionDenied { source_dir: dir_of(source), dest_dir: dir_of(dest) }
+}
@@ -0,0 +1,75 @@
+use snafu::{ResultExt, Snafu};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::datetime::OrdDateTime;
+
+const PNG_SIGNATURE: [u8; 8] = *b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Snafu)]
+pub enum PngReadError {
+  #[snafu(display("Could not read PNG file: {}", source))]
+  PngFileRead {
+    source: std::io::Error,
+  },
+  #[snafu(display("Not a PNG file"))]
+  NotPng,
+  #[snafu(display("No tIME chunk found"))]
+  NoTimeChunk,
+  #[snafu(display("The tIME chunk has an invalid date"))]
+  InvalidTimeChunk,
+}
+
+pub fn is_png(path: &Path)->bool {
+  path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+  // the time of the last change to the image stored in the tIME chunk of a PNG file, in seconds since 1970-01-01 UTC
+  // screenshots and other images without exif data often have one
+pub fn modification_time(path: &Path)->Result<i64, PngReadError> {
+  let mut file = BufReader::new(File::open(path).context(PngFileRead)?);
+  let mut signature = [0u8; 8];
+  file.read_exact(&mut signature).context(PngFileRead)?;
+  if signature != PNG_SIGNATURE {
+    return NotPng.fail();
+  }
+
+  loop {
+    let mut header = [0u8; 8];
+    match file.read_exact(&mut header) {
+      Err(ref error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return NoTimeChunk.fail(),
+      result => result.context(PngFileRead)?,
+    }
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    match &header[4..8] {
+      b"tIME" if len == 7 => {
+        let mut time = [0u8; 7];
+        file.read_exact(&mut time).context(PngFileRead)?;
+        return time_chunk_timestamp(&time);
+      },
+      b"IEND" => return NoTimeChunk.fail(),
+        // the data of other chunks is skipped along with their CRC
+      _ => file.seek(SeekFrom::Current(i64::from(len) + 4)).context(PngFileRead)?,
+    };
+  }
+}
+
+  // the year, month, day, hour, minute and second of a tIME chunk, which are always in UTC
+fn time_chunk_timestamp(time: &[u8; 7])->Result<i64, PngReadError> {
+  let datetime = exif::DateTime {
+    year: u16::from_be_bytes([time[0], time[1]]),
+    month: time[2],
+    day: time[3],
+    hour: time[4],
+    minute: time[5],
+    second: time[6],
+    nanosecond: None,
+    offset: Some(0),
+  };
+  let valid = (1..=12).contains(&datetime.month) && (1..=31).contains(&datetime.day) && datetime.hour < 24 && datetime.minute < 60 && datetime.second <= 60;
+  if !valid {
+    return InvalidTimeChunk.fail();
+  }
+  Ok(OrdDateTime::from(datetime).timestamp())
+}
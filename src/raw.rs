@@ -0,0 +1,80 @@
+use std::path::Path;
+
+  // extensions of the TIFF based RAW formats whose embedded JPEG previews are read when their own exif data has no date
+pub const PREVIEW_RAW_EXTENSIONS: [&str; 3] = ["cr2", "nef", "arw"];
+
+const JPEG_INTERCHANGE_FORMAT: u16 = 0x0201;
+const JPEG_INTERCHANGE_FORMAT_LENGTH: u16 = 0x0202;
+const STRIP_OFFSETS: u16 = 0x0111;
+const STRIP_BYTE_COUNTS: u16 = 0x0117;
+const SUB_IFDS: u16 = 0x014a;
+
+pub fn has_embedded_preview(path: &Path)->bool {
+  path.extension()
+    .map(|ext| ext.to_string_lossy().to_lowercase())
+    .is_some_and(|ext| PREVIEW_RAW_EXTENSIONS.contains(&ext.as_str()))
+}
+
+  // the JPEG previews embedded in a TIFF based RAW file, whose whole contents the exif reader keeps
+  // CR2 files have one in the strips of IFD0, ARW files in IFD0 or IFD1, and NEF files in their sub-IFDs
+pub fn embedded_previews(exif: &exif::Exif)->Vec<&[u8]> {
+  let buf = exif.buf();
+  let little_endian = match buf.get(..2) {
+    Some(b"II") => true,
+    Some(b"MM") => false,
+    _ => return Vec::new(), // the exif data came from a container, so the offsets wouldn't point into the file
+  };
+  let reader = IfdReader { buf, little_endian };
+
+  let mut ranges: Vec<(u32, u32)> = Vec::new();
+  for ifd in [exif::In::PRIMARY, exif::In::THUMBNAIL] {
+    let uint = |number: u16| exif.get_field(exif::Tag(exif::Context::Tiff, number), ifd).and_then(|field| field.value.get_uint(0));
+    ranges.extend(uint(JPEG_INTERCHANGE_FORMAT).zip(uint(JPEG_INTERCHANGE_FORMAT_LENGTH)));
+    ranges.extend(uint(STRIP_OFFSETS).zip(uint(STRIP_BYTE_COUNTS)));
+  }
+    // the exif crate doesn't read sub-IFDs, so their entries are looked up here
+  if let Some(field) = exif.get_field(exif::Tag(exif::Context::Tiff, SUB_IFDS), exif::In::PRIMARY) {
+    for offset in field.value.iter_uint().into_iter().flatten() {
+      let value = |number: u16| reader.entry_value(offset as usize, number);
+      ranges.extend(value(JPEG_INTERCHANGE_FORMAT).zip(value(JPEG_INTERCHANGE_FORMAT_LENGTH)));
+      ranges.extend(value(STRIP_OFFSETS).zip(value(STRIP_BYTE_COUNTS)));
+    }
+  }
+
+  ranges.into_iter()
+    .filter_map(|(start, len)| buf.get(start as usize..(start as usize).checked_add(len as usize)?))
+    .filter(|preview| preview.starts_with(&[0xFF, 0xD8]))
+    .collect()
+}
+
+struct IfdReader<'a> {
+  buf: &'a [u8],
+  little_endian: bool,
+}
+
+impl IfdReader<'_> {
+  fn u16_at(&self, pos: usize)->Option<u16> {
+    let bytes = [*self.buf.get(pos)?, *self.buf.get(pos + 1)?];
+    Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+  }
+
+  fn u32_at(&self, pos: usize)->Option<u32> {
+    let bytes = [*self.buf.get(pos)?, *self.buf.get(pos + 1)?, *self.buf.get(pos + 2)?, *self.buf.get(pos + 3)?];
+    Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+  }
+
+    // the value of the entry with the given tag in the IFD at ifd_offset, if it is a single SHORT or LONG
+  fn entry_value(&self, ifd_offset: usize, tag: u16)->Option<u32> {
+    let count = self.u16_at(ifd_offset)?;
+    (0..usize::from(count)).map(|index| ifd_offset + 2 + 12 * index).find_map(|entry| {
+      if self.u16_at(entry)? != tag || self.u32_at(entry + 4)? != 1 {
+        return None;
+      }
+      match self.u16_at(entry + 2)? {
+        3 => self.u16_at(entry + 8).map(u32::from),
+        4 => self.u32_at(entry + 8),
+        _ => None,
+      }
+    })
+  }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::plan::{planned_dirs, PlanAction, RenamePlan};
+
+  // the kind of script written by write_script
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptStyle {
+    // a POSIX shell script using mv or cp
+  Shell,
+    // a Windows batch file using move or copy
+  Batch,
+}
+
+impl ScriptStyle {
+    // a batch file for .bat and .cmd files, a shell script for .sh files, and whatever the system runs for everything else
+  pub fn for_path(path: &Path)->ScriptStyle {
+    let ext = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    match ext.as_deref() {
+      Some("bat") | Some("cmd") => ScriptStyle::Batch,
+      Some("sh") => ScriptStyle::Shell,
+      _ if cfg!(windows) => ScriptStyle::Batch,
+      _ => ScriptStyle::Shell,
+    }
+  }
+}
+
+  // writes a script that does the renames of the plan (or copies, if copy is set) when it is run, including creating the directories
+  // if a file is renamed to the old name of another one, all files are first moved to temporary names, like execute_plan does
+pub fn write_script(plan: &[RenamePlan], copy: bool, path: &Path, style: ScriptStyle)->std::io::Result<()> {
+  let renames: Vec<(&PathBuf, &PathBuf)> = plan.iter().filter_map(|entry| match entry.action {
+    PlanAction::Rename(ref dest) => Some((&entry.source, dest)),
+    PlanAction::Skip(_) => None,
+  }).collect();
+
+  let mut out = BufWriter::new(fs::File::create(path)?);
+  match style {
+    ScriptStyle::Shell => writeln!(out, "#!/bin/sh\n# written by datier\nset -e")?,
+    ScriptStyle::Batch => writeln!(out, "@echo off\nrem written by datier")?,
+  }
+  for dir in planned_dirs(plan) {
+    match style {
+      ScriptStyle::Shell => writeln!(out, "mkdir -p -- {}", shell_quote(&dir))?,
+      ScriptStyle::Batch => writeln!(out, "mkdir {}", batch_quote(&dir))?,
+    }
+  }
+
+  let sources: HashSet<&PathBuf> = renames.iter().map(|&(source, _)| source).collect();
+  let needs_temporary = !copy && renames.iter().any(|(_, dest)| sources.contains(dest));
+  let mut moves: Vec<(PathBuf, &PathBuf)> = Vec::new();
+  for (index, &(source, dest)) in renames.iter().enumerate() {
+    if needs_temporary {
+      let temporary = source.with_file_name(format!(".datier-script-{}.tmp", index));
+      write_command(&mut out, style, false, source, &temporary)?;
+      moves.push((temporary, dest));
+    } else {
+      moves.push((source.clone(), dest));
+    }
+  }
+  for (source, dest) in moves {
+    write_command(&mut out, style, copy, &source, dest)?;
+  }
+  out.flush()?;
+  drop(out);
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    if style == ScriptStyle::Shell {
+      fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+  }
+  Ok(())
+}
+
+fn write_command(out: &mut impl Write, style: ScriptStyle, copy: bool, source: &Path, dest: &Path)->std::io::Result<()> {
+  match style {
+    ScriptStyle::Shell => writeln!(out, "{} -- {} {}", if copy { "cp -p" } else { "mv" }, shell_quote(source), shell_quote(dest)),
+    ScriptStyle::Batch => writeln!(out, "{} {} {}", if copy { "copy" } else { "move" }, batch_quote(source), batch_quote(dest)),
+  }
+}
+
+  // single quotes keep everything as it is in sh, except single quotes themselves, which are written as '\''
+fn shell_quote(path: &Path)->String {
+  format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+  // Windows paths can't contain double quotes, but % would expand variables in a batch file
+fn batch_quote(path: &Path)->String {
+  format!("\"{}\"", path.to_string_lossy().replace('%', "%%"))
+}
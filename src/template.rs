@@ -0,0 +1,403 @@
+use snafu::Snafu;
+use std::borrow::Cow;
+
+use crate::locale::Locale;
+
+pub const DEFAULT_TEMPLATE: &str = "{year}_{month}_{day}-{n:4}";
+  // the largest width a placeholder can be padded to
+pub const MAX_FIELD_WIDTH: usize = 32;
+
+#[derive(Debug, Snafu)]
+pub enum TemplateParseError {
+  #[snafu(display("Unknown placeholder {{{}}}", name))]
+  UnknownPlaceholder {
+    name: String,
+  },
+  #[snafu(display("Invalid width \"{}\" for placeholder {{{}}}", width, name))]
+  InvalidWidth {
+    name: String,
+    width: String,
+  },
+  #[snafu(display("Placeholder is missing its closing }}"))]
+  UnclosedPlaceholder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TemplateField {
+  Year,
+  Month,
+  MonthName,
+  MonthAbbr,
+  Day,
+  Hour,
+  Minute,
+  Second,
+  Counter,
+  Place,
+  Make,
+  Model,
+  Lens,
+  Focal,
+  Subdir,
+  Parent,
+  Session,
+}
+
+impl TemplateField {
+  fn from_name(name: &str)->Option<TemplateField> {
+    match name {
+      "year" => Some(TemplateField::Year),
+      "month" => Some(TemplateField::Month),
+      "month_name" => Some(TemplateField::MonthName),
+      "month_abbr" => Some(TemplateField::MonthAbbr),
+      "day" => Some(TemplateField::Day),
+      "hour" => Some(TemplateField::Hour),
+      "minute" => Some(TemplateField::Minute),
+      "second" => Some(TemplateField::Second),
+      "n" => Some(TemplateField::Counter),
+      "place" => Some(TemplateField::Place),
+      "make" => Some(TemplateField::Make),
+      "model" => Some(TemplateField::Model),
+      "lens" => Some(TemplateField::Lens),
+      "focal" => Some(TemplateField::Focal),
+      "subdir" => Some(TemplateField::Subdir),
+      "parent" => Some(TemplateField::Parent),
+      "session" => Some(TemplateField::Session),
+      _ => None,
+    }
+  }
+
+    // the padding used when a placeholder doesn't specify a width
+  fn default_width(self)->usize {
+    match self {
+      TemplateField::Year => 4,
+      TemplateField::Counter | TemplateField::Session => 1,
+      _ => 2,
+    }
+  }
+
+    // fields that hold text are written as they are, so they can't have a width
+  fn is_text(self)->bool {
+    matches!(self, TemplateField::MonthName | TemplateField::MonthAbbr | TemplateField::Place | TemplateField::Make | TemplateField::Model | TemplateField::Lens | TemplateField::Focal | TemplateField::Subdir | TemplateField::Parent)
+  }
+}
+
+  // the values of the placeholders for one file that don't come from its date or counter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextValues {
+  pub place: String,
+  pub make: String,
+  pub model: String,
+  pub lens: String,
+    // the focal length, e.g. "50mm"
+  pub focal: String,
+    // the folder of the file relative to the input folder, with - between the folder names
+  pub subdir: String,
+    // the name of the folder the file is in, e.g. the event it shows, or "" for files directly in the input folder
+  pub parent: String,
+    // the number of the file's session, see RenameOptions::session_gap
+  pub session: u32,
+}
+
+  // the case that new file names are written in, e.g. for tools that need lowercase names
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NameCase {
+    // the template and the values of its placeholders are used as they are
+  #[default]
+  Preserve,
+  Lower,
+  Upper,
+}
+
+impl NameCase {
+  pub fn apply<'a>(self, name: &'a str)->Cow<'a, str> {
+    match self {
+      NameCase::Preserve => Cow::Borrowed(name),
+      NameCase::Lower => Cow::Owned(name.to_lowercase()),
+      NameCase::Upper => Cow::Owned(name.to_uppercase()),
+    }
+  }
+}
+
+impl std::str::FromStr for NameCase {
+  type Err = String;
+
+  fn from_str(s: &str)->Result<NameCase, String> {
+    match s.to_lowercase().as_str() {
+      "preserve" => Ok(NameCase::Preserve),
+      "lower" => Ok(NameCase::Lower),
+      "upper" => Ok(NameCase::Upper),
+      _ => Err(format!("Unknown case \"{}\" (expected lower, upper or preserve)", s)),
+    }
+  }
+}
+
+#[derive(Debug)]
+enum TemplatePiece {
+  Literal(String),
+  Field(TemplateField, usize),
+}
+
+  // a parsed file name template, e.g. "{year}_{month}_{day}-{n:4}"
+  // "{{" and "}}" can be used to produce literal braces
+#[derive(Debug)]
+pub struct Template {
+  pieces: Vec<TemplatePiece>,
+    // the language of {month_name} and {month_abbr}
+  locale: Locale,
+    // pad {n} only to the number of digits the largest counter of its group needs, instead of to its width
+  auto_width: bool,
+    // the case of the expanded names, including the -a, -b, ... suffixes of tied files
+  case: NameCase,
+}
+
+impl Default for Template {
+  fn default()->Self {
+    Template::parse(DEFAULT_TEMPLATE).expect("the default template is valid")
+  }
+}
+
+impl Template {
+  pub fn parse(template: &str)->Result<Template, TemplateParseError> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+      match c {
+        '{' if chars.peek() == Some(&'{') => {
+          chars.next();
+          literal.push('{');
+        },
+        '}' if chars.peek() == Some(&'}') => {
+          chars.next();
+          literal.push('}');
+        },
+        '{' => {
+          let mut placeholder = String::new();
+          loop {
+            match chars.next() {
+              Some('}') => break,
+              Some(c) => placeholder.push(c),
+              None => return UnclosedPlaceholder.fail(),
+            }
+          }
+
+          let (name, width) = match placeholder.find(':') {
+            Some(idx) => (&placeholder[..idx], Some(&placeholder[idx + 1..])),
+            None => (&placeholder[..], None),
+          };
+
+          let field = TemplateField::from_name(name).ok_or_else(|| TemplateParseError::UnknownPlaceholder { name: name.to_string() })?;
+          let width = match width {
+            Some(width) if field.is_text() => return InvalidWidth { name, width }.fail(),
+            Some(width) => width.parse::<usize>().ok().filter(|&w| w > 0 && w <= MAX_FIELD_WIDTH)
+              .ok_or_else(|| TemplateParseError::InvalidWidth { name: name.to_string(), width: width.to_string() })?,
+            None => field.default_width(),
+          };
+
+          if !literal.is_empty() {
+            pieces.push(TemplatePiece::Literal(std::mem::take(&mut literal)));
+          }
+          pieces.push(TemplatePiece::Field(field, width));
+        },
+        c => literal.push(c),
+      }
+    }
+
+    if !literal.is_empty() {
+      pieces.push(TemplatePiece::Literal(literal));
+    }
+
+    Ok(Template { pieces, locale: Locale::default(), auto_width: false, case: NameCase::Preserve })
+  }
+
+  fn has_field(&self, field: TemplateField)->bool {
+    self.pieces.iter().any(|piece| matches!(*piece, TemplatePiece::Field(piece_field, _) if piece_field == field))
+  }
+
+  pub fn has_counter(&self)->bool {
+    self.has_field(TemplateField::Counter)
+  }
+
+  pub fn has_place(&self)->bool {
+    self.has_field(TemplateField::Place)
+  }
+
+    // pads all {n} placeholders to the given width, regardless of what the template says
+  pub fn set_counter_width(&mut self, width: usize) {
+    for piece in &mut self.pieces {
+      if let TemplatePiece::Field(TemplateField::Counter, ref mut counter_width) = *piece {
+        *counter_width = width;
+      }
+    }
+  }
+
+  pub fn set_locale(&mut self, locale: Locale) {
+    self.locale = locale;
+  }
+
+  pub fn set_auto_width(&mut self, auto_width: bool) {
+    self.auto_width = auto_width;
+  }
+
+  pub fn set_case(&mut self, case: NameCase) {
+    self.case = case;
+  }
+
+  pub fn case(&self)->NameCase {
+    self.case
+  }
+
+    // puts prefix before and suffix after every name, e.g. "Wedding_" for all photos of an event
+    // like text values, they can't contain characters that can't be part of a file name
+  pub fn add_affixes(&mut self, prefix: &str, suffix: &str) {
+    let (prefix, suffix) = (sanitize(prefix), sanitize(suffix));
+    if !prefix.is_empty() {
+      self.pieces.insert(0, TemplatePiece::Literal(prefix));
+    }
+    if !suffix.is_empty() {
+      self.pieces.push(TemplatePiece::Literal(suffix));
+    }
+  }
+
+    // whether stem is a name this template could have produced for a file with the given date and text values
+    // any counter is accepted, as well as the -a, -b, ... suffixes of tied files and the -2, -3, ... suffixes added on collisions
+  pub fn matches(&self, stem: &str, datetime: &exif::DateTime, text: &TextValues)->bool {
+    self.match_pieces(&self.pieces, stem, datetime, text).is_some()
+  }
+
+    // the counter in stem, if it is a name this template could have produced for a file with the given date and text values (see matches)
+  pub fn counter_of(&self, stem: &str, datetime: &exif::DateTime, text: &TextValues)->Option<u32> {
+    self.match_pieces(&self.pieces, stem, datetime, text).flatten()
+  }
+
+    // None if rest doesn't match the pieces, and otherwise the value of the first counter in it, if there is one
+  fn match_pieces(&self, pieces: &[TemplatePiece], rest: &str, datetime: &exif::DateTime, text: &TextValues)->Option<Option<u32>> {
+    let (piece, remaining) = match pieces.split_first() {
+      Some(split) => split,
+      None => return if is_name_suffix(rest, self.case) { Some(None) } else { None },
+    };
+    let expected = match *piece {
+      TemplatePiece::Literal(ref literal) => self.case.apply(literal),
+      TemplatePiece::Field(TemplateField::Counter, width) => {
+        let width = if self.auto_width { 1 } else { width };
+          // the counter can have any number of digits, so every length is tried
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        return (width..=digits).rev().find_map(|len| {
+          self.match_pieces(remaining, &rest[len..], datetime, text).map(|_| rest[..len].parse::<u32>().ok())
+        });
+      },
+        // like counters, sessions change when files are added, so any number is accepted
+      TemplatePiece::Field(TemplateField::Session, width) => {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        return (width..=digits).rev().find_map(|len| self.match_pieces(remaining, &rest[len..], datetime, text));
+      },
+      TemplatePiece::Field(field, width) => Cow::Owned(self.case.apply(&self.expand_field(field, width, datetime, 0, text)).into_owned()),
+    };
+    self.match_pieces(remaining, rest.strip_prefix(expected.as_ref())?, datetime, text)
+  }
+
+    // values wider than their placeholder's width are written out in full, not cut off
+    // characters in text values that can't be part of a file name are replaced by _
+  pub fn expand(&self, datetime: &exif::DateTime, counter: u32, text: &TextValues)->String {
+    self.expand_in_group(datetime, counter, counter, text)
+  }
+
+    // like expand, but with auto_width, the counter is padded to the number of digits of largest_counter, the largest one in its group
+  pub fn expand_in_group(&self, datetime: &exif::DateTime, counter: u32, largest_counter: u32, text: &TextValues)->String {
+    let mut expanded = String::new();
+    self.expand_into(&mut expanded, datetime, counter, largest_counter, text);
+    expanded
+  }
+
+    // like expand_in_group, but replaces the contents of out, so that the same buffer can be used for many names
+  pub fn expand_into(&self, out: &mut String, datetime: &exif::DateTime, counter: u32, largest_counter: u32, text: &TextValues) {
+    out.clear();
+    for piece in &self.pieces {
+      match *piece {
+        TemplatePiece::Literal(ref literal) => out.push_str(literal),
+        TemplatePiece::Field(TemplateField::Counter, _) if self.auto_width => {
+          let width = digits(largest_counter.max(counter));
+          write_number(out, counter, width);
+        },
+        TemplatePiece::Field(field, width) => self.write_field(out, field, width, datetime, counter, text),
+      }
+    }
+    if let Cow::Owned(cased) = self.case.apply(out) {
+      *out = cased;
+    }
+  }
+
+  fn expand_field(&self, field: TemplateField, width: usize, datetime: &exif::DateTime, counter: u32, text: &TextValues)->String {
+    let mut expanded = String::new();
+    self.write_field(&mut expanded, field, width, datetime, counter, text);
+    expanded
+  }
+
+  fn write_field(&self, out: &mut String, field: TemplateField, width: usize, datetime: &exif::DateTime, counter: u32, text: &TextValues) {
+    let value = match field {
+      TemplateField::Year => u32::from(datetime.year),
+      TemplateField::Month => u32::from(datetime.month),
+      TemplateField::MonthName => return out.push_str(self.locale.month_name(datetime.month)),
+      TemplateField::MonthAbbr => return out.push_str(self.locale.month_abbr(datetime.month)),
+      TemplateField::Day => u32::from(datetime.day),
+      TemplateField::Hour => u32::from(datetime.hour),
+      TemplateField::Minute => u32::from(datetime.minute),
+      TemplateField::Second => u32::from(datetime.second),
+      TemplateField::Counter => counter,
+      TemplateField::Session => text.session,
+      TemplateField::Place => return push_sanitized(out, &text.place),
+      TemplateField::Make => return push_sanitized(out, &text.make),
+      TemplateField::Model => return push_sanitized(out, &text.model),
+      TemplateField::Lens => return push_sanitized(out, &text.lens),
+      TemplateField::Focal => return push_sanitized(out, &text.focal),
+      TemplateField::Subdir => return push_sanitized(out, &text.subdir),
+      TemplateField::Parent => return push_sanitized(out, &text.parent),
+    };
+    write_number(out, value, width);
+  }
+}
+
+  // value zero-padded to width, without going through a temporary string
+fn write_number(out: &mut String, value: u32, width: usize) {
+  use std::fmt::Write;
+  write!(out, "{:0width$}", value, width = width).expect("writing to a String can't fail");
+}
+
+fn digits(mut value: u32)->usize {
+  let mut digits = 1;
+  while value >= 10 {
+    value /= 10;
+    digits += 1;
+  }
+  digits
+}
+
+  // "", or the tie and collision suffixes that can follow an expanded template, e.g. "-b" or "-a-2"
+fn is_name_suffix(rest: &str, case: NameCase)->bool {
+  let mut parts = rest.split('-');
+  if parts.next() != Some("") {
+    return false;
+  }
+  let parts: Vec<&str> = parts.collect();
+  let is_letters = |part: &str| !part.is_empty() && part.chars().all(|c| if case == NameCase::Upper { c.is_ascii_uppercase() } else { c.is_ascii_lowercase() });
+  let is_number = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+  match parts.as_slice() {
+    [] => true,
+    [part] => is_letters(part) || is_number(part),
+    [letters, number] => is_letters(letters) && is_number(number),
+    _ => false,
+  }
+}
+
+  // replaces path separators, characters that Windows doesn't allow in file names, and control characters
+fn sanitize(value: &str)->String {
+  let mut sanitized = String::with_capacity(value.len());
+  push_sanitized(&mut sanitized, value);
+  sanitized
+}
+
+fn push_sanitized(out: &mut String, value: &str) {
+  out.extend(value.trim().chars().map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c }));
+}
@@ -0,0 +1,87 @@
+use snafu::{OptionExt, ResultExt, Snafu};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+  // extensions of the QuickTime based containers, whose dates are read from the movie header instead of exif data
+pub const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "mov", "m4v", "3gp"];
+
+  // seconds between 1904-01-01, the epoch of QuickTime timestamps, and 1970-01-01
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+#[derive(Debug, Snafu)]
+pub enum VideoReadError {
+  #[snafu(display("Could not read video file: {}", source))]
+  VideoFileRead {
+    source: std::io::Error,
+  },
+  #[snafu(display("No movie header found"))]
+  NoMovieHeader,
+  #[snafu(display("Movie header has no creation time"))]
+  NoCreationTime,
+}
+
+pub fn is_video(path: &Path)->bool {
+  path.extension()
+    .map(|ext| ext.to_string_lossy().to_lowercase())
+    .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.as_str()))
+}
+
+  // the creation time stored in the mvhd box of an mp4 or mov file, in seconds since 1970-01-01 UTC
+pub fn creation_time(path: &Path)->Result<i64, VideoReadError> {
+  let mut file = File::open(path).context(VideoFileRead)?;
+  let len = file.metadata().context(VideoFileRead)?.len();
+
+  let (moov_start, moov_end) = find_box(&mut file, 0, len, b"moov").context(VideoFileRead)?.context(NoMovieHeader)?;
+  let (mvhd_start, _) = find_box(&mut file, moov_start, moov_end, b"mvhd").context(VideoFileRead)?.context(NoMovieHeader)?;
+
+  file.seek(SeekFrom::Start(mvhd_start)).context(VideoFileRead)?;
+  let mut version_flags = [0u8; 4];
+  file.read_exact(&mut version_flags).context(VideoFileRead)?;
+  let creation_time = if version_flags[0] == 1 {
+    let mut time = [0u8; 8];
+    file.read_exact(&mut time).context(VideoFileRead)?;
+    u64::from_be_bytes(time)
+  } else {
+    let mut time = [0u8; 4];
+    file.read_exact(&mut time).context(VideoFileRead)?;
+    u64::from(u32::from_be_bytes(time))
+  };
+
+    // many devices leave the field at 0 instead of setting a date
+  if creation_time == 0 {
+    return NoCreationTime.fail();
+  }
+  Ok(creation_time as i64 - QUICKTIME_EPOCH_OFFSET)
+}
+
+  // looks for a box of the given type between start and end, returning where its contents start and end
+  // the contents of other boxes are skipped without reading them, as they can be very large
+fn find_box(file: &mut File, start: u64, end: u64, name: &[u8; 4])->std::io::Result<Option<(u64, u64)>> {
+  let mut pos = start;
+  while pos + 8 <= end {
+    file.seek(SeekFrom::Start(pos))?;
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)?;
+
+    let mut header_len = 8;
+    let mut size = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+    if size == 1 {
+      let mut large_size = [0u8; 8];
+      file.read_exact(&mut large_size)?;
+      size = u64::from_be_bytes(large_size);
+      header_len = 16;
+    } else if size == 0 {
+      size = end - pos; // the box extends to the end
+    }
+    if size < header_len || pos + size > end {
+      return Ok(None); // malformed, so nothing after this can be trusted
+    }
+
+    if &header[4..8] == name {
+      return Ok(Some((pos + header_len, pos + size)));
+    }
+    pos += size;
+  }
+  Ok(None)
+}
@@ -0,0 +1,200 @@
+use datier::{exclude_paths, execute_plan_with_progress, find_files, plan_renames, Journal, PlanAction, RenameError, RenameOptions, RenamePlan, RenameResult};
+use notify::{EventKind, RecursiveMode, Watcher};
+use snafu::{ResultExt, Snafu};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::logger::Logger;
+
+  // how often the new files are checked while no events come in
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+  // how long the size of a new file has to stay the same before it is considered completely written
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Snafu)]
+pub enum WatchError {
+  #[snafu(display("Could not watch {}: {}", dir.display(), source))]
+  WatchFailed {
+    dir: PathBuf,
+    source: notify::Error,
+  },
+  #[snafu(display("Could not set up a Ctrl-C handler: {}", source))]
+  HandlerFailed {
+    source: ctrlc::Error,
+  },
+}
+
+  // which files in the watched folder are renamed, which is the same as for the initial run
+pub struct WatchedFiles<'a> {
+  pub dir: &'a Path,
+  pub extensions: &'a [String],
+  pub deep: bool,
+  pub max_depth: Option<usize>,
+  pub follow_symlinks: bool,
+  pub exclude: &'a [glob::Pattern],
+  pub include_hidden: bool,
+}
+
+  // the command line options that also apply to the files renamed while watching
+pub struct WatchActions<'a> {
+  pub copy: bool,
+  pub exec: Option<&'a str>,
+  pub strict: bool,
+  pub keep_original_name: bool,
+}
+
+  // renames new files as they appear in the folder, until Ctrl-C is pressed
+  // each batch of new files is planned together with the files already in the folder, so that the counters continue where they left off
+  // returns whether any file failed
+pub fn watch(files: &WatchedFiles, actions: &WatchActions, opts: &RenameOptions, journal: &mut Journal, l: &Logger)->Result<bool, WatchError> {
+  let stop = Arc::new(AtomicBool::new(false));
+  let handler_stop = Arc::clone(&stop);
+  ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst)).context(HandlerFailed)?;
+
+  let (sender, events) = channel();
+  let mut watcher = notify::recommended_watcher(sender).context(WatchFailed { dir: files.dir.to_path_buf() })?;
+  let mode = if files.deep { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+  watcher.watch(files.dir, mode).context(WatchFailed { dir: files.dir.to_path_buf() })?;
+  l.info(format_args!("Watching {} for new files, press Ctrl-C to stop", files.dir.display()));
+
+    // the size each new file had when it was last checked, and since when it has had that size
+  let mut pending: HashMap<PathBuf, (u64, Instant)> = HashMap::new();
+    // the files datier created itself, which show up as new files as well
+  let mut created: HashSet<PathBuf> = HashSet::new();
+  let mut failed = false;
+  while !stop.load(Ordering::SeqCst) {
+    match events.recv_timeout(POLL_INTERVAL) {
+      Ok(Ok(event)) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+        for path in event.paths {
+          if !created.contains(&path) && has_extension(&path, files.extensions) {
+            pending.entry(path).or_insert((u64::MAX, Instant::now()));
+          }
+        }
+      },
+      Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => {},
+      Ok(Err(error)) => l.warn(format_args!("While watching {}: {}", files.dir.display(), error)),
+      Err(RecvTimeoutError::Disconnected) => break,
+    }
+
+    let ready = settled_files(&mut pending);
+    if ready.is_empty() {
+      continue;
+    }
+    let (results, exec_failed) = rename_new_files(&ready, files, actions, opts, &stop, l);
+    failed |= exec_failed || results.iter().any(|result| result.outcome.is_err());
+    crate::warn_not_moved_back(&results, l);
+    if actions.keep_original_name {
+      failed |= crate::keep_original_names(&results, actions.copy, l);
+    }
+    let renamed: Vec<&RenameResult> = results.iter().filter(|result| result.outcome.is_ok()).collect();
+    created.extend(renamed.iter().map(|result| result.dest.clone()));
+    if actions.copy || renamed.is_empty() {
+      continue;
+    }
+    journal.add_results(files.dir, &results);
+    if let Err(error) = journal.write(files.dir) {
+      l.error(format_args!("{}", error));
+      failed = true;
+    }
+    if let Some(ref cache) = opts.cache {
+      for result in renamed {
+        cache.renamed(&result.source, &result.dest);
+      }
+    }
+  }
+
+  if let Some(ref cache) = opts.cache {
+    if let Err(error) = cache.save() {
+      l.warn(format_args!("{}", error));
+    }
+  }
+  Ok(failed)
+}
+
+  // the case doesn't matter, like when searching for files
+fn has_extension(path: &Path, extensions: &[String])->bool {
+  path.extension().is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase()))
+}
+
+  // takes the files whose size hasn't changed for SETTLE_TIME out of pending
+  // files that are gone, e.g. because they were only temporary, are dropped
+fn settled_files(pending: &mut HashMap<PathBuf, (u64, Instant)>)->HashSet<PathBuf> {
+  let now = Instant::now();
+  let mut ready = HashSet::new();
+  pending.retain(|path, (size, since)| {
+    let current_size = match fs::metadata(path) {
+      Ok(metadata) if metadata.is_file() => metadata.len(),
+      _ => return false,
+    };
+    if current_size != *size {
+      *size = current_size;
+      *since = now;
+      true
+    } else if now.duration_since(*since) >= SETTLE_TIME {
+      ready.insert(path.clone());
+      false
+    } else {
+      true
+    }
+  });
+  ready
+}
+
+  // plans the whole folder again, but only renames the new files and their sidecars
+  // also returns whether an --exec command failed
+fn rename_new_files(new_files: &HashSet<PathBuf>, files: &WatchedFiles, actions: &WatchActions, opts: &RenameOptions, stop: &AtomicBool, l: &Logger)->(Vec<RenameResult>, bool) {
+  let found = match find_files(files.dir, files.extensions, files.deep, files.max_depth, files.follow_symlinks) {
+    Ok(found) => found,
+    Err(error) => {
+      l.error(format_args!("Could not read glob pattern: {}", error));
+      return (Vec::new(), true);
+    }
+  };
+  let (paths, _) = exclude_paths(found.paths, files.dir, files.exclude);
+  let paths = match crate::skip_hidden_and_ignored(paths, files.dir, files.include_hidden, l) {
+    Ok(paths) => paths,
+    Err(error) => {
+      l.error(format_args!("{}", error));
+      return (Vec::new(), true);
+    }
+  };
+  let is_new = |path: &Path| new_files.contains(path);
+  let plan: Vec<RenamePlan> = plan_renames(&paths, opts).into_iter()
+    .filter(|entry| is_new(&entry.source) || entry.sidecar_of.as_deref().is_some_and(is_new))
+    .collect();
+
+  let mut exec_failed = false;
+  let results = execute_plan_with_progress(&plan, opts, &mut |result| {
+    if let (Some(command), Ok(())) = (actions.exec, &result.outcome) {
+      if let Err(error) = crate::run_exec(command, &result.dest, l.is_json()) {
+        l.error(format_args!("Command for {} {}", result.dest.display(), error));
+        exec_failed = true;
+        if actions.strict {
+          return false;
+        }
+      }
+    }
+      // Ctrl-C lets the current rename finish, but stops the remaining ones
+    !stop.load(Ordering::SeqCst)
+  });
+
+  let outcomes: HashMap<&Path, &Result<(), RenameError>> = results.iter().map(|result| (result.source.as_path(), &result.outcome)).collect();
+  for entry in &plan {
+    let datetime = entry.datetime.as_ref();
+    match entry.action {
+      PlanAction::Skip(ref reason) => l.skipped(&entry.source, reason, datetime),
+      PlanAction::Rename(ref dest) => match outcomes.get(entry.source.as_path()) {
+        Some(Err(error)) => l.failed(&entry.source, dest, error, datetime),
+        None => l.skipped(&entry.source, &"Not renamed, as the run was stopped", datetime),
+        Some(Ok(())) if actions.copy => l.copied(&entry.source, dest, datetime),
+        Some(Ok(())) => l.renamed(&entry.source, dest, datetime),
+      },
+    }
+  }
+  (results, exec_failed)
+}
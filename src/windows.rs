@@ -0,0 +1,50 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+  // names Windows reserves for devices, which it won't create files under, whatever their extension
+const RESERVED_NAMES: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL",
+  "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+  "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+  // paths this long need the \\?\ prefix to be used at all
+const MAX_PATH: usize = 260;
+
+  // appends _ to a file stem that Windows would read as a device name, e.g. "CON" or "nul.backup"
+  // the part before the first dot is what counts, ignoring case and trailing spaces
+pub fn safe_stem(mut stem: String)->String {
+  let base_len = stem.find('.').unwrap_or(stem.len());
+  let base = stem[..base_len].trim_end_matches(' ');
+  if RESERVED_NAMES.iter().any(|name| name.eq_ignore_ascii_case(base)) {
+    let end = base.len();
+    stem.insert(end, '_');
+  }
+  stem
+}
+
+  // the path with the \\?\ extended-length prefix if it is too long for the usual Windows functions
+  // the prefix turns off the normalization of the path, so it is made absolute first
+pub fn long_path(path: &Path)->PathBuf {
+  if path.as_os_str().len() < MAX_PATH {
+    return path.to_path_buf();
+  }
+  let absolute = match std::path::absolute(path) {
+    Ok(absolute) => absolute,
+    Err(_) => return path.to_path_buf(),
+  };
+  let absolute_str = absolute.as_os_str().to_string_lossy();
+  if absolute_str.starts_with(r"\\?\") {
+    return absolute;
+  }
+  let mut prefixed = OsString::from(r"\\?\");
+  match absolute_str.strip_prefix(r"\\") {
+      // network paths, e.g. \\server\share\..., use \\?\UNC\server\share\...
+    Some(unc) => {
+      prefixed.push(r"UNC\");
+      prefixed.push(unc);
+    },
+    None => prefixed.push(absolute.as_os_str()),
+  }
+  PathBuf::from(prefixed)
+}
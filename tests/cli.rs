@@ -0,0 +1,140 @@
+mod common;
+
+use common::{write_jpeg, Exif};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+  // datier run on dir with the given options, with config_home as the user's config folder
+fn datier(dir: &Path, args: &[&str], config_home: &Path)->Output {
+  Command::new(env!("CARGO_BIN_EXE_datier")).arg(dir).args(args).arg("--no-cache")
+    .env("XDG_CONFIG_HOME", config_home).output().unwrap()
+}
+
+fn run(dir: &Path, args: &[&str], config_home: &Path)->Option<i32> {
+  datier(dir, args, config_home).status.code()
+}
+
+  // the exit code of datier run without a config in the user's config folder
+fn exit_code(dir: &Path, args: &[&str])->Option<i32> {
+  let config_home = tempfile::tempdir().unwrap();
+  run(dir, args, config_home.path())
+}
+
+  // the output of datier run on dir with its cache kept in cache_home
+fn cached_stdout(dir: &Path, args: &[&str], cache_home: &Path)->String {
+  let config_home = tempfile::tempdir().unwrap();
+  let output = Command::new(env!("CARGO_BIN_EXE_datier")).arg(dir).args(args)
+    .env("XDG_CONFIG_HOME", config_home.path()).env("XDG_CACHE_HOME", cache_home).output().unwrap();
+  String::from_utf8(output.stdout).unwrap()
+}
+
+fn stdout(dir: &Path, args: &[&str])->String {
+  let config_home = tempfile::tempdir().unwrap();
+  String::from_utf8(datier(dir, args, config_home.path()).stdout).unwrap()
+}
+
+#[test]
+fn runs_without_usable_dates_exit_with_4_in_every_mode() {
+  let dir = tempfile::tempdir().unwrap();
+  write_jpeg(dir.path(), "undated.jpg", &Exif::new());
+
+  for mode in ["--dry-run", "--explain", "--print-plan"] {
+    assert_eq!(exit_code(dir.path(), &[mode]), Some(4), "{}", mode);
+  }
+  assert!(dir.path().join("undated.jpg").exists());
+}
+
+#[test]
+fn a_config_in_the_input_folder_cannot_run_commands() {
+  let dir = tempfile::tempdir().unwrap();
+  let elsewhere = tempfile::tempdir().unwrap();
+  let marker = elsewhere.path().join("hooked");
+  write_jpeg(dir.path(), "photo.jpg", &Exif::new().datetime("2021:03:15 14:30:00"));
+  let config = format!("exec = \"touch {}\"\n", marker.display());
+  fs::write(dir.path().join("datier.toml"), &config).unwrap();
+
+  assert_eq!(exit_code(dir.path(), &[]), Some(1));
+  assert!(!marker.exists());
+  assert!(dir.path().join("photo.jpg").exists());
+
+    // the same key is fine in the user's own config
+  fs::remove_file(dir.path().join("datier.toml")).unwrap();
+  let config_home = tempfile::tempdir().unwrap();
+  fs::create_dir(config_home.path().join("datier")).unwrap();
+  fs::write(config_home.path().join("datier").join("datier.toml"), &config).unwrap();
+  assert_eq!(run(dir.path(), &[], config_home.path()), Some(0));
+  assert!(marker.exists());
+}
+
+#[test]
+fn a_config_in_the_input_folder_cannot_write_or_remove_files_elsewhere() {
+  let dir = tempfile::tempdir().unwrap();
+  write_jpeg(dir.path(), "photo.jpg", &Exif::new().datetime("2021:03:15 14:30:00"));
+
+  for config in ["report = \"/tmp/report.csv\"", "dedupe = \"delete\"", "overwrite = true"] {
+    fs::write(dir.path().join("datier.toml"), config).unwrap();
+    assert_eq!(exit_code(dir.path(), &["--dry-run"]), Some(1), "{}", config);
+  }
+  fs::write(dir.path().join("datier.toml"), "dedupe = \"report\"").unwrap();
+  assert_eq!(exit_code(dir.path(), &["--dry-run"]), Some(0));
+}
+
+#[test]
+fn broken_config_files_stop_the_run() {
+  let dir = tempfile::tempdir().unwrap();
+  write_jpeg(dir.path(), "photo.jpg", &Exif::new().datetime("2021:03:15 14:30:00"));
+
+  for config in ["deep = ", "recursive = true", "group-by = \"fortnight\"", "assume-offset = \"+25:00\"", "counter-width = 0"] {
+    fs::write(dir.path().join("datier.toml"), config).unwrap();
+    assert_eq!(exit_code(dir.path(), &[]), Some(1), "{}", config);
+    assert!(dir.path().join("photo.jpg").exists(), "{}", config);
+  }
+  assert_eq!(exit_code(dir.path(), &["--no-config", "--dry-run"]), Some(0));
+}
+
+#[test]
+fn the_command_line_beats_the_config() {
+  let dir = tempfile::tempdir().unwrap();
+  write_jpeg(dir.path(), "photo.jpg", &Exif::new().datetime("2021:03:15 14:30:00"));
+  fs::write(dir.path().join("datier.toml"), "ext = [\"png\"]\nformat = \"{year}\"\nquiet = true\ndry-run = true\n").unwrap();
+
+    // values
+  assert_eq!(exit_code(dir.path(), &[]), Some(4));
+  assert!(stdout(dir.path(), &["--ext", "jpg", "-l"]).contains("2021.jpg"));
+  assert!(stdout(dir.path(), &["--ext", "jpg", "-l", "--format", "{month}"]).contains("03.jpg"));
+
+    // flags
+  assert_eq!(stdout(dir.path(), &["--ext", "jpg"]), "");
+  assert_eq!(exit_code(dir.path(), &["--ext", "jpg"]), Some(0));
+  assert!(dir.path().join("photo.jpg").exists());
+  assert_eq!(exit_code(dir.path(), &["--ext", "jpg", "--no-dry-run", "--dry-run"]), Some(0));
+  assert!(dir.path().join("photo.jpg").exists());
+  assert_eq!(exit_code(dir.path(), &["--ext", "jpg", "--no-dry-run"]), Some(0));
+  assert!(dir.path().join("2021.jpg").exists());
+
+    // the config is ignored altogether
+  assert!(stdout(dir.path(), &["--no-config", "--dry-run", "-l"]).contains("2021_03_15-0001.jpg"));
+}
+
+#[test]
+fn clearing_the_cache_reads_all_files_again() {
+  let dir = tempfile::tempdir().unwrap();
+  let cache_home = tempfile::tempdir().unwrap();
+  let path = write_jpeg(dir.path(), "photo.jpg", &Exif::new().datetime("2021:03:15 14:30:00"));
+  let modified = fs::metadata(&path).unwrap().modified().unwrap();
+  let args = ["--dry-run", "-l"];
+  assert!(cached_stdout(dir.path(), &args, cache_home.path()).contains("2021_03_15-0001.jpg"));
+  let cache_files = || fs::read_dir(cache_home.path().join("datier")).map_or(0, |entries| entries.count());
+  assert_eq!(cache_files(), 1);
+
+    // a change the cache can't notice, as the size and modification time stay the same
+  write_jpeg(dir.path(), "photo.jpg", &Exif::new().datetime("2022:03:15 14:30:00"));
+  fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+  assert!(cached_stdout(dir.path(), &args, cache_home.path()).contains("2021_03_15-0001.jpg"));
+  assert!(cached_stdout(dir.path(), &["--dry-run", "-l", "--clear-cache"], cache_home.path()).contains("2022_03_15-0001.jpg"));
+  assert!(cached_stdout(dir.path(), &args, cache_home.path()).contains("2022_03_15-0001.jpg"));
+
+  cached_stdout(dir.path(), &["--dry-run", "--clear-cache", "--no-cache"], cache_home.path());
+  assert_eq!(cache_files(), 0);
+}
@@ -0,0 +1,270 @@
+// helpers to build small image files with known exif data
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+  // the value of an exif field in a test image
+pub enum Value {
+  Ascii(String),
+  Rational(Vec<(u32, u32)>),
+  Byte(Vec<u8>),
+  Long(Vec<u32>),
+}
+
+impl Value {
+    // (type, count, bytes) of the value as stored in an IFD entry
+  fn encode(&self)->(u16, u32, Vec<u8>) {
+    match self {
+      Value::Ascii(text) => {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.push(0);
+        (2, bytes.len() as u32, bytes)
+      },
+      Value::Rational(values) => {
+        let bytes = values.iter().flat_map(|&(num, denom)| [num.to_be_bytes(), denom.to_be_bytes()].concat()).collect();
+        (5, values.len() as u32, bytes)
+      },
+      Value::Byte(bytes) => (1, bytes.len() as u32, bytes.clone()),
+      Value::Long(values) => (4, values.len() as u32, values.iter().flat_map(|value| value.to_be_bytes()).collect()),
+    }
+  }
+}
+
+  // (tag, (type, count, bytes)) of an IFD entry
+type IfdEntry = (u16, (u16, u32, Vec<u8>));
+
+  // the exif fields of a test image, as (tag, value) pairs
+  // fields of the primary TIFF IFD go into IFD0, GPS fields into the GPS IFD and all others into the Exif IFD
+#[derive(Default)]
+pub struct Exif {
+  fields: Vec<(exif::Tag, Value)>,
+}
+
+impl Exif {
+  pub fn new()->Exif {
+    Exif::default()
+  }
+
+  pub fn field(self, tag: exif::Tag, value: &str)->Exif {
+    self.value(tag, Value::Ascii(value.to_string()))
+  }
+
+  pub fn value(mut self, tag: exif::Tag, value: Value)->Exif {
+    self.fields.push((tag, value));
+    self
+  }
+
+  pub fn datetime(self, value: &str)->Exif {
+    self.field(exif::Tag::DateTime, value)
+  }
+
+  pub fn subsec(self, value: &str)->Exif {
+    self.field(exif::Tag::SubSecTime, value)
+  }
+
+    // a position in degrees, which is stored as whole degrees, minutes and hundredths of seconds
+  pub fn gps(self, latitude: f64, longitude: f64)->Exif {
+    let dms = |degrees: f64| {
+      let hundredths = (degrees.abs() * 360_000.0).round() as u32;
+      Value::Rational(vec![(hundredths / 360_000, 1), (hundredths / 6000 % 60, 1), (hundredths % 6000, 100)])
+    };
+    self.field(exif::Tag::GPSLatitudeRef, if latitude < 0.0 { "S" } else { "N" })
+      .value(exif::Tag::GPSLatitude, dms(latitude))
+      .field(exif::Tag::GPSLongitudeRef, if longitude < 0.0 { "W" } else { "E" })
+      .value(exif::Tag::GPSLongitude, dms(longitude))
+  }
+
+    // the exif data as a big-endian TIFF structure
+  pub fn tiff(&self)->Vec<u8> {
+    let mut ifds: [Vec<IfdEntry>; 3] = [Vec::new(), Vec::new(), Vec::new()];
+    for (tag, value) in &self.fields {
+      let ifd = match tag.context() {
+        exif::Context::Tiff => 0,
+        exif::Context::Gps => 2,
+        _ => 1,
+      };
+      ifds[ifd].push((tag.number(), value.encode()));
+    }
+    for ifd in &mut ifds {
+      ifd.sort_by_key(|entry| entry.0);
+    }
+
+      // IFD0 comes first, followed by the Exif and GPS IFDs if they have any entries, and then the values that don't fit into an entry
+    let pointer_tags = [0x8769u16, 0x8825];
+    let pointers: Vec<u16> = (1..3).filter(|&ifd| !ifds[ifd].is_empty()).map(|ifd| pointer_tags[ifd - 1]).collect();
+    let mut ifd_offsets = [8, 0, 0];
+    let mut offset = 8 + ifd_len(ifds[0].len() + pointers.len());
+    for ifd in 1..3 {
+      if !ifds[ifd].is_empty() {
+        ifd_offsets[ifd] = offset;
+        offset += ifd_len(ifds[ifd].len());
+      }
+    }
+    let mut data_offset = offset;
+
+    let mut out = b"MM\0\x2a\0\0\0\x08".to_vec();
+    let mut data = Vec::new();
+    for (ifd, ifd_entries) in ifds.iter().enumerate() {
+      if ifd > 0 && ifd_entries.is_empty() {
+        continue;
+      }
+
+      let mut entries = ifd_entries.clone();
+      if ifd == 0 {
+        for (index, &tag) in pointer_tags.iter().enumerate() {
+          if pointers.contains(&tag) {
+            entries.push((tag, (4, 1, (ifd_offsets[index + 1] as u32).to_be_bytes().to_vec()))); // LONG
+          }
+        }
+        entries.sort_by_key(|entry| entry.0);
+      }
+
+      out.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+      for (tag, (value_type, count, bytes)) in entries {
+        out.extend_from_slice(&tag.to_be_bytes());
+        out.extend_from_slice(&value_type.to_be_bytes());
+        out.extend_from_slice(&count.to_be_bytes());
+        if bytes.len() <= 4 {
+          let mut inline = bytes.clone();
+          inline.resize(4, 0);
+          out.extend_from_slice(&inline);
+        } else {
+          out.extend_from_slice(&(data_offset as u32).to_be_bytes());
+          data.extend_from_slice(&bytes);
+          data_offset += bytes.len();
+          if bytes.len() % 2 == 1 {
+            data.push(0);
+            data_offset += 1;
+          }
+        }
+      }
+      out.extend_from_slice(&0u32.to_be_bytes()); // no next IFD
+    }
+
+    out.extend_from_slice(&data);
+    out
+  }
+
+    // a minimal JPEG file holding only the exif segment
+  pub fn jpeg(&self)->Vec<u8> {
+    let tiff = self.tiff();
+    let mut out = vec![0xff, 0xd8, 0xff, 0xe1];
+    out.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+    out.extend_from_slice(b"Exif\0\0");
+    out.extend_from_slice(&tiff);
+    out.extend_from_slice(&[0xff, 0xd9]);
+    out
+  }
+}
+
+fn ifd_len(entries: usize)->usize {
+  2 + 12 * entries + 4
+}
+
+pub fn write_jpeg(dir: &Path, name: &str, exif: &Exif)->PathBuf {
+  let path = dir.join(name);
+  fs::write(&path, exif.jpeg()).unwrap();
+  path
+}
+
+  // a DNG is a TIFF file with a DNGVersion field in IFD0. this one has no image data, which datier doesn't need
+pub fn write_dng(dir: &Path, name: &str, exif: Exif)->PathBuf {
+  let dng_version = exif::Tag(exif::Context::Tiff, 0xc612);
+  let path = dir.join(name);
+  fs::write(&path, exif.value(dng_version, Value::Byte(vec![1, 4, 0, 0])).tiff()).unwrap();
+  path
+}
+
+pub fn file_name(path: &Path)->String {
+  path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+  // a minimal mp4 file whose movie header was created at the given unix time, or has no creation time if None
+pub fn write_mp4(dir: &Path, name: &str, unix_time: Option<i64>)->PathBuf {
+  let creation_time = unix_time.map_or(0, |time| (time + 2_082_844_800) as u32); // QuickTime timestamps count from 1904
+  let mut mvhd = vec![0u8; 4]; // version 0, no flags
+  mvhd.extend_from_slice(&creation_time.to_be_bytes());
+  mvhd.extend_from_slice(&creation_time.to_be_bytes()); // modification time
+  mvhd.resize(100, 0);
+
+  let mut out = mp4_box(b"ftyp", b"isom\0\0\x02\0isom");
+  out.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+  out.extend(mp4_box(b"mdat", &[0u8; 16]));
+
+  let path = dir.join(name);
+  fs::write(&path, out).unwrap();
+  path
+}
+
+fn mp4_box(name: &[u8; 4], contents: &[u8])->Vec<u8> {
+  let mut out = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+  out.extend_from_slice(name);
+  out.extend_from_slice(contents);
+  out
+}
+
+  // a minimal PNG file without exif data, with a tIME chunk for the given year, month, day, hour, minute and second if they are given
+  // the chunks have no valid CRCs, which nothing checks
+pub fn write_png(dir: &Path, name: &str, time: Option<(u16, u8, u8, u8, u8, u8)>)->PathBuf {
+  let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+  out.extend(png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]));
+  if let Some((year, month, day, hour, minute, second)) = time {
+    let mut time = year.to_be_bytes().to_vec();
+    time.extend_from_slice(&[month, day, hour, minute, second]);
+    out.extend(png_chunk(b"tIME", &time));
+  }
+  out.extend(png_chunk(b"IDAT", &[0u8; 12]));
+  out.extend(png_chunk(b"IEND", &[]));
+
+  let path = dir.join(name);
+  fs::write(&path, out).unwrap();
+  path
+}
+
+fn png_chunk(name: &[u8; 4], contents: &[u8])->Vec<u8> {
+  let mut out = (contents.len() as u32).to_be_bytes().to_vec();
+  out.extend_from_slice(name);
+  out.extend_from_slice(contents);
+  out.extend_from_slice(&[0u8; 4]);
+  out
+}
+
+  // a minimal HEIF file with only an Exif item, stored in the mdat box
+pub fn write_heic(dir: &Path, name: &str, exif: &Exif)->PathBuf {
+  let mut exif_item = 0u32.to_be_bytes().to_vec(); // no offset to the TIFF header
+  exif_item.extend(exif.tiff());
+
+  let ftyp = mp4_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+  let mut infe = vec![2, 0, 0, 0]; // version 2
+  infe.extend_from_slice(&1u16.to_be_bytes()); // item id
+  infe.extend_from_slice(&0u16.to_be_bytes()); // protection index
+  infe.extend_from_slice(b"Exif\0");
+  let mut iinf = vec![0, 0, 0, 0];
+  iinf.extend_from_slice(&1u16.to_be_bytes());
+  iinf.extend(mp4_box(b"infe", &infe));
+
+  let meta_for_offset = |offset: u32| {
+    let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00]; // version 0, 4 byte offsets and lengths, no base offset
+    iloc.extend_from_slice(&1u16.to_be_bytes()); // item count
+    iloc.extend_from_slice(&1u16.to_be_bytes()); // item id
+    iloc.extend_from_slice(&0u16.to_be_bytes()); // data reference index
+    iloc.extend_from_slice(&1u16.to_be_bytes()); // extent count
+    iloc.extend_from_slice(&offset.to_be_bytes());
+    iloc.extend_from_slice(&(exif_item.len() as u32).to_be_bytes());
+
+    let mut meta = vec![0, 0, 0, 0];
+    meta.extend(mp4_box(b"iinf", &iinf));
+    meta.extend(mp4_box(b"iloc", &iloc));
+    mp4_box(b"meta", &meta)
+  };
+  let data_offset = ftyp.len() + meta_for_offset(0).len() + 8;
+
+  let mut out = ftyp;
+  out.extend(meta_for_offset(data_offset as u32));
+  out.extend(mp4_box(b"mdat", &exif_item));
+
+  let path = dir.join(name);
+  fs::write(&path, out).unwrap();
+  path
+}
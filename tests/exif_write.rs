@@ -0,0 +1,25 @@
+mod common;
+
+use common::{write_jpeg, Exif};
+use datier::{get_datetime, rewrite_dates, DateTag, ReadOptions};
+
+fn read(path: &std::path::Path, tag: DateTag)->String {
+  let opts = ReadOptions { date_tags: vec![tag], ..ReadOptions::default() };
+  get_datetime(path, &opts).unwrap().to_string()
+}
+
+#[test]
+fn shifts_all_date_fields_in_place() {
+  let dir = tempfile::tempdir().unwrap();
+  let exif = Exif::new()
+    .datetime("2020:12:31 23:30:00")
+    .field(exif::Tag::DateTimeOriginal, "2020:12:31 23:00:00")
+    .field(exif::Tag::Make, "Datier");
+  let path = write_jpeg(dir.path(), "a.jpg", &exif);
+  let len = std::fs::metadata(&path).unwrap().len();
+
+  assert_eq!(rewrite_dates(&path, 3600).unwrap(), 2);
+  assert_eq!(read(&path, DateTag::DateTime), "2021-01-01 00:30:00");
+  assert_eq!(read(&path, DateTag::DateTimeOriginal), "2021-01-01 00:00:00");
+  assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+}
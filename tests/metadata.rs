@@ -0,0 +1,226 @@
+mod common;
+
+use common::{write_dng, write_jpeg, write_mp4, write_png, Exif, Value};
+use datier::{consulted_tags, detect_kind, get_datetime, get_metadata, read_tag_values, DateTag, FileKind, GetDateTimeError, MetadataCache, ReadOptions, ValidationSummary, DEFAULT_EXTENSIONS};
+
+#[test]
+fn lists_the_values_of_the_consulted_tags() {
+  let dir = tempfile::tempdir().unwrap();
+  let exif = Exif::new()
+    .datetime("garbage")
+    .field(exif::Tag::DateTimeOriginal, "2021:03:15 09:00:00")
+    .field(exif::Tag::Make, "Datier");
+  let path = write_jpeg(dir.path(), "a.jpg", &exif);
+  let opts = ReadOptions { date_tags: vec![DateTag::DateTime, DateTag::DateTimeOriginal], ..ReadOptions::default() };
+
+  let values = read_tag_values(&path, &consulted_tags(&opts)).unwrap();
+  let value = |tag: exif::Tag| values.iter().find(|&&(value_tag, _)| value_tag == tag).unwrap().1.clone();
+  assert_eq!(values[0].0, exif::Tag::DateTime);
+  assert_eq!(values[1].0, exif::Tag::DateTimeOriginal);
+  assert_eq!(value(exif::Tag::DateTime).as_deref(), Some("\"garbage\""));
+  assert_eq!(value(exif::Tag::DateTimeOriginal).as_deref(), Some("2021-03-15 09:00:00"));
+  assert_eq!(value(exif::Tag::Make).as_deref(), Some("\"Datier\""));
+  assert_eq!(value(exif::Tag::SubSecTime), None);
+  assert_eq!(value(exif::Tag::OffsetTimeOriginal), None);
+  assert!(!values.iter().any(|&(tag, _)| tag == exif::Tag::DateTimeDigitized));
+}
+
+#[test]
+fn subseconds_are_read_from_the_field_of_the_date_used() {
+  let dir = tempfile::tempdir().unwrap();
+  let exif = Exif::new()
+    .subsec("75")
+    .field(exif::Tag::DateTimeOriginal, "2021:03:15 09:00:00")
+    .field(exif::Tag::SubSecTimeOriginal, "25")
+    .field(exif::Tag::DateTimeDigitized, "2021:03:15 09:00:01")
+    .field(exif::Tag::SubSecTimeDigitized, "5");
+  let path = write_jpeg(dir.path(), "a.jpg", &exif);
+
+  assert_eq!(get_metadata(&path, &ReadOptions::default()).unwrap().datetime.nanosecond, Some(250_000_000));
+  let opts = ReadOptions { date_tags: vec![DateTag::DateTimeDigitized], ..ReadOptions::default() };
+  assert_eq!(get_metadata(&path, &opts).unwrap().datetime.nanosecond, Some(500_000_000));
+  assert!(consulted_tags(&opts).contains(&exif::Tag::SubSecTimeDigitized));
+  assert!(!consulted_tags(&opts).contains(&exif::Tag::SubSecTime));
+}
+
+#[test]
+fn reads_dng_and_tiff_files() {
+  let dir = tempfile::tempdir().unwrap();
+  let exif = || Exif::new().datetime("2021:03:15 09:00:00").field(exif::Tag::DateTimeOriginal, "2021:03:14 08:00:00");
+  let dng = write_dng(dir.path(), "a.dng", exif());
+  let tiff = dir.path().join("b.tif");
+  std::fs::write(&tiff, exif().tiff()).unwrap();
+
+  assert_eq!(get_datetime(&dng, &ReadOptions::default()).unwrap().to_string(), "2021-03-15 09:00:00");
+  let opts = ReadOptions { date_tags: vec![DateTag::DateTimeOriginal], ..ReadOptions::default() };
+  assert_eq!(get_datetime(&dng, &opts).unwrap().to_string(), "2021-03-14 08:00:00");
+  assert_eq!(get_datetime(&tiff, &ReadOptions::default()).unwrap().to_string(), "2021-03-15 09:00:00");
+  for ext in &["tif", "tiff", "dng"] {
+    assert!(DEFAULT_EXTENSIONS.contains(ext));
+  }
+}
+
+#[test]
+fn png_files_are_dated_by_their_time_chunk() {
+  let dir = tempfile::tempdir().unwrap();
+  let screenshot = write_png(dir.path(), "screenshot.png", Some((2021, 3, 15, 9, 30, 0)));
+  let opts = ReadOptions::default();
+  assert_eq!(get_metadata(&screenshot, &opts).unwrap().datetime.to_string(), "2021-03-15 09:30:00");
+
+  let berlin = ReadOptions { to_timezone: Some(chrono_tz::Europe::Berlin), ..ReadOptions::default() };
+  assert_eq!(get_metadata(&screenshot, &berlin).unwrap().datetime.to_string(), "2021-03-15 10:30:00");
+
+  let undated = write_png(dir.path(), "undated.png", None);
+  assert!(matches!(get_metadata(&undated, &opts), Err(GetDateTimeError::ReaderCreateError { .. })));
+}
+
+#[test]
+fn videos_are_dated_in_the_local_time_zone_like_photos() {
+  let dir = tempfile::tempdir().unwrap();
+  let video = write_mp4(dir.path(), "a.mp4", Some(1_615_802_400)); // 2021-03-15 10:00:00 UTC
+  assert_eq!(get_metadata(&video, &ReadOptions::default()).unwrap().datetime.to_string(), "2021-03-15 10:00:00");
+
+  let tokyo = ReadOptions { local_timezone: Some(chrono_tz::Asia::Tokyo), ..ReadOptions::default() };
+  let datetime = get_metadata(&video, &tokyo).unwrap().datetime;
+  assert_eq!(datetime.to_string(), "2021-03-15 19:00:00");
+  assert_eq!(datetime.offset, None);
+
+    // the offset given for the files wins over the computer's time zone
+  let offset = ReadOptions { use_offset: true, assumed_offset: 60, ..tokyo };
+  assert_eq!(get_metadata(&video, &offset).unwrap().datetime.to_string(), "2021-03-15 11:00:00");
+}
+
+#[test]
+fn raw_files_without_a_date_are_dated_by_their_embedded_preview() {
+  let dir = tempfile::tempdir().unwrap();
+  let preview = Exif::new().datetime("2021:03:15 09:30:00").field(exif::Tag::Model, "D850").jpeg();
+  let long = |value: usize| Value::Long(vec![value as u32]);
+  let opts = ReadOptions::default();
+
+    // the preview is referenced from IFD0, like in ARW files. the offsets don't change the length of the TIFF data
+  let with_preview_at = |offset: usize| Exif::new()
+    .value(exif::Tag::JPEGInterchangeFormat, long(offset))
+    .value(exif::Tag::JPEGInterchangeFormatLength, long(preview.len()))
+    .tiff();
+  let mut arw = with_preview_at(0);
+  arw = with_preview_at(arw.len());
+  arw.extend_from_slice(&preview);
+  let path = dir.path().join("a.arw");
+  std::fs::write(&path, &arw).unwrap();
+  let metadata = get_metadata(&path, &opts).unwrap();
+  assert_eq!(metadata.datetime.to_string(), "2021-03-15 09:30:00");
+  assert_eq!(metadata.model.as_deref(), Some("D850"));
+
+    // other extensions don't have their previews read
+  let tiff = dir.path().join("a.tif");
+  std::fs::write(&tiff, &arw).unwrap();
+  assert!(get_metadata(&tiff, &opts).is_err());
+
+    // in NEF files, the preview is in a sub-IFD
+  let sub_ifds = exif::Tag(exif::Context::Tiff, 0x014a);
+  let mut nef = Exif::new().value(sub_ifds, long(0)).tiff();
+  let sub_ifd_offset = nef.len();
+  nef = Exif::new().value(sub_ifds, long(sub_ifd_offset)).tiff();
+  let preview_offset = sub_ifd_offset + 2 + 2 * 12 + 4;
+  nef.extend_from_slice(&2u16.to_be_bytes());
+  for (tag, value) in [(0x0201u16, preview_offset), (0x0202, preview.len())] {
+    nef.extend_from_slice(&tag.to_be_bytes());
+    nef.extend_from_slice(&4u16.to_be_bytes()); // LONG
+    nef.extend_from_slice(&1u32.to_be_bytes());
+    nef.extend_from_slice(&(value as u32).to_be_bytes());
+  }
+  nef.extend_from_slice(&0u32.to_be_bytes());
+  nef.extend_from_slice(&preview);
+  let path = dir.path().join("a.nef");
+  std::fs::write(&path, &nef).unwrap();
+  assert_eq!(get_metadata(&path, &opts).unwrap().datetime.to_string(), "2021-03-15 09:30:00");
+}
+
+#[test]
+fn cache_is_used_until_the_file_changes() {
+  let dir = tempfile::tempdir().unwrap();
+  let cache_file = dir.path().join("cache.json");
+  let path = write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00"));
+  let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+  let opts = ReadOptions::default();
+  let read = |cache: &MetadataCache| cache.get_metadata(&path, &opts).unwrap().datetime.to_string();
+
+  let cache = MetadataCache::load(cache_file.clone(), &opts);
+  assert_eq!(read(&cache), "2021-03-15 09:00:00");
+  cache.save().unwrap();
+
+    // the same size and modification time, so the file counts as unchanged
+  write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2022:03:15 09:00:00"));
+  std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+  let cache = MetadataCache::load(cache_file.clone(), &opts);
+  assert_eq!(read(&cache), "2021-03-15 09:00:00");
+
+  let shifted = ReadOptions { shift: 60, ..ReadOptions::default() };
+  let cache_with_other_options = MetadataCache::load(cache_file.clone(), &shifted);
+  assert_eq!(cache_with_other_options.get_metadata(&path, &shifted).unwrap().datetime.to_string(), "2022-03-15 09:01:00");
+
+  write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2022:03:15 09:00:00").subsec("5"));
+  assert_eq!(read(&cache), "2022-03-15 09:00:00");
+}
+
+#[test]
+fn files_that_cant_hold_exif_data_are_pointed_out() {
+  let dir = tempfile::tempdir().unwrap();
+  let gif = dir.path().join("a.jpg");
+  std::fs::write(&gif, b"GIF89a\x01\x00\x01\x00\x00\x00\x00;").unwrap();
+  let empty = dir.path().join("b.jpg");
+  std::fs::write(&empty, b"").unwrap();
+  let jpeg = write_jpeg(dir.path(), "c.cr2", &Exif::new());
+
+  assert_eq!(detect_kind(&gif).unwrap(), FileKind::Gif);
+  assert_eq!(detect_kind(&jpeg).unwrap(), FileKind::Jpeg);
+  match get_datetime(&gif, &ReadOptions::default()) {
+    Err(GetDateTimeError::UnsupportedFormat { path, detected_kind }) => {
+      assert_eq!(path, gif);
+      assert_eq!(detected_kind, FileKind::Gif);
+    },
+    other => panic!("expected an unsupported format, got {:?}", other),
+  }
+  let error = get_datetime(&empty, &ReadOptions::default()).unwrap_err();
+  assert_eq!(error.to_string(), "Unsupported file format: the file is empty");
+    // files are read by what they contain, not by their extension
+  assert!(matches!(get_datetime(&jpeg, &ReadOptions::default()), Err(GetDateTimeError::FieldReadError { .. })));
+}
+
+#[test]
+fn dates_with_unusual_separators_and_padding_are_read() {
+  let dir = tempfile::tempdir().unwrap();
+  let read = |value: &str| {
+    let path = write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime(value));
+    get_datetime(&path, &ReadOptions::default()).map(|datetime| datetime.to_string())
+  };
+  for value in &[
+    "2021/03/15 14:30:00", "2021-03-15 14:30:00", "2021-03-15T14:30:00", "2021.03.15 14.30.00",
+    "2021:03:15 14:30:00\0\0", "\x002021:03:15 14:30:00", "  2021:03:15 14:30:00 ", "2021:03:15 14:30:00+01:00",
+  ] {
+    assert_eq!(read(value).unwrap(), "2021-03-15 14:30:00", "{:?}", value);
+  }
+  for value in &["2021/03/15", "    :  :     :  :  ", "15/03/2021 14:30:00", "2021/03/15, 14:30"] {
+    assert!(read(value).is_err(), "{:?}", value);
+  }
+}
+
+#[test]
+fn validation_counts_files_by_their_problem() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = [
+    write_jpeg(dir.path(), "valid.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "undated.jpg", &Exif::new().field(exif::Tag::Make, "Datier")),
+    write_jpeg(dir.path(), "garbage.jpg", &Exif::new().datetime("garbage")),
+    write_jpeg(dir.path(), "no_extension", &Exif::new().datetime("2021:03:15 09:00:00")),
+  ];
+  let empty = dir.path().join("empty.jpg");
+  std::fs::write(&empty, b"").unwrap();
+
+  let mut summary = ValidationSummary::default();
+  for path in paths.iter().chain(Some(&empty)) {
+    summary.add(path, &get_metadata(path, &ReadOptions::default()));
+  }
+  assert_eq!(summary, ValidationSummary { valid: 1, missing_field: 1, parse_error: 1, no_extension: 1, no_exif: 1, ..ValidationSummary::default() });
+  assert_eq!(summary.invalid(), 4);
+}
@@ -0,0 +1,1446 @@
+mod common;
+
+use common::{file_name, write_dng, write_heic, write_jpeg, write_mp4, write_png, Exif, Value};
+use datier::{check_destinations, distinct_offsets, exclude_paths, find_duplicates, find_files, find_other_files, format_offset, execute_plan, execute_plan_with_progress, hidden_paths, ignored_paths, journal_path, keep_original_name, original_name, parse_duration, parse_offset, parse_name_pattern, parse_shift, plan_renames, restore_first_names, stash_files, write_script, DateSource, Dimensions, GroupBy, Journal, Locale, Manifest, MetadataCache, NameCase, NumberingScope, OnError, PlanAction, PlanSummary, Places, ReadOptions, RenameError, RenameOptions, ScriptStyle, SkipReason, Subseconds, Template, TieBreak, IGNORE_FILE_NAME};
+use glob::Pattern;
+use std::path::PathBuf;
+
+  // the TIFF/EP ImageNumber field, which the exif crate has no name for
+const IMAGE_NUMBER: exif::Tag = exif::Tag(exif::Context::Tiff, 0x9211);
+
+fn options(dir: &tempfile::TempDir)->RenameOptions {
+  RenameOptions {
+    target_dir: Some(dir.path().to_path_buf()),
+    ..RenameOptions::default()
+  }
+}
+
+  // the planned new names of paths, in the order of the input
+fn planned_names(paths: &[PathBuf], opts: &RenameOptions)->Vec<(String, String)> {
+  let plan = plan_renames(paths, opts);
+  paths.iter().map(|path| {
+    let entry = plan.iter().find(|entry| &entry.source == path).unwrap();
+    let new_name = match entry.action {
+      PlanAction::Rename(ref dest) => file_name(dest),
+      PlanAction::Skip(ref reason) => format!("skipped: {}", reason),
+    };
+    (file_name(path), new_name)
+  }).collect()
+}
+
+#[test]
+fn numbers_files_in_order_of_time() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 14:30:00")),
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 18:45:10")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir)), vec![
+    ("b.jpg".to_string(), "2021_03_15-0002.jpg".to_string()),
+    ("a.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("c.jpg".to_string(), "2021_03_15-0003.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn counter_resets_on_new_day() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 22:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 23:59:59")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:16 00:00:00")),
+    write_jpeg(dir.path(), "d.jpg", &Exif::new().datetime("2021:03:16 08:00:00")),
+    write_jpeg(dir.path(), "e.jpg", &Exif::new().datetime("2022:01:01 08:00:00")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir)), vec![
+    ("a.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_15-0002.jpg".to_string()),
+    ("c.jpg".to_string(), "2021_03_16-0001.jpg".to_string()),
+    ("d.jpg".to_string(), "2021_03_16-0002.jpg".to_string()),
+    ("e.jpg".to_string(), "2022_01_01-0001.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn global_and_per_folder_numbering() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 22:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:16 08:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:04:01 08:00:00")),
+  ];
+  let numbers = |opts: &RenameOptions| planned_names(&paths, opts).into_iter().map(|(_, name)| name).collect::<Vec<_>>();
+
+  let opts = RenameOptions { numbering: NumberingScope::Global, template: Template::parse("{n}").unwrap(), ..options(&dir) };
+  assert_eq!(numbers(&opts), vec!["1.jpg", "2.jpg", "3.jpg"]);
+
+  let opts = RenameOptions { numbering: NumberingScope::PerFolder, group_by: Some(GroupBy::Month), template: Template::parse("{n}").unwrap(), ..options(&dir) };
+  assert_eq!(numbers(&opts), vec!["1.jpg", "2.jpg", "1.jpg"]);
+}
+
+#[test]
+fn subseconds_break_ties() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "late.jpg", &Exif::new().datetime("2021:03:15 14:30:00").subsec("75")),
+    write_jpeg(dir.path(), "early.jpg", &Exif::new().datetime("2021:03:15 14:30:00").subsec("25")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir)), vec![
+    ("late.jpg".to_string(), "2021_03_15-0002.jpg".to_string()),
+    ("early.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn subseconds_can_be_ignored() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 14:30:00").subsec("75")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 14:30:00").subsec("25")),
+  ];
+  let opts = RenameOptions { read: ReadOptions { ignore_subseconds: true, ..ReadOptions::default() }, ..options(&dir) };
+
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2021_03_15-0001-a.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_15-0001-b.jpg".to_string()),
+  ]);
+  let plan = plan_renames(&paths, &opts);
+  assert!(plan.iter().all(|entry| entry.subseconds == Subseconds::Missing));
+}
+
+#[test]
+fn invalid_subseconds_are_reported() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "broken.jpg", &Exif::new().datetime("2021:03:15 14:30:00").subsec("1x")),
+    write_jpeg(dir.path(), "blank.jpg", &Exif::new().datetime("2021:03:15 14:31:00").subsec("   ")),
+    write_jpeg(dir.path(), "valid.jpg", &Exif::new().datetime("2021:03:15 14:32:00").subsec("5")),
+  ];
+
+  let plan = plan_renames(&paths, &options(&dir));
+  let subseconds: Vec<(String, Subseconds)> = plan.iter().map(|entry| (file_name(&entry.source), entry.subseconds.clone())).collect();
+  assert_eq!(subseconds, vec![
+    ("broken.jpg".to_string(), Subseconds::Invalid("1x".to_string())),
+    ("blank.jpg".to_string(), Subseconds::Missing),
+    ("valid.jpg".to_string(), Subseconds::Parsed),
+  ]);
+  assert_eq!(plan[0].datetime.as_ref().unwrap().0.nanosecond, None);
+  assert!(matches!(plan[0].action, PlanAction::Rename(_)));
+}
+
+#[test]
+fn skips_files_without_extension() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "noext", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+
+  let plan = plan_renames(&paths, &options(&dir));
+  let noext = plan.iter().find(|entry| entry.source == paths[1]).unwrap();
+  assert!(matches!(noext.action, PlanAction::Skip(SkipReason::NoExtension)));
+}
+
+#[test]
+fn skips_files_without_date() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().field(exif::Tag::Make, "Camera")),
+  ];
+
+  let plan = plan_renames(&paths, &options(&dir));
+  assert!(matches!(plan[0].action, PlanAction::Skip(SkipReason::NoDateTime(_))));
+}
+
+#[test]
+fn identical_timestamps_share_counter() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "d.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+
+  let names = planned_names(&paths, &options(&dir));
+  assert_eq!(names[0].1, "2021_03_15-0001.jpg");
+  assert_eq!(names[3].1, "2021_03_15-0003.jpg");
+
+    // both files with the same time get the second number, told apart by a letter
+  let mut tied = vec![names[1].1.clone(), names[2].1.clone()];
+  tied.sort();
+  assert_eq!(tied, vec!["2021_03_15-0002-a.jpg".to_string(), "2021_03_15-0002-b.jpg".to_string()]);
+}
+
+#[test]
+fn tied_files_are_numbered_by_path() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "z.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "m.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+  let expected = vec![
+    ("a.jpg".to_string(), "2021_03_15-0001-a.jpg".to_string()),
+    ("m.jpg".to_string(), "2021_03_15-0001-b.jpg".to_string()),
+    ("z.jpg".to_string(), "2021_03_15-0001-c.jpg".to_string()),
+  ];
+
+    // neither the order the files are found in nor repeated runs may change their numbers
+  let mut reversed = paths.clone();
+  reversed.reverse();
+  for _ in 0..10 {
+    for paths in &[&paths, &reversed] {
+      let mut names = planned_names(paths, &options(&dir));
+      names.sort();
+      assert_eq!(names, expected);
+    }
+  }
+}
+
+fn tied_paths(dir: &tempfile::TempDir)->Vec<PathBuf> {
+  vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "d.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ]
+}
+
+#[test]
+fn tie_break_keep_both_increment() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = tied_paths(&dir);
+  let opts = RenameOptions { tie_break: TieBreak::KeepBothIncrement, ..options(&dir) };
+
+  let names = planned_names(&paths, &opts);
+  let mut tied = vec![names[1].1.clone(), names[2].1.clone()];
+  tied.sort();
+  assert_eq!(tied, vec!["2021_03_15-0002.jpg".to_string(), "2021_03_15-0003.jpg".to_string()]);
+  assert_eq!(names[3].1, "2021_03_15-0004.jpg");
+}
+
+#[test]
+fn tie_break_skip() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = tied_paths(&dir);
+  let opts = RenameOptions { tie_break: TieBreak::Skip, ..options(&dir) };
+
+  let plan = plan_renames(&paths, &opts);
+  let skipped = plan.iter().filter(|entry| matches!(entry.action, PlanAction::Skip(SkipReason::SameTimestamp(_)))).count();
+  assert_eq!(skipped, 1);
+  assert_eq!(planned_names(&paths, &opts)[3].1, "2021_03_15-0003.jpg");
+}
+
+#[test]
+fn identical_timestamps_across_days() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:16 10:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:16 12:00:00")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir)), vec![
+    ("a.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_16-0001.jpg".to_string()),
+    ("c.jpg".to_string(), "2021_03_16-0002.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn copy_keeps_originals_in_place() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "2021_03_15-0002.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "x.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+
+    // when moving, the first file frees up its old name for the second one
+    // the first file is already named like the template, so it's only renamed with force
+  let opts = RenameOptions { force: true, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[1].1, "2021_03_15-0002.jpg");
+
+  let opts = RenameOptions { copy: true, ..opts };
+  let plan = plan_renames(&paths, &opts);
+  let results = execute_plan(&plan, &opts);
+  assert!(results.iter().all(|result| result.outcome.is_ok()));
+
+  let mut names: Vec<String> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+  names.sort();
+  assert_eq!(names, vec!["2021_03_15-0001.jpg", "2021_03_15-0002-2.jpg", "2021_03_15-0002.jpg", "x.jpg"]);
+}
+
+#[test]
+fn renames_and_copies_can_be_verified() {
+  let dir = tempfile::tempdir().unwrap();
+  let output = tempfile::tempdir().unwrap();
+  let paths = vec![write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00"))];
+  let original = std::fs::read(&paths[0]).unwrap();
+
+  let opts = RenameOptions { target_dir: Some(output.path().to_path_buf()), copy: true, verify: true, ..RenameOptions::default() };
+  assert!(execute_plan(&plan_renames(&paths, &opts), &opts).iter().all(|result| result.outcome.is_ok()));
+  assert_eq!(std::fs::read(output.path().join("2021_03_15-0001.jpg")).unwrap(), original);
+
+  let opts = RenameOptions { verify: true, ..options(&dir) };
+  assert!(execute_plan(&plan_renames(&paths, &opts), &opts).iter().all(|result| result.outcome.is_ok()));
+  assert_eq!(std::fs::read(dir.path().join("2021_03_15-0001.jpg")).unwrap(), original);
+}
+
+#[cfg(unix)]
+#[test]
+fn files_that_fail_verification_after_renaming_are_reported_at_their_new_name() {
+  let dir = tempfile::tempdir().unwrap();
+  let folder = dir.path().join("folder");
+  std::fs::create_dir(&folder).unwrap();
+  let paths = vec![dir.path().join("link.jpg")];
+  std::os::unix::fs::symlink(&folder, &paths[0]).unwrap();
+  let dest = dir.path().join("2021_03_15-0001.jpg");
+  let mut plan = plan_renames(&paths, &options(&dir));
+  plan[0].action = PlanAction::Rename(dest.clone());
+
+    // the link is renamed, but what it points to isn't a file
+  let opts = RenameOptions { verify: true, on_error: OnError::Retry, ..options(&dir) };
+  let results = execute_plan(&plan, &opts);
+  match results[0].outcome {
+    Err(RenameError::NotFoundAfterRename { dest: ref reported }) => assert_eq!(reported, &dest),
+    ref outcome => panic!("unexpected outcome {:?}", outcome),
+  }
+  assert!(std::fs::symlink_metadata(&dest).unwrap().file_type().is_symlink());
+  let mut names: Vec<String> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+  names.sort();
+  assert_eq!(names, vec!["2021_03_15-0001.jpg", "folder"]);
+
+  let journal = Journal::from_results(dir.path(), &results);
+  assert_eq!(journal.entries.len(), 1);
+  assert_eq!(dir.path().join(&journal.entries[0].renamed), dest);
+}
+
+#[test]
+fn mirror_recreates_the_folders_in_the_target_dir() {
+  let input = tempfile::tempdir().unwrap();
+  let output = tempfile::tempdir().unwrap();
+  let trip = input.path().join("trip");
+  std::fs::create_dir(&trip).unwrap();
+  let exif = Exif::new().datetime("2021:03:15 10:00:00");
+  let paths = vec![write_jpeg(input.path(), "a.jpg", &exif), write_jpeg(&trip, "b.jpg", &exif)];
+  let opts = RenameOptions { target_dir: Some(output.path().to_path_buf()), mirror: true, input_dir: input.path().to_path_buf(), copy: true, ..RenameOptions::default() };
+
+  let plan = plan_renames(&paths, &opts);
+  assert!(execute_plan(&plan, &opts).iter().all(|result| result.outcome.is_ok()));
+    // each folder is numbered on its own
+  assert!(output.path().join("2021_03_15-0001.jpg").is_file());
+  assert!(output.path().join("trip").join("2021_03_15-0001.jpg").is_file());
+  assert!(paths.iter().all(|path| path.is_file()));
+}
+
+#[test]
+fn first_names_are_restored_over_several_runs() {
+  let dir = tempfile::tempdir().unwrap();
+  write_jpeg(dir.path(), "IMG_0001.JPG", &Exif::new().datetime("2021:03:15 10:00:00"));
+  write_jpeg(dir.path(), "IMG_0002.JPG", &Exif::new().datetime("2021:03:15 11:00:00"));
+  let run = |format: &str| {
+    let paths = find_files(dir.path(), &[String::from("jpg")], false, None, false).unwrap().paths;
+    let opts = RenameOptions { template: Template::parse(format).unwrap(), ..options(&dir) };
+    let results = execute_plan(&plan_renames(&paths, &opts), &opts);
+    let mut journal = Journal::for_next_run(dir.path());
+    journal.add_results(dir.path(), &results);
+    journal.write(dir.path()).unwrap();
+  };
+  run("{year}-{n}");
+  run("{n}-{year}");
+  assert!(dir.path().join("1-2021.JPG").is_file());
+
+  let results = restore_first_names(dir.path()).unwrap();
+  assert!(results.iter().all(|result| result.outcome.is_ok()));
+  assert!(dir.path().join("IMG_0001.JPG").is_file());
+  assert!(dir.path().join("IMG_0002.JPG").is_file());
+  assert!(!journal_path(dir.path()).exists());
+}
+
+#[test]
+fn shift_rolls_over_into_the_next_year() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2020:12:31 22:30:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:01:01 00:30:00")),
+  ];
+  let mut opts = options(&dir);
+  opts.read.shift = parse_shift("+1h45m").unwrap();
+
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2021_01_01-0001.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_01_01-0002.jpg".to_string()),
+  ]);
+
+  opts.read.shift = parse_shift("-1d").unwrap();
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2020_12_30-0001.jpg".to_string()),
+    ("b.jpg".to_string(), "2020_12_31-0001.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn videos_share_the_counter_with_photos() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_mp4(dir.path(), "b.mp4", Some(1_615_802_400)), // 2021-03-15 10:00:00 UTC
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+    write_mp4(dir.path(), "d.mov", None),
+  ];
+
+  let names = planned_names(&paths, &options(&dir));
+  assert_eq!(names[..3], [
+    ("a.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("b.mp4".to_string(), "2021_03_15-0002.mp4".to_string()),
+    ("c.jpg".to_string(), "2021_03_15-0003.jpg".to_string()),
+  ]);
+  assert_eq!(names[3].1, "skipped: Could not read video date: Movie header has no creation time");
+}
+
+#[test]
+fn mtime_tolerance_flags_suspicious_dates() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2000:01:01 00:00:00")),
+  ];
+  let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_615_806_000); // 2021-03-15 11:00:00 UTC
+  for path in &paths {
+    std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+  }
+
+  let opts = RenameOptions { mtime_tolerance: Some(parse_duration("1d").unwrap()), ..options(&dir) };
+  let names = planned_names(&paths, &opts);
+  assert_eq!(names[0].1, "2021_03_15-0001.jpg");
+  assert!(names[1].1.starts_with("skipped: Suspicious date"));
+
+  let opts = RenameOptions { fallback_to_mtime: true, ..opts };
+  let plan = plan_renames(&paths, &opts);
+  let fallback = plan.iter().find(|entry| entry.source == paths[1]).unwrap();
+  assert_eq!(fallback.date_source, DateSource::ModifiedTime);
+  assert_eq!(planned_names(&paths, &opts)[1].1, "2021_03_15-0002.jpg");
+}
+
+#[test]
+fn future_dates_can_be_rejected() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2099:01:01 12:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir))[0].1, "2099_01_01-0001.jpg");
+
+  let opts = RenameOptions { reject_future: true, ..options(&dir) };
+  let plan = plan_renames(&paths, &opts);
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "skipped: Date is in the future".to_string()),
+    ("b.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+  ]);
+  assert_eq!(PlanSummary::of(&plan).future, 1);
+}
+
+#[test]
+fn png_files_without_a_date_can_fall_back_to_their_modification_time() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![write_png(dir.path(), "screenshot.png", None), write_jpeg(dir.path(), "undated.jpg", &Exif::new())];
+  let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_615_806_000); // 2021-03-15 11:00:00 UTC
+  for path in &paths {
+    std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+  }
+
+  assert!(planned_names(&paths, &options(&dir)).iter().all(|(_, name)| name.starts_with("skipped")));
+  let opts = RenameOptions { fallback_to_mtime: true, ..options(&dir) };
+  let plan = plan_renames(&paths, &opts);
+  assert_eq!(plan.iter().find(|entry| entry.source == paths[0]).unwrap().date_source, DateSource::ModifiedTime);
+    // only PNG files, which seldom have a date in them, are dated this way
+  assert_eq!(planned_names(&paths, &opts).iter().map(|(_, name)| name.starts_with("skipped")).collect::<Vec<_>>(), vec![false, true]);
+  assert_eq!(planned_names(&paths, &opts)[0].1, "2021_03_15-0001.png");
+}
+
+#[test]
+fn reads_exif_from_heic_files() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_heic(dir.path(), "a.heic", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir)), vec![
+    ("a.heic".to_string(), "2021_03_15-0002.heic".to_string()),
+    ("b.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn sidecars_follow_their_file() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "IMG_1.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "IMG_2.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+  std::fs::write(dir.path().join("IMG_1.XMP"), "sidecar").unwrap();
+  std::fs::write(dir.path().join("IMG_2.xmp"), "sidecar").unwrap();
+    // taken by an unrelated file, so IMG_2 and its sidecar have to move on to the next free name together
+  std::fs::write(dir.path().join("2021_03_15-0002.xmp"), "other").unwrap();
+
+  let opts = RenameOptions { sidecar_extensions: vec!["xmp".to_string()], ..options(&dir) };
+  let mut renames: Vec<(String, String)> = plan_renames(&paths, &opts).iter().filter_map(|entry| match entry.action {
+    PlanAction::Rename(ref dest) => Some((file_name(&entry.source), file_name(dest))),
+    PlanAction::Skip(_) => None,
+  }).collect();
+  renames.sort();
+
+  assert_eq!(renames, vec![
+    ("IMG_1.XMP".to_string(), "2021_03_15-0001.XMP".to_string()),
+    ("IMG_1.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("IMG_2.jpg".to_string(), "2021_03_15-0002-2.jpg".to_string()),
+    ("IMG_2.xmp".to_string(), "2021_03_15-0002-2.xmp".to_string()),
+  ]);
+}
+
+#[test]
+fn sidecar_extensions_are_written_like_their_files() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![write_dng(dir.path(), "IMG.CR2", Exif::new().datetime("2021:03:15 10:00:00"))];
+  std::fs::write(dir.path().join("IMG.XMP"), "sidecar").unwrap();
+  let renames = |opts: &RenameOptions| -> Vec<String> {
+    plan_renames(&paths, opts).iter().filter_map(|entry| match entry.action {
+      PlanAction::Rename(ref dest) => Some(file_name(dest)),
+      PlanAction::Skip(_) => None,
+    }).collect()
+  };
+
+  let opts = RenameOptions { sidecar_extensions: vec!["xmp".to_string()], ..options(&dir) };
+  assert_eq!(renames(&opts), vec!["2021_03_15-0001.CR2", "2021_03_15-0001.XMP"]);
+  let opts = RenameOptions { normalize_ext: true, ..opts };
+  assert_eq!(renames(&opts), vec!["2021_03_15-0001.cr2", "2021_03_15-0001.xmp"]);
+
+  let mut template = Template::default();
+  template.set_case(NameCase::Upper);
+  let opts = RenameOptions { template, ..opts };
+  assert_eq!(renames(&opts), vec!["2021_03_15-0001.CR2", "2021_03_15-0001.XMP"]);
+  execute_plan(&plan_renames(&paths, &opts), &opts);
+  assert!(dir.path().join("2021_03_15-0001.CR2").is_file());
+  assert!(dir.path().join("2021_03_15-0001.XMP").is_file());
+}
+
+#[test]
+fn counter_start_and_width() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+  let mut template = Template::default();
+  template.set_counter_width(2);
+  let opts = RenameOptions { template, counter_start: 99, ..options(&dir) };
+
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2021_03_15-99.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_15-100.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn place_is_looked_up_from_gps() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "berlin.jpg", &Exif::new().datetime("2021:03:15 09:00:00").gps(52.5163, 13.3777)),
+    write_jpeg(dir.path(), "rio.jpg", &Exif::new().datetime("2021:03:15 10:00:00").gps(-22.9519, -43.2105)),
+    write_jpeg(dir.path(), "nowhere.jpg", &Exif::new().datetime("2021:03:15 11:00:00").gps(0.0, 0.0)),
+    write_jpeg(dir.path(), "no_gps.jpg", &Exif::new().datetime("2021:03:15 12:00:00")),
+  ];
+  let places = Places::parse("# name, latitude, longitude, radius\nBerlin, 52.52, 13.405\nPotsdam, 52.39, 13.06, 20\n\nRio de Janeiro, -22.9068, -43.1729, 30\n").unwrap();
+  let opts = RenameOptions {
+    template: Template::parse("{place}-{n}").unwrap(),
+    places: Some(places),
+    ..options(&dir)
+  };
+
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("berlin.jpg".to_string(), "Berlin-1.jpg".to_string()),
+    ("rio.jpg".to_string(), "Rio de Janeiro-2.jpg".to_string()),
+    ("nowhere.jpg".to_string(), "unknown-3.jpg".to_string()),
+    ("no_gps.jpg".to_string(), "unknown-4.jpg".to_string()),
+  ]);
+  assert_eq!(Places::parse("Berlin, 52.52\n").unwrap_err().0, 1);
+  assert_eq!(Places::parse("\nBerlin, 95, 13.4\n").unwrap_err().0, 2);
+}
+
+#[test]
+fn camera_make_and_model() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "canon.jpg", &Exif::new().datetime("2021:03:15 09:00:00").field(exif::Tag::Make, "Canon").field(exif::Tag::Model, "EOS 5D Mk/II  ")),
+    write_jpeg(dir.path(), "unnamed.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+  let opts = RenameOptions { template: Template::parse("{make}_{model}-{n}").unwrap(), ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("canon.jpg".to_string(), "Canon_EOS 5D Mk_II-1.jpg".to_string()),
+    ("unnamed.jpg".to_string(), "_-2.jpg".to_string()),
+  ]);
+
+  let opts = RenameOptions { template: Template::parse("{model}-{n}").unwrap(), camera_default: "camera".to_string(), ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[1].1, "camera-2.jpg");
+}
+
+#[test]
+fn names_can_be_written_in_one_case() {
+  let dir = tempfile::tempdir().unwrap();
+  let exif = || Exif::new().datetime("2021:03:15 09:00:00").field(exif::Tag::Model, "EOS R5");
+  let paths = vec![write_jpeg(dir.path(), "a.JPG", &exif()), write_jpeg(dir.path(), "b.jpg", &exif())];
+  let mut template = Template::parse("{model}-{n}").unwrap();
+  template.set_case(NameCase::Lower);
+  let opts = RenameOptions { template, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.JPG".to_string(), "eos r5-1-a.jpg".to_string()),
+    ("b.jpg".to_string(), "eos r5-1-b.jpg".to_string()),
+  ]);
+
+  let mut template = Template::parse("{model}-{n}").unwrap();
+  template.set_case(NameCase::Upper);
+  let opts = RenameOptions { template, ..options(&dir) };
+  let plan = plan_renames(&paths, &opts);
+  execute_plan(&plan, &opts);
+  let renamed = vec![dir.path().join("EOS R5-1-A.JPG"), dir.path().join("EOS R5-1-B.JPG")];
+  assert!(renamed.iter().all(|path| path.is_file()));
+    // the names are recognized as already matching the format on the next run
+  assert!(planned_names(&renamed, &opts).iter().all(|(_, name)| name == "skipped: Already named correctly"));
+}
+
+#[test]
+fn lens_and_focal_length() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "prime.jpg", &Exif::new().datetime("2021:03:15 09:00:00").field(exif::Tag::LensModel, "EF50mm f/1.8 STM").value(exif::Tag::FocalLength, Value::Rational(vec![(50, 1)]))),
+    write_jpeg(dir.path(), "phone.jpg", &Exif::new().datetime("2021:03:15 10:00:00").value(exif::Tag::FocalLength, Value::Rational(vec![(425, 100)]))),
+    write_jpeg(dir.path(), "unknown.jpg", &Exif::new().datetime("2021:03:15 11:00:00").value(exif::Tag::FocalLength, Value::Rational(vec![(0, 0)]))),
+  ];
+  let opts = RenameOptions { template: Template::parse("{focal}_{lens}-{n}").unwrap(), lens_default: "x".to_string(), ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("prime.jpg".to_string(), "50mm_EF50mm f_1.8 STM-1.jpg".to_string()),
+    ("phone.jpg".to_string(), "4.3mm_x-2.jpg".to_string()),
+    ("unknown.jpg".to_string(), "x_x-3.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn already_named_files_are_kept() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "2021_03_15-0007.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "2021_03_15-0003-b-2.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "2021_03_16-0001.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+    write_jpeg(dir.path(), "2021_03_15-01.jpg", &Exif::new().datetime("2021:03:15 12:00:00")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir)), vec![
+    ("2021_03_15-0007.jpg".to_string(), "skipped: Already named correctly".to_string()),
+    ("2021_03_15-0003-b-2.jpg".to_string(), "skipped: Already named correctly".to_string()),
+    ("2021_03_16-0001.jpg".to_string(), "2021_03_15-0003.jpg".to_string()),
+    ("2021_03_15-01.jpg".to_string(), "2021_03_15-0004.jpg".to_string()),
+  ]);
+
+  let opts = RenameOptions { force: true, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[0].1, "2021_03_15-0001.jpg");
+}
+
+#[test]
+fn exclude_patterns_skip_matching_paths() {
+  let dir = tempfile::tempdir().unwrap();
+  let thumbnails = dir.path().join("trip").join("Thumbnails");
+  std::fs::create_dir_all(&thumbnails).unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new()),
+    write_jpeg(&thumbnails, "a.jpg", &Exif::new()),
+    write_jpeg(&dir.path().join("trip"), "b_small.jpg", &Exif::new()),
+    write_jpeg(&dir.path().join("trip"), "c.jpg", &Exif::new()),
+  ];
+  let patterns = vec![Pattern::new("*thumbnails/*").unwrap(), Pattern::new("*_small.jpg").unwrap()];
+
+  let (kept, excluded) = exclude_paths(paths.clone(), dir.path(), &patterns);
+  assert_eq!(kept, vec![paths[0].clone(), paths[3].clone()]);
+  assert_eq!(excluded, vec![paths[1].clone(), paths[2].clone()]);
+}
+
+#[test]
+fn hidden_and_ignored_files_are_left_out() {
+  let dir = tempfile::tempdir().unwrap();
+  let git = dir.path().join(".git");
+  let raw = dir.path().join("raw");
+  std::fs::create_dir_all(&git).unwrap();
+  std::fs::create_dir_all(&raw).unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new()),
+    write_jpeg(dir.path(), ".b.jpg", &Exif::new()),
+    write_jpeg(&git, "c.jpg", &Exif::new()),
+    write_jpeg(&raw, "d.jpg", &Exif::new()),
+    write_jpeg(&raw, "keep.jpg", &Exif::new()),
+    write_jpeg(dir.path(), "e_edit.jpg", &Exif::new()),
+  ];
+
+  let (kept, hidden) = hidden_paths(paths.clone(), dir.path());
+  assert_eq!(kept, vec![paths[0].clone(), paths[3].clone(), paths[4].clone(), paths[5].clone()]);
+  assert_eq!(hidden, vec![paths[1].clone(), paths[2].clone()]);
+
+  assert_eq!(ignored_paths(kept.clone(), dir.path()).unwrap().0, kept);
+  std::fs::write(dir.path().join(IGNORE_FILE_NAME), "# leave these alone\nraw/*\n!raw/keep.jpg\n*_edit.jpg\n").unwrap();
+  let (kept, ignored) = ignored_paths(kept, dir.path()).unwrap();
+  assert_eq!(kept, vec![paths[0].clone(), paths[4].clone()]);
+  assert_eq!(ignored, vec![paths[3].clone(), paths[5].clone()]);
+}
+
+#[test]
+fn month_names_in_the_chosen_language() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00"))];
+
+  let opts = RenameOptions { template: Template::parse("{day}. {month_name} {year} ({month_abbr})").unwrap(), ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[0].1, "15. March 2021 (Mar).jpg");
+
+  let mut template = Template::parse("{day}. {month_name} {year} ({month_abbr})").unwrap();
+  template.set_locale("de_DE.UTF-8".parse::<Locale>().unwrap());
+  let opts = RenameOptions { template, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[0].1, "15. März 2021 (Mär).jpg");
+  assert!("xx".parse::<Locale>().is_err());
+}
+
+#[test]
+fn on_error_abort_stops_the_renames() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+  let opts = RenameOptions { on_error: OnError::Abort, ..options(&dir) };
+  let plan = plan_renames(&paths, &opts);
+
+    // a file that shows up after planning blocks the first rename
+  std::fs::write(dir.path().join("2021_03_15-0001.jpg"), b"").unwrap();
+  let results = execute_plan(&plan, &opts);
+  assert_eq!(results.len(), 1);
+  assert!(results[0].outcome.is_err());
+  assert!(paths[1].exists());
+
+  let opts = RenameOptions { on_error: OnError::Skip, ..options(&dir) };
+  let results = execute_plan(&plan, &opts);
+  assert_eq!(results.len(), 2);
+  assert!(results[1].outcome.is_ok());
+}
+
+#[test]
+fn files_can_swap_names() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "2021_03_15-0002.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "2021_03_15-0001.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+  let first = std::fs::read(&paths[0]).unwrap();
+  let opts = RenameOptions { force: true, ..options(&dir) };
+
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("2021_03_15-0002.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("2021_03_15-0001.jpg".to_string(), "2021_03_15-0002.jpg".to_string()),
+  ]);
+
+  let results = execute_plan(&plan_renames(&paths, &opts), &opts);
+  assert!(results.iter().all(|result| result.outcome.is_ok()));
+  assert_eq!(std::fs::read(dir.path().join("2021_03_15-0001.jpg")).unwrap(), first);
+  assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn files_are_not_lost_when_a_run_stops_halfway() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "2021_03_15-0002.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "2021_03_15-0001.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+  let contents: Vec<Vec<u8>> = paths.iter().map(|path| std::fs::read(path).unwrap()).collect();
+  let opts = RenameOptions { force: true, ..options(&dir) };
+  let all_contents = || -> Vec<Vec<u8>> {
+    let mut all: Vec<Vec<u8>> = std::fs::read_dir(dir.path()).unwrap().map(|entry| std::fs::read(entry.unwrap().path()).unwrap()).collect();
+    all.sort();
+    all
+  };
+
+    // the files swap names, but the run stops after the first one, whose old name the second one can't get back
+  let results = execute_plan_with_progress(&plan_renames(&paths, &opts), &opts, &mut |_| false);
+  assert_eq!(results.len(), 2);
+  assert!(results[0].outcome.is_ok());
+  match results[1].outcome {
+    Err(RenameError::NotMovedBack { ref temporary, error: None, .. }) => assert_eq!(std::fs::read(temporary).unwrap(), contents[1]),
+    ref outcome => panic!("unexpected outcome {:?}", outcome),
+  }
+  let mut expected = contents.clone();
+  expected.sort();
+  assert_eq!(all_contents(), expected);
+
+    // a chain of renames, whose last one fails because its destination was taken after planning
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![dir.path().join("a.jpg"), dir.path().join("b.jpg")];
+  std::fs::write(&paths[0], b"a").unwrap();
+  std::fs::write(&paths[1], b"b").unwrap();
+  let blocker = dir.path().join("c.jpg");
+  let mut plan = plan_renames(&paths, &options(&dir));
+  plan[0].action = PlanAction::Rename(paths[1].clone());
+  plan[1].action = PlanAction::Rename(blocker.clone());
+  std::fs::write(&blocker, b"c").unwrap();
+
+  let results = execute_plan(&plan, &options(&dir));
+  assert!(results[0].outcome.is_ok());
+  let temporary = match results[1].outcome {
+    Err(RenameError::NotMovedBack { ref temporary, error: Some(_), .. }) => temporary,
+    ref outcome => panic!("unexpected outcome {:?}", outcome),
+  };
+  assert_eq!(std::fs::read(&paths[1]).unwrap(), b"a");
+  assert_eq!(std::fs::read(temporary).unwrap(), b"b");
+  assert_eq!(std::fs::read(&blocker).unwrap(), b"c");
+  assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+}
+
+#[test]
+fn flattening_keeps_identical_files_and_subdir_names() {
+  let dir = tempfile::tempdir().unwrap();
+  let day = dir.path().join("trip").join("day 1");
+  std::fs::create_dir_all(&day).unwrap();
+  let exif = Exif::new().datetime("2021:03:15 09:00:00");
+  write_jpeg(dir.path(), "2021_03_15-0001.jpg", &exif);
+  let paths = vec![write_jpeg(&day, "a.jpg", &exif)];
+
+  assert!(planned_names(&paths, &options(&dir))[0].1.starts_with("skipped: An identical file already exists"));
+  let opts = RenameOptions { flat: true, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[0].1, "2021_03_15-0001-2.jpg");
+
+  let opts = RenameOptions { template: Template::parse("{subdir}_{n}").unwrap(), input_dir: dir.path().to_path_buf(), ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[0].1, "trip-day 1_1.jpg");
+}
+
+#[test]
+fn smaller_or_identical_files_are_overwritten_when_asked_to() {
+  let dir = tempfile::tempdir().unwrap();
+  let sub = dir.path().join("sub");
+  std::fs::create_dir(&sub).unwrap();
+  let identical = Exif::new().datetime("2021:03:17 09:00:00");
+  let paths = vec![
+    write_jpeg(&sub, "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(&sub, "b.jpg", &Exif::new().datetime("2021:03:16 09:00:00")),
+    write_jpeg(&sub, "c.jpg", &identical),
+  ];
+  std::fs::write(dir.path().join("2021_03_15-0001.jpg"), "smaller").unwrap();
+  std::fs::write(dir.path().join("2021_03_16-0001.jpg"), vec![0u8; 100_000]).unwrap();
+  write_jpeg(dir.path(), "2021_03_17-0001.jpg", &identical);
+
+  assert_eq!(planned_names(&paths, &options(&dir)), vec![
+    ("a.jpg".to_string(), "2021_03_15-0001-2.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_16-0001-2.jpg".to_string()),
+    ("c.jpg".to_string(), "skipped: An identical file already exists as ".to_string() + &dir.path().join("2021_03_17-0001.jpg").display().to_string()),
+  ]);
+
+    // the bigger file stays, as it may be the better copy
+  let opts = RenameOptions { overwrite: true, ..options(&dir) };
+  let mut plan = plan_renames(&paths, &opts);
+  assert!(check_destinations(&mut plan, false, true).is_empty());
+  let names: Vec<String> = plan.iter().filter_map(|entry| match entry.action {
+    PlanAction::Rename(ref dest) => Some(file_name(dest)),
+    PlanAction::Skip(_) => None,
+  }).collect();
+  assert_eq!(names, vec!["2021_03_15-0001.jpg", "2021_03_16-0001-2.jpg", "2021_03_17-0001.jpg"]);
+  assert_eq!(plan.iter().filter(|entry| entry.replaces).count(), 2);
+
+  let results = execute_plan(&plan, &opts);
+  assert!(results.iter().all(|result| result.outcome.is_ok()));
+  assert_ne!(std::fs::read(dir.path().join("2021_03_15-0001.jpg")).unwrap(), b"smaller");
+  assert_eq!(std::fs::read(dir.path().join("2021_03_16-0001.jpg")).unwrap().len(), 100_000);
+  assert_eq!(std::fs::read_dir(&sub).unwrap().count(), 0);
+}
+
+#[test]
+fn files_that_should_have_moved_out_of_the_way_are_not_overwritten() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "2021_03_15-0001.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+  let mut bigger = std::fs::read(&paths[0]).unwrap();
+  bigger.extend_from_slice(&[0u8; 1000]);
+  std::fs::write(&paths[0], bigger).unwrap();
+  let opts = RenameOptions { overwrite: true, force: true, ..options(&dir) };
+  let mut plan = plan_renames(&paths, &opts);
+  assert!(check_destinations(&mut plan, false, true).is_empty());
+  assert!(plan.iter().all(|entry| !entry.replaces));
+
+    // the smaller file was to be renamed to make room, but changes before it can be, so it has to stay
+  let kept = std::fs::read(&paths[1]).unwrap();
+  let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_615_806_000);
+  std::fs::File::options().write(true).open(&paths[1]).unwrap().set_modified(modified).unwrap();
+  let results = execute_plan(&plan, &opts);
+  assert!(results.iter().all(|result| result.outcome.is_err()));
+  assert_eq!(std::fs::read(&paths[1]).unwrap(), kept);
+  assert!(paths[0].exists());
+}
+
+#[test]
+fn parent_folder_names_can_be_part_of_the_name() {
+  let dir = tempfile::tempdir().unwrap();
+  let hike = dir.path().join("2021").join("Hike: Alps");
+  std::fs::create_dir_all(&hike).unwrap();
+  let paths = vec![
+    write_jpeg(&hike, "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+  let opts = RenameOptions { template: Template::parse("{year}_{month}_{day}-{parent}-{n}").unwrap(), input_dir: dir.path().to_path_buf(), ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2021_03_15-Hike_ Alps-1.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_15--2.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn files_that_change_after_planning_are_left_alone() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+  let opts = options(&dir);
+  let plan = plan_renames(&paths, &opts);
+  std::fs::remove_file(&paths[0]).unwrap();
+  let modified = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+  std::fs::File::options().write(true).open(&paths[1]).unwrap().set_modified(modified).unwrap();
+
+  let results = execute_plan(&plan, &opts);
+  assert!(matches!(results[0].outcome, Err(RenameError::SourceChanged)));
+  assert!(matches!(results[1].outcome, Err(RenameError::SourceChanged)));
+  assert!(results[2].outcome.is_ok());
+  assert!(paths[1].is_file());
+  assert!(dir.path().join("2021_03_15-0003.jpg").is_file());
+}
+
+#[test]
+fn duplicates_are_skipped() {
+  let dir = tempfile::tempdir().unwrap();
+  let sub = dir.path().join("sub");
+  std::fs::create_dir(&sub).unwrap();
+  let exif = Exif::new().datetime("2021:03:15 09:00:00");
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &exif),
+    write_jpeg(&sub, "a.jpg", &exif),
+    write_jpeg(&sub, "b.jpg", &exif.field(exif::Tag::Make, "Datier")),
+  ];
+
+  let opts = RenameOptions { dedupe: true, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2021_03_15-0001-a.jpg".to_string()),
+    ("a.jpg".to_string(), format!("skipped: Duplicate of {}", paths[0].display())),
+    ("b.jpg".to_string(), "2021_03_15-0001-b.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn normalized_extensions() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "PHOTO.JPEG", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "b.Tif", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "2021_03_15-0003.JPG", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+
+  assert_eq!(planned_names(&paths, &options(&dir))[0].1, "2021_03_15-0001.JPEG");
+  let opts = RenameOptions { normalize_ext: true, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("PHOTO.JPEG".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("b.Tif".to_string(), "2021_03_15-0002.tiff".to_string()),
+    ("2021_03_15-0003.JPG".to_string(), "2021_03_15-0003.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn conflicting_destinations_are_skipped() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+  let mut plan = plan_renames(&paths, &options(&dir));
+  assert!(check_destinations(&mut plan, false, false).is_empty());
+
+    // plans can also be put together by hand, which makes it possible to have conflicts
+  let dest = dir.path().join("x.jpg");
+  plan[0].action = PlanAction::Rename(dest.clone());
+  plan[1].action = PlanAction::Rename(dest.clone());
+  plan[2].action = PlanAction::Rename(paths[0].clone());
+  assert_eq!(check_destinations(&mut plan, true, false), vec![paths[0].clone(), dest]);
+  assert!(plan.iter().all(|entry| matches!(entry.action, PlanAction::Skip(SkipReason::Conflict(_)))));
+}
+
+#[test]
+fn dates_from_file_names() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "Scan_2019-06-01.jpg", &Exif::new()),
+    write_jpeg(dir.path(), "Scan_2019-02-30.jpg", &Exif::new()),
+    write_jpeg(dir.path(), "2019-06-01_exif.jpg", &Exif::new().datetime("2019:06:01 09:00:00")),
+    write_jpeg(dir.path(), "Screenshot 2019-06-01 at 08.30.00.jpg", &Exif::new()),
+  ];
+  let pattern = parse_name_pattern(r"(?P<year>\d{4})-(?P<month>\d\d)-(?P<day>\d\d)( at (?P<hour>\d\d)\.(?P<minute>\d\d)\.(?P<second>\d\d))?").unwrap();
+  let opts = RenameOptions { parse_name: Some(pattern), ..options(&dir) };
+
+  let names = planned_names(&paths, &opts);
+  assert_eq!(names[0].1, "2019_06_01-0001.jpg");
+  assert!(names[1].1.starts_with("skipped:"));
+  assert_eq!(names[2].1, "2019_06_01-0003.jpg");
+  assert_eq!(names[3].1, "2019_06_01-0002.jpg");
+  assert!(parse_name_pattern(r"(?P<year>\d{4})-(?P<month>\d\d)").is_err());
+
+    // a file that can't be read is pointed out, rather than dated by its name
+  let broken = dir.path().join("Scan_2019-06-02.jpg");
+  std::fs::write(&broken, b"GIF89a").unwrap();
+  assert_eq!(planned_names(&[broken], &opts)[0].1, "skipped: Unsupported file format: the file is a GIF image");
+}
+
+#[cfg(unix)]
+#[test]
+fn emitted_scripts_do_the_renames() {
+  let dir = tempfile::tempdir().unwrap();
+  let sub = dir.path().join("it's a \"dir\" $HOME");
+  std::fs::create_dir(&sub).unwrap();
+  let paths = vec![
+    write_jpeg(&sub, "2021_03_15-0002.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(&sub, "2021_03_15-0001.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(&sub, "o'neil & `co`.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+  let opts = RenameOptions { target_dir: None, group_by: Some(GroupBy::Year), ..options(&dir) };
+  let plan = plan_renames(&paths, &opts);
+  let first = std::fs::read(&paths[0]).unwrap();
+  let script = dir.path().join("rename.sh");
+  write_script(&plan, false, &script, ScriptStyle::for_path(&script)).unwrap();
+  assert!(paths.iter().all(|path| path.exists()));
+
+  let status = std::process::Command::new("sh").arg(&script).status().unwrap();
+  assert!(status.success());
+  let year = sub.join("2021");
+  let mut names: Vec<String> = std::fs::read_dir(&year).unwrap().map(|entry| file_name(&entry.unwrap().path())).collect();
+  names.sort();
+  assert_eq!(names, vec!["2021_03_15-0001.jpg", "2021_03_15-0002.jpg", "2021_03_15-0003.jpg"]);
+  assert_eq!(std::fs::read(year.join("2021_03_15-0001.jpg")).unwrap(), first);
+}
+
+#[test]
+fn counters_continue_after_existing_files() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("new");
+  std::fs::create_dir(&input).unwrap();
+  for name in ["2021_03_15-0001.jpg", "2021_03_15-0005.jpg", "2021_03_15-0002-b.jpg", "2021_03_16-0002.jpg", "holiday-0009.jpg"] {
+    write_jpeg(dir.path(), name, &Exif::new());
+  }
+  let paths = vec![
+    write_jpeg(&input, "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(&input, "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(&input, "c.jpg", &Exif::new().datetime("2021:03:16 09:00:00")),
+    write_jpeg(&input, "d.jpg", &Exif::new().datetime("2021:03:17 09:00:00")),
+  ];
+
+  let opts = RenameOptions { continue_counter: true, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2021_03_15-0006.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_15-0007.jpg".to_string()),
+    ("c.jpg".to_string(), "2021_03_16-0003.jpg".to_string()),
+    ("d.jpg".to_string(), "2021_03_17-0001.jpg".to_string()),
+  ]);
+  assert_eq!(planned_names(&paths, &options(&dir))[0].1, "2021_03_15-0001-2.jpg");
+}
+
+#[test]
+fn auto_width_pads_to_the_largest_counter_of_each_day() {
+  let dir = tempfile::tempdir().unwrap();
+  let mut paths: Vec<PathBuf> = (0..3).map(|i| write_jpeg(dir.path(), &format!("a{}.jpg", i), &Exif::new().datetime(&format!("2021:03:15 09:00:0{}", i)))).collect();
+  paths.extend((0..10).map(|i| write_jpeg(dir.path(), &format!("b{}.jpg", i), &Exif::new().datetime(&format!("2021:03:16 09:00:0{}", i)))));
+  let mut template = Template::parse("{year}_{month}_{day}-{n:4}").unwrap();
+  template.set_auto_width(true);
+  let opts = RenameOptions { template, ..options(&dir) };
+
+  let names: Vec<String> = planned_names(&paths, &opts).into_iter().map(|(_, new_name)| new_name).collect();
+  assert_eq!(&names[..4], ["2021_03_15-1.jpg", "2021_03_15-2.jpg", "2021_03_15-3.jpg", "2021_03_16-01.jpg"]);
+  assert_eq!(names[12], "2021_03_16-10.jpg");
+
+    // names with fewer digits than the template's width are still recognized
+  let named = vec![write_jpeg(dir.path(), "2021_03_17-1.jpg", &Exif::new().datetime("2021:03:17 09:00:00"))];
+  assert_eq!(planned_names(&named, &opts)[0].1, "skipped: Already named correctly");
+}
+
+#[test]
+fn transactional_renames_are_undone_after_a_failure() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "2021_03_15-0002.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+    write_jpeg(dir.path(), "2021_03_15-0001.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+  let contents: Vec<Vec<u8>> = paths.iter().map(|path| std::fs::read(path).unwrap()).collect();
+  let opts = RenameOptions { transactional: true, force: true, ..options(&dir) };
+  let plan = plan_renames(&paths, &opts);
+
+    // the first two files swap names, and then the last one is blocked
+  std::fs::write(dir.path().join("2021_03_15-0003.jpg"), b"").unwrap();
+  let results = execute_plan(&plan, &opts);
+  assert_eq!(results.len(), 3);
+  assert!(results.iter().all(|result| result.outcome.is_err()));
+  assert_eq!(results.iter().filter(|result| matches!(result.outcome, Err(RenameError::RolledBack))).count(), 2);
+  for (path, contents) in paths.iter().zip(contents) {
+    assert_eq!(std::fs::read(path).unwrap(), contents);
+  }
+}
+
+#[test]
+fn files_outside_of_the_size_range_are_skipped() {
+  let dir = tempfile::tempdir().unwrap();
+  let sized = |width: u32, height: u32| Exif::new().datetime("2021:03:15 09:00:00")
+    .value(exif::Tag::PixelXDimension, Value::Long(vec![width]))
+    .value(exif::Tag::PixelYDimension, Value::Long(vec![height]));
+  let paths = vec![
+    write_jpeg(dir.path(), "photo.jpg", &sized(4000, 3000)),
+    write_jpeg(dir.path(), "portrait.jpg", &sized(3000, 4000)),
+    write_jpeg(dir.path(), "thumb.jpg", &sized(160, 120)),
+    write_jpeg(dir.path(), "unknown.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+  ];
+
+  let opts = RenameOptions { min_dimensions: Some("640x480".parse().unwrap()), ..options(&dir) };
+  let names = planned_names(&paths, &opts);
+  assert!(!names[0].1.starts_with("skipped"));
+  assert!(!names[1].1.starts_with("skipped"));
+  assert_eq!(names[2].1, "skipped: Its size of 160x120 is below the minimum");
+  assert!(!names[3].1.starts_with("skipped"));
+
+  let opts = RenameOptions { max_dimensions: Some("3000x4000".parse().unwrap()), skip_unknown_dimensions: true, ..options(&dir) };
+  let names = planned_names(&paths, &opts);
+  assert!(!names[0].1.starts_with("skipped"));
+  assert!(!names[2].1.starts_with("skipped"));
+  assert_eq!(names[3].1, "skipped: Its size is unknown");
+  assert_eq!(PlanSummary::of(&plan_renames(&paths, &opts)).wrong_size, 1);
+  assert!("640".parse::<Dimensions>().is_err());
+}
+
+#[test]
+fn exported_plans_rename_copies_by_their_contents() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+  ];
+  let plan = plan_renames(&paths, &options(&dir));
+  let manifest_file = dir.path().join("plan.json");
+  Manifest::from_plan(&plan, dir.path()).unwrap().write(&manifest_file).unwrap();
+
+  let copies = tempfile::tempdir().unwrap();
+  let copy = |from: &PathBuf, name: &str| {
+    let path = copies.path().join(name);
+    std::fs::copy(from, &path).unwrap();
+    path
+  };
+  let copied = vec![
+    copy(&paths[0], "copy of a.jpg"),
+    copy(&paths[1], "IMG_0001.jpg"),
+    copy(&paths[1], "IMG_0001 (2).jpg"),
+    write_jpeg(copies.path(), "other.jpg", &Exif::new().datetime("2021:03:15 11:00:00")),
+  ];
+  let plan = Manifest::read(&manifest_file).unwrap().plan(&copied, copies.path(), &|| {});
+  let names: Vec<String> = plan.iter().map(|entry| match entry.action {
+    PlanAction::Rename(ref dest) => file_name(dest),
+    PlanAction::Skip(ref reason) => format!("skipped: {}", reason),
+  }).collect();
+  assert_eq!(names[0], "2021_03_15-0002.jpg");
+  assert_eq!(names[1], "2021_03_15-0001.jpg");
+  assert_eq!(names[2], format!("skipped: Duplicate of {}", copied[1].display()));
+  assert_eq!(names[3], "skipped: Not in the plan file");
+
+  assert!(execute_plan(&plan, &options(&copies)).iter().all(|result| result.outcome.is_ok()));
+  assert_eq!(std::fs::read(copies.path().join("2021_03_15-0002.jpg")).unwrap(), std::fs::read(&paths[0]).unwrap());
+}
+
+#[test]
+fn prefix_and_suffix_go_around_every_name() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 10:00:00")),
+    write_jpeg(dir.path(), "Wedding_2021_03_15-0001_edit.jpg", &Exif::new().datetime("2021:03:15 09:00:00")),
+  ];
+  let mut template = Template::default();
+  template.add_affixes("Wedding_", " _edit");
+  let opts = RenameOptions { template, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "Wedding_2021_03_15-0002_edit.jpg".to_string()),
+    ("Wedding_2021_03_15-0001_edit.jpg".to_string(), "skipped: Already named correctly".to_string()),
+  ]);
+
+  let mut template = Template::default();
+  template.add_affixes("a/b:", "");
+  let opts = RenameOptions { template, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts)[0].1, "a_b_2021_03_15-0002.jpg");
+}
+
+#[test]
+fn counters_start_over_for_each_session() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 23:50:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:16 00:10:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:16 01:00:00")),
+    write_jpeg(dir.path(), "d.jpg", &Exif::new().datetime("2021:03:16 01:30:00")),
+  ];
+  let opts = RenameOptions {
+    template: Template::parse("{year}_{month}_{day}-s{session}-{n:2}").unwrap(),
+    session_gap: Some(parse_duration("30m").unwrap()),
+    ..options(&dir)
+  };
+
+    // the first two are one session across midnight, and exactly 30 minutes isn't a gap
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("a.jpg".to_string(), "2021_03_15-s1-01.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_16-s1-02.jpg".to_string()),
+    ("c.jpg".to_string(), "2021_03_16-s2-01.jpg".to_string()),
+    ("d.jpg".to_string(), "2021_03_16-s2-02.jpg".to_string()),
+  ]);
+
+  let renamed = vec![write_jpeg(dir.path(), "2021_03_16-s7-01.jpg", &Exif::new().datetime("2021:03:16 01:00:00"))];
+  assert_eq!(planned_names(&renamed, &opts)[0].1, "skipped: Already named correctly");
+}
+
+#[test]
+fn offsets_are_collected_even_when_not_used() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 10:00:00").field(exif::Tag::OffsetTime, "+02:00")),
+    write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 11:00:00").field(exif::Tag::OffsetTime, "-05:00")),
+    write_jpeg(dir.path(), "c.jpg", &Exif::new().datetime("2021:03:15 12:00:00").field(exif::Tag::OffsetTime, "+02:00")),
+    write_jpeg(dir.path(), "d.jpg", &Exif::new().datetime("2021:03:15 13:00:00")),
+  ];
+  let plan = plan_renames(&paths, &options(&dir));
+  assert_eq!(distinct_offsets(&plan), vec![-300, 120]);
+  assert!(plan.iter().all(|entry| entry.datetime.as_ref().unwrap().0.offset.is_none()));
+  assert_eq!(format_offset(-300), "-05:00");
+
+  let same = plan_renames(&paths[..1], &options(&dir));
+  assert_eq!(distinct_offsets(&same), vec![120]);
+}
+
+#[test]
+fn offsets_are_only_read_with_one_sign_and_digits() {
+  assert_eq!(parse_offset("+02:00"), Ok(120));
+  assert_eq!(parse_offset("-0530"), Ok(-330));
+  assert_eq!(parse_offset("+5"), Ok(300));
+  assert_eq!(parse_offset("UTC"), Ok(0));
+  for offset in ["+é1", "+1é", "+5:-30", "+-3", "-+3", "+", "+:30", "+5:", "+1:2:3", "+15", "+05:60", "02:00", "+99999999"] {
+    assert!(parse_offset(offset).is_err(), "{}", offset);
+  }
+}
+
+#[test]
+fn deep_searches_can_be_limited_in_depth() {
+  let dir = tempfile::tempdir().unwrap();
+  let nested = dir.path().join("a").join("b");
+  std::fs::create_dir_all(&nested).unwrap();
+  let exif = Exif::new().datetime("2021:03:15 10:00:00");
+  let top = write_jpeg(dir.path(), "top.JPG", &exif);
+  let middle = write_jpeg(&dir.path().join("a"), "middle.jpg", &exif);
+  let bottom = write_jpeg(&nested, "bottom.jpg", &exif);
+  let extensions = vec![String::from("jpg")];
+
+  let found = |deep, max_depth| find_files(dir.path(), &extensions, deep, max_depth, false).unwrap().paths;
+  assert_eq!(found(false, None), vec![top.clone()]);
+  assert_eq!(found(true, Some(0)), vec![top.clone()]);
+  assert_eq!(found(true, Some(1)), vec![middle.clone(), top.clone()]);
+  assert_eq!(found(true, None), vec![bottom, middle, top]);
+}
+
+#[test]
+fn other_files_can_be_stashed() {
+  let dir = tempfile::tempdir().unwrap();
+  let sub = dir.path().join("sub");
+  std::fs::create_dir(&sub).unwrap();
+  write_jpeg(dir.path(), "a.JPG", &Exif::new().datetime("2021:03:15 09:00:00"));
+  for path in [dir.path().join("GH010001.LRV"), dir.path().join("a.xmp"), sub.join("MISC.CTG")] {
+    std::fs::write(path, "clutter").unwrap();
+  }
+  let extensions = vec!["jpg".to_string(), "xmp".to_string()];
+  let names = |deep: bool| -> Vec<String> {
+    let mut names: Vec<String> = find_other_files(dir.path(), &extensions, deep, None, false).unwrap().paths.iter().map(|path| file_name(path)).collect();
+    names.sort();
+    names
+  };
+  assert_eq!(names(false), vec!["GH010001.LRV"]);
+  assert_eq!(names(true), vec!["GH010001.LRV", "MISC.CTG"]);
+
+  let stash = dir.path().join("stash");
+  let others = find_other_files(dir.path(), &extensions, true, None, false).unwrap().paths;
+  let results = stash_files(&others, dir.path(), &stash);
+  assert!(results.iter().all(|result| result.outcome.is_ok()));
+  assert!(stash.join("GH010001.LRV").is_file());
+  assert!(stash.join("sub").join("MISC.CTG").is_file());
+  assert!(!sub.join("MISC.CTG").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_only_followed_when_asked_to() {
+  use std::os::unix::fs::symlink;
+
+  let dir = tempfile::tempdir().unwrap();
+  let sub = dir.path().join("sub");
+  std::fs::create_dir(&sub).unwrap();
+  let exif = Exif::new().datetime("2021:03:15 10:00:00");
+  let photo = write_jpeg(&sub, "photo.jpg", &exif);
+  symlink(&photo, dir.path().join("link.jpg")).unwrap();
+  symlink(&sub, dir.path().join("linked")).unwrap();
+    // a loop, which must not be searched forever
+  symlink(dir.path(), sub.join("loop")).unwrap();
+  let extensions = vec![String::from("jpg")];
+
+  let found = |deep, follow| find_files(dir.path(), &extensions, deep, None, follow).unwrap();
+  assert!(found(false, false).paths.is_empty());
+  assert_eq!(found(true, false).paths, vec![photo.clone()]);
+  assert_eq!(found(false, true).paths, vec![dir.path().join("link.jpg")]);
+  let followed = found(true, true);
+  assert_eq!(followed.paths, vec![dir.path().join("link.jpg")]);
+  assert!(!followed.errors.is_empty());
+}
+
+#[test]
+fn original_names_are_kept_in_an_extended_attribute() {
+  let dir = tempfile::tempdir().unwrap();
+  let path = write_jpeg(dir.path(), "IMG_0001.JPG", &Exif::new().datetime("2021:03:15 10:00:00"));
+  let renamed = dir.path().join("2021_03_15-01.jpg");
+  std::fs::rename(&path, &renamed).unwrap();
+  match keep_original_name(&renamed, &path) {
+    Err(ref error) if error.kind() == std::io::ErrorKind::Unsupported => return, // e.g. on tmpfs
+    result => result.unwrap(),
+  }
+  assert_eq!(original_name(&renamed).unwrap(), Some("IMG_0001.JPG".into()));
+
+    // renaming it again keeps the first name
+  keep_original_name(&renamed, &renamed).unwrap();
+  assert_eq!(original_name(&renamed).unwrap(), Some("IMG_0001.JPG".into()));
+}
+
+#[test]
+fn dates_are_converted_to_the_target_time_zone() {
+  let dir = tempfile::tempdir().unwrap();
+  let paths = vec![
+    write_jpeg(dir.path(), "tokyo.jpg", &Exif::new().datetime("2021:03:15 10:00:00").field(exif::Tag::OffsetTime, "+09:00")),
+    write_jpeg(dir.path(), "new_york.jpg", &Exif::new().datetime("2021:03:15 01:00:00").field(exif::Tag::OffsetTime, "-05:00")),
+    write_jpeg(dir.path(), "unknown.jpg", &Exif::new().datetime("2021:03:15 03:00:00")),
+    write_jpeg(dir.path(), "summer.jpg", &Exif::new().datetime("2021:07:01 12:00:00").field(exif::Tag::OffsetTime, "Z")),
+  ];
+  let opts = RenameOptions {
+    template: Template::parse("{year}_{month}_{day}-{hour}{minute}-{n}").unwrap(),
+    read: ReadOptions { to_timezone: Some(chrono_tz::Europe::Berlin), ..ReadOptions::default() },
+    ..options(&dir)
+  };
+
+    // they are also ordered by the converted time, which puts new_york last on its day
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("tokyo.jpg".to_string(), "2021_03_15-0200-1.jpg".to_string()),
+    ("new_york.jpg".to_string(), "2021_03_15-0700-3.jpg".to_string()),
+    ("unknown.jpg".to_string(), "2021_03_15-0400-2.jpg".to_string()),
+    ("summer.jpg".to_string(), "2021_07_01-1400-1.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn files_with_the_same_contents_are_grouped() {
+  let dir = tempfile::tempdir().unwrap();
+  let a = write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00"));
+  let b = write_jpeg(dir.path(), "b.jpg", &Exif::new().datetime("2021:03:15 10:00:00"));
+  let a_copy = write_jpeg(dir.path(), "a_copy.jpg", &Exif::new().datetime("2021:03:15 09:00:00"));
+  let b_copy = write_jpeg(dir.path(), "b_copy.jpg", &Exif::new().datetime("2021:03:15 10:00:00"));
+  let single = write_jpeg(dir.path(), "single.jpg", &Exif::new().datetime("2021:03:15 11:00:00").field(exif::Tag::Make, "Datier"));
+
+  let paths = vec![b.clone(), a.clone(), single, a_copy.clone(), b_copy.clone(), dir.path().join("missing.jpg")];
+  assert_eq!(find_duplicates(&paths, &|| {}), vec![vec![b, b_copy], vec![a, a_copy]]);
+}
+
+#[test]
+fn ties_are_ordered_by_image_number_then_creation_time() {
+  let dir = tempfile::tempdir().unwrap();
+  let burst = |number: u32| Exif::new().datetime("2021:03:15 09:00:00").value(IMAGE_NUMBER, Value::Long(vec![number]));
+    // written in the order they were taken, which isn't the order of their names
+  let paths = vec![
+    write_jpeg(dir.path(), "c.jpg", &burst(7)),
+    write_jpeg(dir.path(), "a.jpg", &burst(8)),
+    write_jpeg(dir.path(), "b.jpg", &burst(9)),
+  ];
+  let opts = RenameOptions { tie_break: TieBreak::KeepBothIncrement, ..options(&dir) };
+  assert_eq!(planned_names(&paths, &opts), vec![
+    ("c.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+    ("a.jpg".to_string(), "2021_03_15-0002.jpg".to_string()),
+    ("b.jpg".to_string(), "2021_03_15-0003.jpg".to_string()),
+  ]);
+
+    // without an ImageNumber, the creation time decides where the file system has one
+  let unnumbered = tempfile::tempdir().unwrap();
+  let second = write_jpeg(unnumbered.path(), "second.jpg", &Exif::new().datetime("2021:03:15 09:00:00"));
+  std::thread::sleep(std::time::Duration::from_millis(20));
+  let first = write_jpeg(unnumbered.path(), "first.jpg", &Exif::new().datetime("2021:03:15 09:00:00"));
+  if std::fs::metadata(&first).and_then(|metadata| metadata.created()).is_err() {
+    return;
+  }
+  let opts = RenameOptions { tie_break: TieBreak::KeepBothIncrement, ..options(&unnumbered) };
+  assert_eq!(planned_names(&[first, second], &opts), vec![
+    ("first.jpg".to_string(), "2021_03_15-0002.jpg".to_string()),
+    ("second.jpg".to_string(), "2021_03_15-0001.jpg".to_string()),
+  ]);
+}
+
+#[test]
+fn cached_dates_are_read_again_when_the_file_changes() {
+  let dir = tempfile::tempdir().unwrap();
+  let cache_file = dir.path().join("cache.json");
+  let path = write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2021:03:15 09:00:00"));
+  let opts = ReadOptions::default();
+    // the date the cache returns in a run with the given options, which then saves the cache
+  let cached = |opts: &ReadOptions| {
+    let cache = MetadataCache::load(cache_file.clone(), opts);
+    let datetime = cache.get_metadata(&path, opts).unwrap().datetime.to_string();
+    cache.save().unwrap();
+    datetime
+  };
+  let set_modified = |time| std::fs::File::options().write(true).open(&path).unwrap().set_modified(time).unwrap();
+  assert_eq!(cached(&opts), "2021-03-15 09:00:00");
+  let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+    // other contents, but the same size and modification time
+  write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2022:03:15 09:00:00"));
+  set_modified(modified);
+  assert_eq!(cached(&opts), "2021-03-15 09:00:00");
+
+    // only the modification time changes
+  let modified = modified + std::time::Duration::from_secs(1);
+  set_modified(modified);
+  assert_eq!(cached(&opts), "2022-03-15 09:00:00");
+
+    // only the size changes
+  write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2023:03:15 09:00:00").subsec("5"));
+  set_modified(modified);
+  assert_eq!(cached(&opts), "2023-03-15 09:00:00");
+
+    // the cache was saved with other read options, and then only has the entries read with those
+  write_jpeg(dir.path(), "a.jpg", &Exif::new().datetime("2024:03:15 09:00:00").subsec("5"));
+  set_modified(modified);
+  assert_eq!(cached(&opts), "2023-03-15 09:00:00");
+  let shifted = ReadOptions { shift: 60, ..ReadOptions::default() };
+  assert_eq!(cached(&shifted), "2024-03-15 09:01:00");
+  assert_eq!(cached(&opts), "2024-03-15 09:00:00");
+
+  MetadataCache::clear(&cache_file).unwrap();
+  assert!(!cache_file.exists());
+  MetadataCache::clear(&cache_file).unwrap();
+}